@@ -0,0 +1,61 @@
+//! Convert maps to the current map format (e.g. legacy `.json.gz` maps).
+use game::prelude::*;
+use std::fs;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+struct Args {
+	/// Output file. Default: input file with extension `.map`.
+	#[structopt(short, long)]
+	pub output: Option<PathBuf>,
+
+	/// Overwrite the output file if it exists.
+	#[structopt(short, long)]
+	pub force: bool,
+
+	/// Map files to convert.
+	pub input: Vec<PathBuf>,
+}
+
+fn main() -> Result<()> {
+	let args = Args::from_args();
+	if args.output.is_some() && args.input.len() != 1 {
+		return err("--output requires exactly one input file");
+	}
+
+	for input in &args.input {
+		let output = match &args.output {
+			Some(output) => output.clone(),
+			None => default_output(input),
+		};
+		if output.exists() && !args.force {
+			return err(format!(
+				"convert {}: {} already exists (use --force to overwrite)",
+				input.to_string_lossy(),
+				output.to_string_lossy()
+			));
+		}
+
+		let data = fs::read(input)?;
+		let version = map_format_version(&data)?;
+		let map = Map::from_bytes(&data)?;
+		map.save(&output)?;
+		println!(
+			"{} (format v{}, {} bytes) -> {} (format v{}, {} bytes)",
+			input.to_string_lossy(),
+			version,
+			data.len(),
+			output.to_string_lossy(),
+			MAP_FORMAT_VERSION,
+			fs::metadata(&output)?.len()
+		);
+	}
+	Ok(())
+}
+
+// `foo.json.gz` -> `foo.map`
+fn default_output(input: &Path) -> PathBuf {
+	let name = input.file_name().unwrap_or_default().to_string_lossy();
+	let stem = name.strip_suffix(".json.gz").or_else(|| name.strip_suffix(".map")).unwrap_or(&name);
+	input.with_file_name(format!("{}.map", stem))
+}
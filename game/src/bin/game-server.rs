@@ -1,4 +1,5 @@
 use game::prelude::*;
+use game::{error, info};
 use std::fs;
 use std::io::BufWriter;
 use std::process::exit;
@@ -14,19 +15,60 @@ struct Args {
 	#[structopt(short, long)]
 	pub create: Option<u32>,
 
+	/// With --create: make an unbounded map, which can grow in any direction.
+	#[structopt(long)]
+	pub unbounded: bool,
+
 	/// Auto-save map whenever a client drops out.
 	#[structopt(long)]
 	pub autosave: bool,
 
+	/// Serve status queries on this address (e.g. 127.0.0.1:3345).
+	#[structopt(long)]
+	pub status_addr: Option<String>,
+
+	/// Announce the server on the LAN, for server browsers.
+	#[structopt(long)]
+	pub announce: bool,
+
+	/// Record the game to this file (watch with `game-client --replay`).
+	#[structopt(long)]
+	pub record: Option<PathBuf>,
+
+	/// Make voxels that are no longer connected to the ground fall down.
+	#[structopt(long)]
+	pub collapse: bool,
+
+	/// With --collapse: larger clusters of voxels are assumed to be supported.
+	#[structopt(long, default_value = "4096")]
+	pub collapse_max_cluster: usize,
+
+	/// With --collapse: maximum number of support checks (flood fills) per tick.
+	#[structopt(long, default_value = "32")]
+	pub collapse_max_fills: usize,
+
+	/// Voxel types data file, instead of the built-in types.
+	/// Clients must use the same file.
+	#[structopt(long)]
+	pub voxels: Option<PathBuf>,
+
+	/// Log levels, e.g. "info" or "warn,server=debug".
+	#[structopt(long, default_value = "info")]
+	pub log: String,
+
+	/// Log as JSON, one object per line.
+	#[structopt(long)]
+	pub log_json: bool,
+
 	/// Map file to open
 	pub map_file: String,
 }
 
 fn main() {
 	match main_result() {
-		Ok(()) => eprintln!("server exited successfully"),
+		Ok(()) => info!("server exited successfully"),
 		Err(e) => {
-			eprintln!("error: {}", e);
+			error!("{}", e);
 			exit(1)
 		}
 	}
@@ -34,24 +76,43 @@ fn main() {
 
 fn main_result() -> Result<()> {
 	let args = Args::from_args();
+	game::logging::init(&args.log, args.log_json)?;
+	if let Some(file) = &args.voxels {
+		VoxelTypes::init(file)?;
+	}
 	let map_file = PathBuf::from(&args.map_file);
 
 	if let Some(size) = args.create {
-		let map = Map::flat(uvec3(size, size, size));
+		let map = if args.unbounded { Map::flat_unbounded(size) } else { Map::flat(uvec3(size, size, size)) };
 		if map_file.exists() {
 			return err(&format!("create {}: file already exists", map_file.to_string_lossy()));
 		}
 		let f = fs::File::create(&args.map_file)?;
 		map.serialize(BufWriter::new(f))?;
-		println!("created {}, size {}x{}x{}", &args.map_file, size, size, size);
+		info!("created {}, size {}x{}x{}", &args.map_file, size, size, size);
 	}
 
 	if args.autosave {
-		println!("auto save ENABLED (edit mode)");
+		info!("auto save ENABLED (edit mode)");
 	} else {
-		println!("auto save DISABLED (play mode)");
+		info!("auto save DISABLED (play mode)");
 	}
 
-	println!("serving map {}", &map_file.to_string_lossy());
-	Server::serve(&args.addr, map_file, args.autosave)
+	info!("serving map {}", &map_file.to_string_lossy());
+	Server::serve(ServerOpts {
+		addr: Some(args.addr),
+		map_file,
+		autosave: args.autosave,
+		status_addr: args.status_addr,
+		announce: args.announce,
+		record: args.record,
+		collapse: if args.collapse {
+			Some(CollapseLimits {
+				max_cluster: args.collapse_max_cluster,
+				max_fills: args.collapse_max_fills,
+			})
+		} else {
+			None
+		},
+	})
 }
@@ -3,8 +3,8 @@ use game::procedural_worlds::*;
 use rand::Rng;
 
 fn main() -> Result<()> {
-	the_mountain().save("the_mountain.json.gz")?;
-	king_off_the_hill().save("king_of_the_hill.json.gz")?;
+	the_mountain().save("the_mountain.map")?;
+	king_off_the_hill().save("king_of_the_hill.map")?;
 	Ok(())
 }
 
@@ -116,7 +116,7 @@ fn irange(m: &mut Map, min: ivec3, max: ivec3, v: Voxel) {
 
 fn the_mountain() -> Map {
 	let mut m = Map::new(uvec3(256, 64, 256));
-	let (nx, _, nz) = m.size().as_ivec().into();
+	let (nx, _, nz) = m.bounds().size().into();
 	let mid = ivec3(nx / 2, 0, nz / 2);
 	let mid = mid.map(|v| v as f32);
 
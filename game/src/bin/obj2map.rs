@@ -66,7 +66,7 @@ fn main() -> Result<()> {
 
 	procedural_worlds::floor(&mut map, 4, Voxel::LAVA);
 
-	map.save("output.json.gz")
+	map.save("output.map")
 }
 
 // fn max_size(vertices: &[vec3]) -> f32 {
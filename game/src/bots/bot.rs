@@ -7,6 +7,7 @@ use std::time::{Duration, Instant};
 pub struct Bot {
 	client: Client,
 	time: Instant,
+	physics: FixedStep,
 }
 
 impl Bot {
@@ -14,9 +15,13 @@ impl Bot {
 
 	// TODO: run(client);
 	pub fn new(client: Client) -> Self {
-		let (x, y, z) = client.game_state().map().size().into();
-		println!("Hello {} x {} x {} world!", x, y, z);
-		Self { client, time: Instant::now() }
+		let (x, y, z) = client.game_state().map().bounds().size().into();
+		info!("Hello {} x {} x {} world!", x, y, z);
+		Self {
+			client,
+			time: Instant::now(),
+			physics: FixedStep::new(),
+		}
 	}
 
 	// Update the Bot's local clock
@@ -34,7 +39,6 @@ impl Bot {
 		loop {
 			sleep(Duration::from_millis(30));
 			let dt = self.update_time();
-			print!("\x1B[;H\x1B[J");
 			self.tick(dt);
 		}
 	}
@@ -49,7 +53,9 @@ impl Bot {
 		let mut updates = Updates::new();
 
 		self.control(&mut me, dt, &mut updates);
-		me.model.tick(dt, self.gs().map()); // TODO: tick(gs)
+		for _ in 0..self.physics.advance(dt) {
+			me.model.tick(FixedStep::DT, self.gs().map()); // TODO: tick(gs)
+		}
 
 		self.client.update_player(me);
 		self.client.send_updates(updates);
@@ -62,19 +68,17 @@ impl Bot {
 
 		if let Some(target) = self.closest_other_player() {
 			let target = target - vec3(0.0, 2.0, 0.0); // shoot at feet :)
-			println!("target: {:?}", target);
 			self.rotate_towards(me, dt, target);
 
 			let target_angle = angle_between(me.look_dir(), target - me.view_pos());
-			println!("target_angle: {}", target_angle);
+			trace!([aim = target, angle = target_angle] "aiming");
 			if target_angle < Self::SHOOT_CONE {
 				me.fire_weapon(dt, false /*alt*/, self.gs(), updates);
 			}
 
 			me.walk(dt, me.look_dir(), false, self.gs());
 		} else {
-			println!("target: None");
-			println!("target_angle: None");
+			trace!("no target");
 
 			me.jump(self.gs());
 		}
@@ -7,11 +7,20 @@ struct Args {
 	/// Server address
 	#[structopt(long, default_value = "localhost:3344")]
 	pub server: String,
+
+	/// Log levels, e.g. "info" or "warn,server=debug".
+	#[structopt(long, default_value = "info")]
+	pub log: String,
+
+	/// Log as JSON, one object per line.
+	#[structopt(long)]
+	pub log_json: bool,
 	// num bots etc.
 }
 
 pub fn main_loop() -> Result<()> {
 	let args = Args::from_args();
+	crate::logging::init(&args.log, args.log_json)?;
 
 	let skin = 2; // TODO
 	let client = Client::connect(&args.server, skin)?;
@@ -22,17 +22,29 @@ pub struct Client {
 	netpipe: NetPipe,      // bi-directional server connection
 	player_id: usize,      // our player ID on the server, the one we control
 	game_state: GameState, // local copy of the server's game state, continually catching up with server
+	spectator: bool,       // we only watch, and have no player in the game
+	recorder: Option<Recorder>,
 }
 
 impl Client {
 	/// Connect to server and join game.
 	/// TODO: pass PlayerOptions{skin, name, ..}
 	pub fn connect(server_addr: &str, player_skin: usize) -> Result<Self> {
-		// connect
-		let mut netpipe = NetPipe::new(TcpStream::connect(server_addr)?);
+		Self::join(NetPipe::new(TcpStream::connect(server_addr)?), player_skin, false)
+	}
+
+	/// Connect to server and watch the game as a spectator.
+	/// Spectators receive all updates, but have no player in the game
+	/// (`player` must not be called).
+	pub fn spectate(server_addr: &str) -> Result<Self> {
+		Self::join(NetPipe::new(TcpStream::connect(server_addr)?), 0, true)
+	}
 
+	/// Join the game over an existing connection,
+	/// e.g. to a server in the same process (see `LocalServer::connect`).
+	pub fn join(mut netpipe: NetPipe, skin: usize, spectator: bool) -> Result<Self> {
 		// join
-		netpipe.send(Message::Join { skin: player_skin });
+		netpipe.send(Message::Join { skin, spectator });
 
 		// receive state
 		let (game_state, player_id) = match netpipe.recv() {
@@ -41,7 +53,13 @@ impl Client {
 			Ok(bad_msg) => return err(format!("client: connect: got bad reply: {}", &bad_msg)),
 		};
 
-		Ok(Self { netpipe, player_id, game_state })
+		Ok(Self {
+			netpipe,
+			player_id,
+			game_state,
+			spectator,
+			recorder: None,
+		})
 	}
 
 	/// Advance our local time by `dt` seconds:
@@ -58,7 +76,7 @@ impl Client {
 				player.model.extrapolate(dt)
 			}
 		}
-		self.game_state.effects.tick(dt);
+		self.game_state.effects.tick(dt, &self.game_state.map);
 	}
 
 	/// Update our local player state,
@@ -80,13 +98,35 @@ impl Client {
 		self.netpipe.send(msg)
 	}
 
+	/// Record all updates received from now on to `file`
+	/// (see `Replay`).
+	pub fn record<P: AsRef<Path>>(&mut self, file: P) -> Result<()> {
+		self.recorder = Some(Recorder::create(file, &self.game_state, self.player_id)?);
+		Ok(())
+	}
+
 	// apply state updates from server.
 	// TODO: return Result<()> !!
 	fn receive_updates(&mut self) {
 		for msg in self.netpipe.try_iter() {
 			use Message::*;
+			if let Some(recorder) = &mut self.recorder {
+				if let Err(e) = recorder.record(&msg) {
+					error!("recording stopped: {}", e);
+					self.recorder = None;
+				}
+			}
 			match msg {
-				UpdateMap { index, voxel } => self.game_state.update_map(index, voxel),
+				UpdateMap { index, voxel, state } => self.game_state.update_map(index, voxel, state),
+				UpdateMapBatch(edits) => self.game_state.apply_map_edits(&edits),
+				DamagePlayer { player_id, damage } => {
+					// Only the client controlling the player applies damage, and sends the result with its next update.
+					if player_id == self.player_id && !self.spectator {
+						let mut player = self.game_state.players.get(player_id).clone();
+						player.take_damage(damage, self.game_state.map());
+						self.game_state.players.set(player_id, player);
+					}
+				}
 				UpdatePlayer { player_id, player } => {
 					// Ignore updates to the local player, who is updated client-side for smoother movement.
 					if player_id != self.player_id {
@@ -108,9 +148,15 @@ impl Client {
 
 	/// The local player, controlled by this client.
 	pub fn player(&self) -> &Player {
+		debug_assert!(!self.spectator);
 		self.game_state.players.get(self.player_id)
 	}
 
+	/// Are we only watching the game?
+	pub fn is_spectator(&self) -> bool {
+		self.spectator
+	}
+
 	/// Get the client's current view of the game state.
 	/// May lag behind the server view, except for the local Player state.
 	pub fn game_state(&self) -> &GameState {
@@ -118,6 +164,7 @@ impl Client {
 	}
 
 	/// Draw our local GameState, seen from our player.
+	#[cfg(feature = "gl")]
 	pub fn draw(&self, ctx: &GLContext) {
 		self.game_state.draw(self.player_id, ctx)
 	}
@@ -1,3 +1,2 @@
-pub use super::super::prelude::*;
 pub use super::super::server::prelude::*;
 pub use super::client::*;
@@ -0,0 +1,397 @@
+use super::internal::*;
+
+/// Structural integrity, run by the server (optional):
+/// voxel clusters that are no longer connected to the ground fall down.
+///
+/// When a voxel is removed, a flood fill from its neighbors finds out
+/// whether they are still connected to the ground (see `GROUND`).
+/// Clusters that are not get removed and become `Debris`,
+/// which falls until it lands on something and is turned back into voxels.
+///
+/// Fluids neither support nor are part of clusters (they have their own physics, see `FluidSim`).
+pub struct Collapse {
+	limits: CollapseLimits,
+	starts: Vec<ivec3>,     // flood fill starts: neighbors of voxels removed since the last tick.
+	queued: HashSet<ivec3>, // the voxels in `starts`, so that each is only queued once.
+	debris: Vec<Debris>,
+}
+
+/// Limits that keep large collapses from stalling the server.
+#[derive(Clone, Copy, Debug)]
+pub struct CollapseLimits {
+	/// Clusters of more voxels than this are assumed to be supported (and not searched further).
+	pub max_cluster: usize,
+	/// At most this many flood fills per tick. Remaining ones are done in later ticks.
+	pub max_fills: usize,
+}
+
+impl Default for CollapseLimits {
+	fn default() -> Self {
+		Self { max_cluster: 4096, max_fills: 32 }
+	}
+}
+
+/// A falling cluster of voxels.
+struct Debris {
+	voxels: Vec<MapEdit>, // where the voxels were before falling.
+	drop: i32,            // number of voxels fallen so far.
+	fall: f32,            // distance fallen so far.
+	vel: f32,             // falling speed.
+}
+
+impl Collapse {
+	const NEIGHBORS: [ivec3; 6] = [ivec3(0, -1, 0), ivec3(-1, 0, 0), ivec3(1, 0, 0), ivec3(0, 0, -1), ivec3(0, 0, 1), ivec3(0, 1, 0)];
+
+	/// Voxels at or below this height are on the ground, and support what is connected to them.
+	/// That is the bottom layer of bounded maps (which start at y=0).
+	/// Unbounded maps are open below, but their bottom keeps moving as they are edited.
+	const GROUND: i32 = 0;
+
+	pub fn new(limits: CollapseLimits) -> Self {
+		Self {
+			limits,
+			starts: Vec::new(),
+			queued: HashSet::default(),
+			debris: Vec::new(),
+		}
+	}
+
+	/// Record that the voxel at `index` has been edited (and already changed in `map`),
+	/// `old` is the voxel that was there before.
+	/// Only removing a structural voxel (e.g. not fluid flowing) can leave others unsupported.
+	pub fn notify(&mut self, map: &Map, index: ivec3, old: Voxel) {
+		if !Self::is_structural(old) || Self::is_structural(map.at(index)) {
+			return;
+		}
+		for &d in Self::NEIGHBORS.iter().rev() {
+			// popped in NEIGHBORS order
+			if self.queued.insert(index + d) {
+				self.starts.push(index + d);
+			}
+		}
+	}
+
+	/// Number of falling clusters.
+	pub fn num_debris(&self) -> usize {
+		self.debris.len()
+	}
+
+	/// Advance by `dt` seconds: detach unsupported clusters, let debris fall and land.
+	/// Returns the map edits to make, and the visual effects of falling debris.
+	pub fn tick(&mut self, dt: f32, map: &Map) -> (Vec<MapEdit>, Vec<Effect>) {
+		let mut edits = Vec::new();
+		let mut effects = Vec::new();
+
+		// detach unsupported clusters.
+		let mut detached = HashSet::default();
+		let mut fills = 0;
+		while fills < self.limits.max_fills {
+			let start = match self.starts.pop() {
+				None => break,
+				Some(index) => index,
+			};
+			self.queued.remove(&start);
+			if !Self::is_structural(map.at(start)) || detached.contains(&start) {
+				continue;
+			}
+			fills += 1;
+			if let Some(cluster) = self.unsupported_cluster(map, start) {
+				let debris = Debris::new(map, cluster);
+				effects.extend(debris.effects(map));
+				for v in &debris.voxels {
+					detached.insert(v.index);
+					edits.push(MapEdit {
+						index: v.index,
+						voxel: Voxel::EMPTY,
+						state: VoxelState::default(),
+					});
+				}
+				self.debris.push(debris);
+			}
+		}
+
+		// fall and land.
+		let mut i = 0;
+		while i < self.debris.len() {
+			if self.debris[i].tick(dt, map, &detached) {
+				edits.extend(self.debris.swap_remove(i).landed(map));
+			} else {
+				i += 1;
+			}
+		}
+
+		(edits, effects)
+	}
+
+	// Flood fill from `start` through structural voxels.
+	// Returns the cluster if it does not reach the ground,
+	// `None` if it does (or is too large to tell).
+	fn unsupported_cluster(&self, map: &Map, start: ivec3) -> Option<Vec<ivec3>> {
+		let mut cluster = vec![start];
+		let mut visited = HashSet::default();
+		visited.insert(start);
+		let mut i = 0;
+		while i < cluster.len() {
+			let index = cluster[i];
+			i += 1;
+			if index.y <= Self::GROUND || cluster.len() > self.limits.max_cluster {
+				return None;
+			}
+			for &d in &Self::NEIGHBORS {
+				let n = index + d;
+				if Self::is_structural(map.at(n)) && visited.insert(n) {
+					cluster.push(n);
+				}
+			}
+		}
+		Some(cluster)
+	}
+
+	// Voxels that hold each other up.
+	fn is_structural(v: Voxel) -> bool {
+		v != Voxel::EMPTY && !v.is_fluid()
+	}
+}
+
+impl Debris {
+	/// At most this many particles show a falling cluster.
+	const MAX_PARTICLES: usize = 16;
+
+	fn new(map: &Map, cluster: Vec<ivec3>) -> Self {
+		let voxels = cluster
+			.into_iter()
+			.map(|index| MapEdit {
+				index,
+				voxel: map.at(index),
+				state: map.state_at(index),
+			})
+			.collect();
+		Self {
+			voxels,
+			drop: 0,
+			fall: 0.0,
+			vel: 0.0,
+		}
+	}
+
+	// Fall for `dt` seconds. Returns true if landed.
+	// `ignore` are voxels that are still on the map, but about to be removed (this debris included).
+	fn tick(&mut self, dt: f32, map: &Map, ignore: &HashSet<ivec3>) -> bool {
+		self.vel += GRAVITY * dt;
+		self.fall += self.vel * dt;
+		while (self.drop + 1) as f32 <= self.fall {
+			if !self.can_drop(map, self.drop + 1, ignore) {
+				return true;
+			}
+			self.drop += 1;
+		}
+		false
+	}
+
+	// Can the debris be `drop` voxels lower than where it started?
+	fn can_drop(&self, map: &Map, drop: i32, ignore: &HashSet<ivec3>) -> bool {
+		let ground = map.bounds().min.y;
+		self.voxels.iter().all(|v| {
+			let below = v.index - ivec3(0, drop, 0);
+			below.y >= ground && (!map.at(below).is_solid() || ignore.contains(&below) || self.contains(below))
+		})
+	}
+
+	fn contains(&self, index: ivec3) -> bool {
+		// Clusters are small enough (`max_cluster`) for a linear search,
+		// which is only needed when the debris has not yet left its original position.
+		self.drop == 0 && self.voxels.iter().any(|v| v.index == index)
+	}
+
+	// Put the voxels back on the map where the debris landed.
+	// Voxels that would end up inside solid voxels (e.g. placed while falling) are lost.
+	fn landed(self, map: &Map) -> Vec<MapEdit> {
+		let drop = ivec3(0, self.drop, 0);
+		self.voxels
+			.iter()
+			.map(|v| MapEdit { index: v.index - drop, ..*v })
+			.filter(|v| !map.at(v.index).is_solid())
+			.collect()
+	}
+
+	// Particles showing the debris falling until where it will land (as far as we can tell now),
+	// for some of its voxels (`MAX_PARTICLES`).
+	fn effects(&self, map: &Map) -> Vec<Effect> {
+		let mut drop = 0;
+		let ignore = self.voxels.iter().map(|v| v.index).collect::<HashSet<_>>();
+		while drop < 256 && self.can_drop(map, drop + 1, &ignore) {
+			drop += 1;
+		}
+		let ttl = f32::sqrt(2.0 * (drop as f32) / GRAVITY);
+		self.voxels
+			.iter()
+			.step_by(self.voxels.len().div_ceil(Self::MAX_PARTICLES))
+			.map(|v| {
+				Effect::ParticleEffect(Particle {
+					pos: v.index.to_vec() + vec3(0.5, 0.5, 0.5),
+					vel: vec3::ZERO,
+					acc: -GRAVITY,
+					ttl,
+					mesh: 0,
+					tex: v.voxel.tex_id() as u8,
+				})
+			})
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	// Tick once, apply the resulting edits. Returns the number of particles.
+	fn tick(c: &mut Collapse, map: &mut Map) -> usize {
+		let (edits, effects) = c.tick(0.1, map);
+		for e in edits {
+			let old = map.at(e.index);
+			map.set(e.index, e.voxel);
+			map.set_state(e.index, e.state);
+			c.notify(map, e.index, old);
+		}
+		effects.len()
+	}
+
+	fn remove(c: &mut Collapse, map: &mut Map, index: ivec3) {
+		let old = map.at(index);
+		map.set(index, Voxel::EMPTY);
+		c.notify(map, index, old);
+	}
+
+	fn run(c: &mut Collapse, map: &mut Map) {
+		for _ in 0..100 {
+			tick(c, map);
+			if c.num_debris() == 0 && c.starts.is_empty() {
+				return;
+			}
+		}
+		panic!("debris did not land")
+	}
+
+	#[test]
+	fn tower_falls() {
+		let mut map = Map::flat(uvec3(16, 32, 16));
+		for y in 1..10 {
+			map.set(ivec3(5, y, 5), Voxel::GREYSTONE);
+		}
+		map.set(ivec3(6, 9, 5), Voxel::SAND); // overhang
+		let mut c = Collapse::new(CollapseLimits::default());
+
+		// removing the top does not make anything fall.
+		remove(&mut c, &mut map, ivec3(5, 9, 5));
+		run(&mut c, &mut map);
+		assert_eq!(map.at(ivec3(5, 8, 5)), Voxel::GREYSTONE);
+		assert_eq!(map.at(ivec3(6, 1, 5)), Voxel::SAND); // overhang fell down
+
+		// cutting the base makes the tower fall by one.
+		remove(&mut c, &mut map, ivec3(5, 1, 5));
+		run(&mut c, &mut map);
+		for y in 1..8 {
+			assert_eq!(map.at(ivec3(5, y, 5)), Voxel::GREYSTONE, "y={}", y);
+		}
+		assert_eq!(map.at(ivec3(5, 8, 5)), Voxel::EMPTY);
+	}
+
+	#[test]
+	fn limits() {
+		let mut map = Map::flat(uvec3(16, 32, 16));
+		for y in 1..10 {
+			map.set(ivec3(5, y, 5), Voxel::GREYSTONE);
+		}
+		let mut c = Collapse::new(CollapseLimits { max_cluster: 4, max_fills: 1 });
+		remove(&mut c, &mut map, ivec3(5, 1, 5));
+		run(&mut c, &mut map);
+		assert_eq!(map.at(ivec3(5, 9, 5)), Voxel::GREYSTONE); // too large to tell: stays
+	}
+
+	#[test]
+	fn unbounded_ground() {
+		let mut map = Map::flat_unbounded(16);
+		let mut c = Collapse::new(CollapseLimits::default());
+		// dig down, and fill the hole again: the map's bounds now reach below its floor.
+		map.set(ivec3(3, -5, 3), Voxel::GREYSTONE);
+		map.set(ivec3(3, -5, 3), Voxel::EMPTY);
+		assert_eq!(map.bounds().min.y, -5);
+
+		// a tower on the floor stays up when its top is removed.
+		for y in 1..5 {
+			map.set(ivec3(5, y, 5), Voxel::GREYSTONE);
+		}
+		remove(&mut c, &mut map, ivec3(5, 4, 5));
+		run(&mut c, &mut map);
+		assert_eq!(map.at(ivec3(5, 3, 5)), Voxel::GREYSTONE);
+		assert_eq!(map.at(ivec3(8, 0, 8)), Voxel::SNOW);
+	}
+
+	#[test]
+	fn max_fills() {
+		// six separate floating voxels around one that is removed.
+		let mut map = Map::flat(uvec3(16, 32, 16));
+		let center = ivec3(8, 10, 8);
+		for &d in &Collapse::NEIGHBORS {
+			map.set(center + d, Voxel::GREYSTONE);
+		}
+		map.set(center, Voxel::GREYSTONE);
+		let mut c = Collapse::new(CollapseLimits { max_cluster: 4096, max_fills: 2 });
+		remove(&mut c, &mut map, center);
+		tick(&mut c, &mut map);
+		assert_eq!(c.num_debris(), 2);
+
+		run(&mut c, &mut map);
+		let landed = (1..32).filter(|&y| map.at(ivec3(8, y, 8)) == Voxel::GREYSTONE).count();
+		assert_eq!(landed, 2); // the ones above and below
+		assert_eq!(map.at(ivec3(7, 1, 8)), Voxel::GREYSTONE);
+		assert_eq!(map.at(ivec3(8, 1, 9)), Voxel::GREYSTONE);
+	}
+
+	#[test]
+	fn particles() {
+		// a 20x20 slab on a pillar, which is cut.
+		let mut map = Map::flat(uvec3(32, 32, 32));
+		for x in 0..20 {
+			for z in 0..20 {
+				map.set(ivec3(x, 10, z), Voxel::GREYSTONE);
+			}
+		}
+		for y in 1..10 {
+			map.set(ivec3(0, y, 0), Voxel::GREYSTONE);
+		}
+		let mut c = Collapse::new(CollapseLimits::default());
+		remove(&mut c, &mut map, ivec3(0, 9, 0));
+		let particles = tick(&mut c, &mut map);
+		assert!(particles <= Debris::MAX_PARTICLES && particles > 0, "{} particles", particles);
+		assert_eq!(map.at(ivec3(19, 10, 19)), Voxel::EMPTY); // detached as a whole
+		run(&mut c, &mut map);
+		assert_eq!(map.at(ivec3(19, 9, 19)), Voxel::GREYSTONE); // landed on the rest of the pillar
+	}
+
+	#[test]
+	fn only_removals() {
+		let water = VoxelTypes::get().by_name("water").unwrap();
+		let mut map = Map::flat(uvec3(16, 16, 16));
+		map.set(ivec3(5, 1, 5), Voxel::GREYSTONE);
+		let mut c = Collapse::new(CollapseLimits::default());
+
+		// fluid flowing next to terrain, and placing voxels, need no flood fills.
+		map.set(ivec3(6, 1, 5), water);
+		c.notify(&map, ivec3(6, 1, 5), Voxel::EMPTY);
+		map.set(ivec3(6, 1, 5), Voxel::EMPTY);
+		c.notify(&map, ivec3(6, 1, 5), water);
+		map.set(ivec3(5, 2, 5), Voxel::SAND);
+		c.notify(&map, ivec3(5, 2, 5), Voxel::EMPTY);
+		assert!(c.starts.is_empty());
+
+		// neighbors are queued once, however often they are notified.
+		remove(&mut c, &mut map, ivec3(5, 2, 5));
+		map.set(ivec3(5, 2, 5), Voxel::SAND);
+		remove(&mut c, &mut map, ivec3(5, 2, 5));
+		assert_eq!(c.starts.len(), 6);
+		assert_eq!(c.queued.len(), 6);
+		run(&mut c, &mut map);
+		assert!(c.queued.is_empty());
+	}
+}
@@ -15,6 +15,7 @@ impl Effects {
 		self.inner.push(e)
 	}
 
+	#[cfg(feature = "gl")]
 	pub fn draw(&self, ctx: &GLContext) {
 		ctx.set_depth_test(true);
 		ctx.textures().bind_voxels();
@@ -25,9 +26,9 @@ impl Effects {
 		}
 	}
 
-	pub fn tick(&mut self, dt: f32) {
+	pub fn tick(&mut self, dt: f32, map: &Map) {
 		for p in &mut self.inner {
-			p.tick(dt)
+			p.tick(dt, map)
 		}
 		self.prune_dead();
 	}
@@ -74,9 +75,9 @@ pub struct Particle {
 }
 
 impl Effect {
-	pub fn tick(&mut self, dt: f32) {
+	pub fn tick(&mut self, dt: f32, map: &Map) {
 		match self {
-			ParticleEffect(e) => e.tick(dt),
+			ParticleEffect(e) => e.tick(dt, map),
 			TrailEffect(e) => e.tick(dt),
 		}
 	}
@@ -88,6 +89,7 @@ impl Effect {
 		}
 	}
 
+	#[cfg(feature = "gl")]
 	pub fn draw(&self, ctx: &GLContext) {
 		// TODO: limit shader switches (expensive)
 		match self {
@@ -108,6 +110,7 @@ impl Trail {
 		self.ttl -= dt;
 	}
 
+	#[cfg(feature = "gl")]
 	fn draw(&self, ctx: &GLContext) {
 		ctx.set_rel_line_width(0.005);
 		let shader = ctx.shaders().bind_flat_shader_3d();
@@ -121,13 +124,21 @@ impl Trail {
 }
 
 impl Particle {
-	fn tick(&mut self, dt: f32) {
+	// Fly, without passing through voxels: come to a halt along the axes where a voxel is hit.
+	fn tick(&mut self, dt: f32, map: &Map) {
 		self.ttl -= dt;
 
 		self.vel.y += self.acc * dt;
-		self.pos += self.vel * dt;
+		let sweep = map.sweep(&BoundingBox::new(self.pos, self.pos), self.vel * dt);
+		self.pos += sweep.delta;
+		for (v, n) in [(&mut self.vel.x, sweep.normal.x), (&mut self.vel.y, sweep.normal.y), (&mut self.vel.z, sweep.normal.z)] {
+			if n != 0 {
+				*v = 0.0;
+			}
+		}
 	}
 
+	#[cfg(feature = "gl")]
 	fn draw(&self, ctx: &GLContext) {
 		let shader = ctx.shaders().bind_anim_shader(); // TODO: limit switches
 		shader.set_texture(self.tex as usize);
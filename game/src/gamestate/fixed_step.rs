@@ -0,0 +1,101 @@
+/// Runs physics at a fixed time step, independent of the frame rate,
+/// so that the same inputs always result in the same trajectory.
+///
+/// Frame times are accumulated, and spent in whole steps of `DT`.
+/// The time left over (less than a step) carries over to the next frame.
+/// Rendering interpolates between the last two steps (see `alpha`).
+#[derive(Clone, Debug, Default)]
+pub struct FixedStep {
+	acc: f32, // seconds not yet simulated
+}
+
+impl FixedStep {
+	/// Physics time step (seconds).
+	pub const DT: f32 = 1.0 / 60.0;
+
+	/// At most this many steps per frame. Time beyond that is dropped (e.g. after a stall),
+	/// rather than falling further and further behind.
+	const MAX_STEPS: u32 = 15;
+
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Advance by a frame of `dt` seconds.
+	/// Returns the number of physics steps (of `DT` seconds each) to take.
+	pub fn advance(&mut self, dt: f32) -> u32 {
+		self.acc += dt;
+		let mut steps = 0;
+		while self.acc >= Self::DT {
+			if steps == Self::MAX_STEPS {
+				self.acc = 0.0;
+				break;
+			}
+			self.acc -= Self::DT;
+			steps += 1;
+		}
+		steps
+	}
+
+	/// How far (0..1) the time not yet simulated is into the next step.
+	/// The state to render is the last two steps' states, mixed in this proportion.
+	pub fn alpha(&self) -> f32 {
+		self.acc / Self::DT
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use crate::prelude::*;
+
+	// Jump, then walk along +x at walking speed, for `secs` seconds of frames of `dt` seconds.
+	// Returns the model's position after every physics step.
+	fn trajectory(map: &Map, dt: f32, secs: f32) -> Vec<vec3> {
+		let mut m = Model::new(0.8, 1.8, 0, 0, 0);
+		m.pos = vec3(4.0, 1.0, 16.0);
+		let mut clock = FixedStep::new();
+		let mut traj = Vec::new();
+		for _ in 0..((secs / dt) as usize) {
+			for _ in 0..clock.advance(dt) {
+				if traj.is_empty() {
+					m.try_jump(map, Player::JUMP_SPEED);
+				}
+				m.try_walk(FixedStep::DT, map, vec3(Player::WALK_SPEED, 0.0, 0.0));
+				m.tick(FixedStep::DT, map);
+				traj.push(m.pos);
+			}
+		}
+		traj
+	}
+
+	#[test]
+	fn fixed_step() {
+		// 30 fps: 2 steps per frame, nothing left over.
+		let mut clock = FixedStep::new();
+		assert_eq!(clock.advance(1.0 / 30.0), 2);
+		assert!(clock.alpha() < 1e-3);
+
+		// 300 fps: a step every 5 frames.
+		let steps = (0..50).map(|_| clock.advance(1.0 / 300.0)).collect::<Vec<_>>();
+		assert_eq!(steps.iter().sum::<u32>(), 10);
+		assert!(steps.iter().all(|&n| n <= 1));
+
+		// a long stall does not have to be caught up with.
+		assert_eq!(clock.advance(10.0), FixedStep::MAX_STEPS);
+		assert_eq!(clock.advance(0.0), 0);
+
+		// Jump height and walking distance do not depend on the frame rate.
+		let map = Map::flat(uvec3(64, 32, 32));
+		let want = trajectory(&map, 1.0 / 60.0, 2.5);
+		let apex = want.iter().map(|p| p.y).fold(0.0, f32::max) - 1.0;
+		assert!(apex > 2.3 && apex < 2.7, "apex {}", apex); // just under JUMP_SPEED² / 2 GRAVITY
+		let walked = want[119].x - want[0].x; // after 2 s
+		assert!(walked > 10.0 && walked < 20.0, "walked {}", walked);
+		for &fps in &[30.0, 144.0, 300.0] {
+			let got = trajectory(&map, 1.0 / fps, 2.5);
+			assert!(got.len() >= 120, "{} fps: {} steps", fps, got.len());
+			let n = usize::min(got.len(), want.len());
+			assert_eq!(&got[..n], &want[..n], "{} fps", fps);
+		}
+	}
+}
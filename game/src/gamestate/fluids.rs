@@ -0,0 +1,306 @@
+use super::internal::*;
+
+/// Cellular automaton for fluid voxels (see `Voxel::is_fluid`), run by the server.
+/// Lava is slow, and cools into stone. Other fluids behave like water.
+///
+/// Fluid voxels hold a level (amount of fluid) between 1 and `MAX_LEVEL`,
+/// stored in their state's data bits (see `fluid_level`). Every tick, fluid
+///
+///   * falls down into empty space, or into the fluid below if it is not full
+///     (or off the bottom of an unbounded map),
+///   * spreads sideways towards lower levels, one level at a time,
+///   * lava touching snow or water cools into stone.
+///
+/// Fluid is conserved, so it spreads until it is level (or one deep) and then stays put.
+/// The limits of a bounded map are solid: fluid rests on its bottom layer, and against its sides.
+///
+/// Only "active" voxels are simulated: those near recent map edits.
+/// So resting fluid costs nothing, and pre-existing lakes stay as they are until disturbed.
+pub struct FluidSim {
+	active: HashSet<ivec3>, // voxels that may need to move
+	ticks: u64,
+	water: Option<Voxel>, // cools lava. Not all voxel type registries have water.
+}
+
+/// Level of a full fluid voxel.
+pub const MAX_LEVEL: u8 = 8;
+
+/// Fluid level of a voxel: MAX_LEVEL minus the state's data bits.
+/// So fluid voxels in the default state (e.g. lava from a map file) are full.
+pub fn fluid_level(state: VoxelState) -> u8 {
+	MAX_LEVEL.saturating_sub(state.data)
+}
+
+/// State of a fluid voxel with given level (1..=MAX_LEVEL).
+pub fn fluid_state(level: u8) -> VoxelState {
+	VoxelState {
+		data: MAX_LEVEL - level,
+		..VoxelState::default()
+	}
+}
+
+impl FluidSim {
+	/// Time between ticks, in seconds.
+	pub const TICK: f32 = 0.1;
+
+	/// Lava is viscous: it only moves every LAVA_PERIOD ticks.
+	const LAVA_PERIOD: u64 = 5;
+
+	/// Limit the work per tick. Remaining active voxels are processed in later ticks.
+	const MAX_UPDATES: usize = 4096;
+
+	const NEIGHBORS: [ivec3; 6] = [ivec3(0, -1, 0), ivec3(-1, 0, 0), ivec3(1, 0, 0), ivec3(0, 0, -1), ivec3(0, 0, 1), ivec3(0, 1, 0)];
+
+	pub fn new() -> Self {
+		Self {
+			active: HashSet::default(),
+			ticks: 0,
+			water: VoxelTypes::get().by_name("water"),
+		}
+	}
+
+	/// Record that the voxel at `index` has changed:
+	/// it, and fluid around it, may need to move.
+	pub fn activate(&mut self, index: ivec3) {
+		self.active.insert(index);
+		for &d in &Self::NEIGHBORS {
+			self.active.insert(index + d);
+		}
+	}
+
+	/// Number of voxels that may need to move.
+	pub fn num_active(&self) -> usize {
+		self.active.len()
+	}
+
+	/// Advance the simulation by one tick (`TICK` seconds).
+	/// Returns the map edits to make, which the caller must apply
+	/// (and `activate`, which `Server::update_map` does).
+	pub fn tick(&mut self, map: &Map) -> Vec<MapEdit> {
+		self.ticks += 1;
+		let lava_moves = self.ticks.is_multiple_of(Self::LAVA_PERIOD);
+
+		// Keep fluids that are not due this tick active for later, drop everything else.
+		let mut todo = Vec::new();
+		for index in std::mem::take(&mut self.active) {
+			match map.at(index) {
+				v if v == Voxel::LAVA && !lava_moves => {
+					self.active.insert(index);
+				}
+				v if v.is_fluid() => todo.push(index),
+				_ => (),
+			}
+		}
+
+		// Bottom-up, so that falling fluid makes room for the fluid above it.
+		todo.sort_by_key(|i| (i.y, i.z, i.x));
+		if todo.len() > Self::MAX_UPDATES {
+			self.active.extend(todo.drain(Self::MAX_UPDATES..));
+		}
+
+		let mut cells = Cells { map, changes: HashMap::default() };
+		for index in todo {
+			self.update(&mut cells, index);
+		}
+		cells.edits()
+	}
+
+	fn update(&self, cells: &mut Cells, index: ivec3) {
+		let (fluid, mut level) = cells.get(index);
+		if !fluid.is_fluid() {
+			return; // moved away earlier this tick.
+		}
+
+		// cool down
+		if fluid == Voxel::LAVA && Self::NEIGHBORS.iter().any(|&d| self.cools_lava(cells.get(index + d).0)) {
+			return cells.set(index, Voxel::GREYSTONE, 0);
+		}
+
+		// fall down, or off the bottom of an unbounded map (rather than forever).
+		let below = index - ivec3(0, 1, 0);
+		if cells.map.is_unbounded() && below.y < cells.map.bounds().min.y {
+			return cells.set(index, Voxel::EMPTY, 0);
+		}
+		match cells.get(below) {
+			_ if cells.is_outside(below) => (),
+			(Voxel::EMPTY, _) => {
+				cells.set(below, fluid, level);
+				return cells.set(index, Voxel::EMPTY, 0);
+			}
+			(v, below_level) if v == fluid && below_level < MAX_LEVEL => {
+				let flow = u8::min(level, MAX_LEVEL - below_level);
+				cells.set(below, fluid, below_level + flow);
+				level -= flow;
+				if level == 0 {
+					return cells.set(index, Voxel::EMPTY, 0);
+				}
+			}
+			_ => (),
+		}
+
+		// spread sideways, starting in a different direction every tick to avoid bias.
+		for i in 0..4 {
+			let d = Self::NEIGHBORS[1 + (i + self.ticks as usize) % 4];
+			let (v, side_level) = cells.get(index + d);
+			if level >= 2 && !cells.is_outside(index + d) && (v == Voxel::EMPTY || (v == fluid && side_level + 1 < level)) {
+				cells.set(index + d, fluid, side_level + 1);
+				level -= 1;
+			}
+		}
+		cells.set(index, fluid, level);
+	}
+
+	fn cools_lava(&self, v: Voxel) -> bool {
+		v == Voxel::SNOW || Some(v) == self.water
+	}
+}
+
+impl Default for FluidSim {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+// The map, with the changes made during one tick on top.
+struct Cells<'a> {
+	map: &'a Map,
+	changes: HashMap<ivec3, (Voxel, u8)>, // voxel, fluid level (0 for non-fluids)
+}
+
+impl<'a> Cells<'a> {
+	fn get(&self, index: ivec3) -> (Voxel, u8) {
+		match self.changes.get(&index) {
+			Some(&cell) => cell,
+			None => match self.map.at(index) {
+				Voxel::EMPTY => (Voxel::EMPTY, 0),
+				v => (v, fluid_level(self.map.state_at(index))),
+			},
+		}
+	}
+
+	// Outside the limits of a bounded map, where fluid cannot go.
+	fn is_outside(&self, index: ivec3) -> bool {
+		!self.map.is_unbounded() && !self.map.bounds().contains(index)
+	}
+
+	fn set(&mut self, index: ivec3, v: Voxel, level: u8) {
+		self.changes.insert(index, (v, level));
+	}
+
+	// The changes that differ from the map.
+	fn edits(self) -> Vec<MapEdit> {
+		let mut edits = Vec::new();
+		for (index, (voxel, level)) in self.changes {
+			let state = if level == 0 { VoxelState::default() } else { fluid_state(level) };
+			if (voxel, state) != (self.map.at(index), self.map.state_at(index)) {
+				edits.push(MapEdit { index, voxel, state })
+			}
+		}
+		edits.sort_by_key(|e| (e.index.y, e.index.z, e.index.x)); // deterministic order
+		edits
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	// Run the simulation until it comes to rest (or panic).
+	fn settle(sim: &mut FluidSim, map: &mut Map) {
+		for _ in 0..1000 {
+			if sim.num_active() == 0 {
+				return;
+			}
+			for e in sim.tick(map) {
+				map.set(e.index, e.voxel);
+				map.set_state(e.index, e.state);
+				sim.activate(e.index);
+			}
+		}
+		panic!("fluid did not come to rest")
+	}
+
+	fn total_level(map: &Map, fluid: Voxel) -> u32 {
+		let b = map.bounds();
+		let mut total = 0;
+		for iz in b.min.z..b.max.z {
+			for iy in b.min.y..b.max.y {
+				for ix in b.min.x..b.max.x {
+					let index = ivec3(ix, iy, iz);
+					if map.at(index) == fluid {
+						total += fluid_level(map.state_at(index)) as u32;
+					}
+				}
+			}
+		}
+		total
+	}
+
+	#[test]
+	fn water_fills_basin() {
+		let water = VoxelTypes::get().by_name("water").unwrap();
+		let mut map = Map::flat(uvec3(16, 16, 16));
+		// a 3x3 basin with 1-voxel walls, at y=1.
+		for x in 4..9 {
+			for z in 4..9 {
+				if x == 4 || x == 8 || z == 4 || z == 8 {
+					map.set(ivec3(x, 1, z), Voxel::GREYSTONE);
+				}
+			}
+		}
+		// pour one full voxel of water from above.
+		let mut sim = FluidSim::new();
+		map.set(ivec3(6, 5, 6), water);
+		sim.activate(ivec3(6, 5, 6));
+		settle(&mut sim, &mut map);
+
+		assert_eq!(total_level(&map, water), MAX_LEVEL as u32);
+		assert_eq!(map.at(ivec3(6, 5, 6)), Voxel::EMPTY);
+		for x in 5..8 {
+			for z in 5..8 {
+				assert_eq!(map.at(ivec3(x, 2, z)), Voxel::EMPTY); // nothing left above the basin
+			}
+		}
+		assert_eq!(map.at(ivec3(6, 1, 6)), water);
+		assert_eq!(map.at(ivec3(3, 1, 6)), Voxel::EMPTY); // stayed inside
+	}
+
+	#[test]
+	fn rests_on_bottom() {
+		// a lava floor, two deep, covering the bottom of a bounded map.
+		let mut map = Map::new(uvec3(16, 8, 16));
+		for x in 0..16 {
+			for z in 0..16 {
+				map.set(ivec3(x, 0, z), Voxel::LAVA);
+				map.set(ivec3(x, 1, z), Voxel::LAVA);
+			}
+		}
+		let mut sim = FluidSim::new();
+		map.set(ivec3(8, 1, 8), Voxel::EMPTY); // disturb it
+		sim.activate(ivec3(8, 1, 8));
+		settle(&mut sim, &mut map);
+		assert_eq!(total_level(&map, Voxel::LAVA), 511 * MAX_LEVEL as u32);
+		assert_eq!(map.at(ivec3(0, 0, 0)), Voxel::LAVA);
+
+		// on an unbounded map, fluid still falls off the bottom.
+		let mut map = Map::flat_unbounded(16);
+		let water = VoxelTypes::get().by_name("water").unwrap();
+		map.set(ivec3(20, 0, 0), water);
+		let mut sim = FluidSim::new();
+		sim.activate(ivec3(20, 0, 0));
+		settle(&mut sim, &mut map);
+		assert_eq!(map.at(ivec3(20, 0, 0)), Voxel::EMPTY);
+	}
+
+	#[test]
+	fn lava_cools() {
+		let mut map = Map::flat(uvec3(16, 16, 16));
+		map.set(ivec3(5, 1, 5), Voxel::SNOW);
+		map.set(ivec3(6, 1, 5), Voxel::LAVA);
+		let mut sim = FluidSim::new();
+		sim.activate(ivec3(6, 1, 5));
+		settle(&mut sim, &mut map);
+		assert_eq!(map.at(ivec3(6, 1, 5)), Voxel::GREYSTONE);
+		assert_eq!(total_level(&map, Voxel::LAVA), 0);
+	}
+}
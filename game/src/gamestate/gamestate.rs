@@ -49,8 +49,15 @@ impl GameState {
 	}
 
 	// TODO: remove
-	pub fn update_map(&mut self, index: ivec3, voxel: Voxel) {
-		self.map.set(index, voxel)
+	pub fn update_map(&mut self, index: ivec3, voxel: Voxel, state: VoxelState) {
+		self.map.set(index, voxel);
+		self.map.set_state(index, state);
+	}
+
+	pub fn apply_map_edits(&mut self, edits: &[MapEdit]) {
+		for e in edits {
+			self.update_map(e.index, e.voxel, e.state)
+		}
 	}
 
 	// TODO: remove
@@ -59,12 +66,63 @@ impl GameState {
 	}
 
 	/// Draw the game state from `player_id`'s viewpoint.
+	#[cfg(feature = "gl")]
 	pub fn draw(&self, player_id: ID, ctx: &GLContext) {
 		let player = &self.players.get(player_id);
-		ctx.set_matrix(player.camera());
-		self.map.draw(ctx, player.bottom_pos());
-		self.effects.draw(ctx);
-		self.players.draw(player_id, ctx);
+		self.draw_from(player.camera(), Some(player_id), ctx);
 		ctx.draw_crosshair(); // draws over the rest, no depth test
 	}
+
+	/// Draw the game state from an arbitrary camera (position, yaw, pitch).
+	/// Player `first_person`, if any, is drawn as seen through their own eyes.
+	#[cfg(feature = "gl")]
+	pub fn draw_from(&self, camera: (vec3, f32, f32), first_person: Option<ID>, ctx: &GLContext) {
+		ctx.set_matrix(camera);
+		self.map.draw(ctx, camera);
+		self.effects.draw(ctx);
+		self.players.draw(first_person, ctx);
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	// The game logic needs no rendering (see the `gl` feature).
+	#[test]
+	fn headless() {
+		let mut map = Map::flat(uvec3(32, 16, 32));
+		for iz in 0..32 {
+			for iy in 1..6 {
+				map.set(ivec3(12, iy, iz), Voxel::SNOW);
+			}
+		}
+		let mut player = Player::new(0);
+		player.model.pos = vec3(4.0, 1.0, 16.5);
+		player.model.set_yaw(-90.0 * DEG); // looking down +x, at the wall.
+		let mut players = Players::new();
+		players.set(1, player);
+		let mut gs = GameState::new(map, players);
+		gs.time = 10.0; // weapons charged
+
+		// walk towards the wall, then shoot a hole in it.
+		let mut player = gs.players.get(1).clone();
+		for _ in 0..60 {
+			player.walk(FixedStep::DT, player.model.look_dir_h(), false, &gs);
+			player.model.tick(FixedStep::DT, &gs.map);
+		}
+		assert!(player.model.pos.x > 8.0 && player.model.pos.x < 11.7, "{}", player.model.pos);
+		let mut updates = Updates::new();
+		player.fire_weapon(FixedStep::DT, false, &gs, &mut updates);
+		gs.update_player(1, player);
+
+		let hit = ivec3(12, 2, 16); // at eye height
+		assert_eq!(gs.map.at(hit), Voxel::SNOW);
+		for msg in updates {
+			if let Message::UpdateMap { index, voxel, state } = msg {
+				gs.update_map(index, voxel, state)
+			}
+		}
+		assert_ne!(gs.map.at(hit), Voxel::SNOW);
+	}
 }
@@ -3,9 +3,13 @@
 pub use crate::prelude::*;
 
 pub use super::boundingbox::*;
+pub use super::collapse::*;
 pub use super::effect::*;
+pub use super::fixed_step::*;
+pub use super::fluids::*;
 pub use super::gamestate::*;
 pub use super::map::*;
+pub use super::map_format::*;
 pub use super::model::*;
 pub use super::player::*;
 pub use super::players::*;
@@ -11,17 +11,22 @@ pub enum Key {
 	Sprint = 6,
 	Mouse1 = 7,
 	Mouse3 = 8,
-	// TODO: fill gap
-	Key1 = 11,
-	Key2 = 12,
-	Key3 = 13,
-	Key4 = 14,
-	Key5 = 15,
-	Key6 = 16,
-	Key7 = 17,
-	Key8 = 18,
-	Key9 = 19,
-	Key0 = 20,
+	Key1 = 9,
+	Key2 = 10,
+	Key3 = 11,
+	Key4 = 12,
+	Key5 = 13,
+	Key6 = 14,
+	Key7 = 15,
+	Key8 = 16,
+	Key9 = 17,
+	Key0 = 18,
+	// replay controls
+	Pause = 19,
+	Slower = 20,
+	Faster = 21,
+	SeekBack = 22,
+	SeekForward = 23,
 }
 use Key::*;
 
@@ -29,4 +34,4 @@ impl Key {
 	pub const NUMERIC_KEYS: [Key; 10] = [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0];
 }
 
-pub const NUM_KEYS: usize = 21;
+pub const NUM_KEYS: usize = 24;
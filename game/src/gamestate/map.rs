@@ -1,14 +1,13 @@
 use super::internal::*;
-use flate2::read::GzDecoder;
-use flate2::write::GzEncoder;
-use flate2::Compression;
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::{BufReader, BufWriter};
 
 /// A world, without connected players.
-/// Maps are saved as `.json.gz` files.
-#[derive(Serialize, Deserialize)]
+/// Maps are saved in a compact binary format (see `map_format.rs`),
+/// legacy `.json.gz` maps can still be loaded.
+/// (JSON serialization via serde remains available, e.g. for tools).
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Map {
 	voxels: VoxelBox,
 	pub fog_dist: f32,
@@ -16,6 +15,14 @@ pub struct Map {
 	pub sun_dir: vec3,
 }
 
+/// A change to one voxel of a Map: its type and state.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MapEdit {
+	pub index: ivec3,
+	pub voxel: Voxel,
+	pub state: VoxelState,
+}
+
 impl Map {
 	const DEFAULT_BACKGROUND_COLOR: vec3 = vec3(0.95, 0.90, 0.85);
 	const DEFAULT_FOG_DIST: f32 = 1e2;
@@ -25,8 +32,18 @@ impl Map {
 	/// An empty map with given size
 	/// and default environmental parameters.
 	pub fn new(size: uvec3) -> Self {
+		Self::with_voxels(VoxelBox::new(size))
+	}
+
+	/// An empty map without bounds: voxels can be added anywhere,
+	/// including at negative indices.
+	pub fn unbounded() -> Self {
+		Self::with_voxels(VoxelBox::unbounded())
+	}
+
+	fn with_voxels(voxels: VoxelBox) -> Self {
 		Self {
-			voxels: VoxelBox::new(size),
+			voxels,
 			background_color: Self::DEFAULT_BACKGROUND_COLOR,
 			fog_dist: Self::DEFAULT_FOG_DIST,
 			sun_dir: Self::DEFAULT_SUN_DIR,
@@ -36,19 +53,47 @@ impl Map {
 	/// A square map populated with a flat, snowy surface.
 	pub fn flat(size: uvec3) -> Self {
 		let mut map = Map::new(size);
-		let (nx, _, nz) = map.size().as_ivec().into();
+		map.add_floor(size.x as i32, size.z as i32);
+		map
+	}
+
+	/// An unbounded map, starting with a `size` x `size` flat, snowy surface
+	/// to build on.
+	pub fn flat_unbounded(size: u32) -> Self {
+		let mut map = Map::unbounded();
+		map.add_floor(size as i32, size as i32);
+		map
+	}
+
+	fn add_floor(&mut self, nx: i32, nz: i32) {
 		for x in 0..nx {
 			for z in 0..nz {
-				map.set(ivec3(x, 0, z), Voxel::SNOW)
+				self.set(ivec3(x, 0, z), Voxel::SNOW)
 			}
 		}
-		map
 	}
 
 	// _________________________ accessors ______________________
 
-	pub fn size(&self) -> uvec3 {
-		self.voxels.world_size()
+	/// The map's bounds: fixed for bounded maps,
+	/// the bounding box of the map's contents for unbounded maps.
+	pub fn bounds(&self) -> VoxelBounds {
+		self.voxels.bounds()
+	}
+
+	pub fn is_unbounded(&self) -> bool {
+		self.voxels.is_unbounded()
+	}
+
+	/// Where new players enter the map: on top of the highest solid voxel
+	/// in the center column (or the center of the map, if that column is empty).
+	pub fn spawn_point(&self) -> vec3 {
+		let bounds = self.bounds();
+		let center = Self::voxel_index(bounds.center());
+		match (bounds.min.y..bounds.max.y).rev().find(|&iy| self.at(ivec3(center.x, iy, center.z)).is_solid()) {
+			Some(iy) => vec3(center.x as f32 + 0.5, (iy + 1) as f32, center.z as f32 + 0.5),
+			None => bounds.center(),
+		}
 	}
 
 	pub fn at(&self, index: ivec3) -> Voxel {
@@ -63,80 +108,186 @@ impl Map {
 		self.voxels.set(index, v)
 	}
 
+	/// State (e.g. damage) of the voxel at given position.
+	pub fn state_at(&self, index: ivec3) -> VoxelState {
+		self.voxels.state_at(index)
+	}
+
+	/// Set the state of the voxel at given position. Ignored for empty voxels.
+	pub fn set_state(&mut self, index: ivec3, state: VoxelState) {
+		self.voxels.set_state(index, state)
+	}
+
+	/// All voxels in a non-default state.
+	pub fn states(&self) -> impl Iterator<Item = (ivec3, VoxelState)> + '_ {
+		self.voxels.states()
+	}
+
 	pub fn voxel_index(pos: vec3) -> ivec3 {
-		pos.to_ivec()
+		pos.floor_ivec()
 	}
 
 	pub fn bumps(&self, bounds: &BoundingBox) -> bool {
 		self.voxels.bumps(bounds)
 	}
 
-	pub fn intersect(&self, start: vec3, dir: vec3, max: f32, bw_offset: bool) -> Option<vec3> {
-		let step = 0.1; // ray march with this step size
-		let num_steps = (max / step) as usize + 1;
-		let mut t = 0.0;
-
-		for _i in 0..num_steps {
-			t += step;
-			let probe = start + t * dir;
-			if self.at_pos(probe) != Voxel::EMPTY {
-				if bw_offset {
-					return Some(start + (t - step) * dir);
-				} else {
-					return Some(probe);
-				}
-			}
-		}
-		None
+	/// Move a box by `delta`, stopping at solid voxels (see `VoxelBox::sweep`).
+	pub fn sweep(&self, bounds: &BoundingBox, delta: vec3) -> Sweep {
+		self.voxels.sweep(bounds, delta)
+	}
+
+	/// Where a ray hits the first non-empty voxel (including transparent ones), if within distance `max`.
+	/// `dir` must be normalized.
+	pub fn raycast(&self, start: vec3, dir: vec3, max: f32) -> Option<RayHit> {
+		self.voxels.raycast(start, dir, max, |v| v != Voxel::EMPTY)
 	}
 
+	/// Is the line of sight blocked by an opaque voxel within distance `max`?
 	pub fn intersects(&self, start: vec3, dir: vec3, max: f32) -> bool {
-		self.intersect(start, dir, max, false) != None
+		self.voxels.raycast(start, dir, max, |v| !v.is_transparent()).is_some()
 	}
 
 	// _______________________ I/O ___________________________
 
-	/// Serialize in gzipped JSON.
-	pub fn serialize<W: Write>(&self, w: W) -> Result<()> {
-		let gz = GzEncoder::new(w, Compression::best());
-		Ok(serde_json::to_writer(gz, self)?)
+	/// Serialize in the binary map format.
+	pub fn serialize<W: Write>(&self, mut w: W) -> Result<()> {
+		Ok(w.write_all(&self.to_bytes())?)
 	}
 
-	/// Deserialize from gzipped JSON.
-	pub fn deserialize<R: Read>(r: R) -> Result<Self> {
-		let gz = GzDecoder::new(r);
-		Ok(serde_json::from_reader(gz)?)
+	/// Deserialize from the binary map format (any version), or legacy gzipped JSON.
+	pub fn deserialize<R: Read>(mut r: R) -> Result<Self> {
+		let mut buf = Vec::new();
+		r.read_to_end(&mut buf)?;
+		Self::from_bytes(&buf)
 	}
 
-	/// Serialize to file, `.json.gz`
+	/// Serialize to file.
 	pub fn save<P: AsRef<Path>>(&self, fname: P) -> Result<()> {
-		self.serialize(BufWriter::new(File::create(fname)?))
+		let mut w = BufWriter::new(File::create(fname)?);
+		self.serialize(&mut w)?;
+		Ok(w.flush()?)
 	}
 
-	/// Deserialize from file, `.json.gz`
+	/// Deserialize from file, auto-detecting the format.
 	pub fn load<P: AsRef<Path>>(fname: P) -> Result<Self> {
-		Self::deserialize(BufReader::new(File::open(fname)?))
+		let fname = fname.as_ref();
+		Self::deserialize(BufReader::new(File::open(fname)?)).map_err(|e| error(format!("load {}: {}", fname.to_string_lossy(), e)))
 	}
 
-	/// Serialize to bytes, gzipped JSON.
+	/// Serialize to bytes, binary map format.
 	pub fn to_bytes(&self) -> Vec<u8> {
-		let mut buf = Vec::new();
-		self.serialize(&mut buf).unwrap();
-		buf
+		encode_map(self)
 	}
 
-	/// Deserialize from bytes, gzipped JSON.
+	/// Deserialize from bytes, auto-detecting the format.
 	pub fn from_bytes(map_data: &[u8]) -> Result<Self> {
-		Self::deserialize(std::io::Cursor::new(map_data))
+		decode_map(map_data)
 	}
 
 	// __________________________ draw __________________________
 
-	pub fn draw(&self, ctx: &GLContext, view_pos: vec3) {
+	/// Draw the map as seen from a camera (position, yaw, pitch).
+	#[cfg(feature = "gl")]
+	pub fn draw(&self, ctx: &GLContext, camera: (vec3, f32, f32)) {
 		ctx.clear(self.background_color);
 		ctx.shaders().set_fog_dist(self.fog_dist);
 		ctx.shaders().set_fog_color(self.background_color);
 		ctx.shaders().set_sun_dir(self.sun_dir);
-		self.voxels.draw(ctx, view_pos, self.fog_dist);
+		self.voxels.draw(ctx, &ctx.frustum(camera), camera.0, self.fog_dist, self.sun_dir);
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	// The ray marching that `raycast` replaced: 0.1 steps, returns the first point inside a non-empty voxel,
+	// and the point one step before.
+	fn march(map: &Map, start: vec3, dir: vec3, max: f32) -> Option<(vec3, vec3)> {
+		let step = 0.1;
+		let num_steps = (max / step) as usize + 1;
+		for i in 1..=num_steps {
+			let probe = start + (i as f32 * step) * dir;
+			if map.at_pos(probe) != Voxel::EMPTY {
+				return Some((probe, start + ((i - 1) as f32 * step) * dir));
+			}
+		}
+		None
+	}
+
+	// Length of the part of a ray inside a voxel.
+	fn length_inside(start: vec3, dir: vec3, index: ivec3) -> f32 {
+		let (min, max) = (index.to_vec(), index.to_vec() + vec3::ONES);
+		let (mut t0, mut t1) = (f32::NEG_INFINITY, f32::INFINITY);
+		for (s, d, lo, hi) in [(start.x, dir.x, min.x, max.x), (start.y, dir.y, min.y, max.y), (start.z, dir.z, min.z, max.z)] {
+			let (a, b) = ((lo - s) / d, (hi - s) / d);
+			t0 = t0.max(a.min(b));
+			t1 = t1.min(a.max(b));
+		}
+		t1 - t0
+	}
+
+	#[test]
+	fn spawn_point() {
+		assert_eq!(Map::flat_unbounded(16).spawn_point(), vec3(8.5, 1.0, 8.5));
+
+		let mut map = Map::flat(uvec3(16, 32, 16));
+		for iy in 1..5 {
+			map.set(ivec3(8, iy, 8), Voxel::GREYSTONE);
+		}
+		map.set(ivec3(8, 3, 8), Voxel::EMPTY);
+		assert_eq!(map.spawn_point(), vec3(8.5, 5.0, 8.5));
+	}
+
+	#[test]
+	fn raycast_vs_march() {
+		let mut map = Map::flat(uvec3(64, 32, 64));
+		for iz in 0..64 {
+			for ix in 0..64 {
+				for iy in 1..((ix * 7 + iz * 3) % 11 - 4) {
+					map.set(ivec3(ix, iy, iz), Voxel::GREYSTONE);
+				}
+			}
+		}
+
+		let (mut n, mut same) = (0, 0);
+		for i in 0..1000 {
+			// rays from all over, in all downward directions (spherical Fibonacci).
+			let start = vec3((i * 37 % 60) as f32 + 2.3, 12.7, (i * 53 % 60) as f32 + 1.9);
+			let y = -(i as f32 + 0.5) / 1000.0;
+			let phi = i as f32 * 2.399_963;
+			let r = (1.0 - y * y).sqrt();
+			let dir = vec3(r * phi.cos(), y, r * phi.sin());
+
+			let hit = map.raycast(start, dir, 100.0);
+			let old = march(&map, start, dir, 100.0);
+			let (hit, (probe, before)) = match (hit, old) {
+				(Some(hit), Some(old)) => (hit, old),
+				(None, None) => continue,
+				(Some(hit), None) => {
+					assert!(length_inside(start, dir, hit.index) < 0.1, "ray {}: only the exact raycast hits {}", i, hit.index);
+					continue;
+				}
+				(None, Some((probe, _))) => panic!("ray {}: only marching hits {}", i, probe),
+			};
+			n += 1;
+
+			// the exact hit is never behind the marched one, and in front only when marching skipped a voxel.
+			assert!(hit.dist <= (probe - start).len() + 1e-4, "ray {}", i);
+			assert!((hit.pos - start).len() <= (probe - start).len() + 1e-4, "ray {}", i);
+			assert_eq!(map.at(hit.index), map.at_pos(hit.pos - 1e-3 * hit.normal.to_vec()));
+			if hit.index != Map::voxel_index(probe) {
+				assert!(length_inside(start, dir, hit.index) < 0.1, "ray {}: {} vs {}", i, hit.index, probe);
+				continue;
+			}
+
+			// placing voxels: the voxel before the hit was approximated by the voxel one step before.
+			assert_eq!(map.at(hit.before()), Voxel::EMPTY);
+			if hit.before() == Map::voxel_index(before) {
+				same += 1;
+			}
+		}
+		assert!(n > 500, "{} hits", n);
+		assert!(same * 10 > n * 9, "{} of {} placed the same", same, n);
 	}
 }
@@ -0,0 +1,470 @@
+//! Binary map file format.
+//!
+//! All numbers are little-endian.
+//!
+//!   header:      MAGIC (6 bytes), version (u16)
+//!   palette:     number of voxel types (u16), names (length (u8), UTF-8 bytes)
+//!   environment: fog_dist (f32), background_color (3 x f32), sun_dir (3 x f32)
+//!   bounds:      bounded (u8: 0 or 1), min (3 x i32), max (3 x i32)
+//!   voxels:      number of chunks (u32), chunks
+//!   states:      number of states (u32), states
+//!
+//! For unbounded maps, `min` and `max` are the bounds of the map's contents.
+//!
+//! The palette lists the names of the voxel types (see `VoxelTypes`) that voxel ids 1, 2, ...
+//! in this file refer to. So maps remain valid if the voxel types are re-ordered or extended.
+//!
+//! Each chunk holds CHUNK^3 voxels, at chunk position (3 x i32, in units of CHUNK voxels),
+//! run-length encoded in X, Z, Y order (X fastest) as:
+//!
+//!   number of runs (u32), runs: (length (u16), voxel (u8))
+//!
+//! Empty chunks are omitted.
+//!
+//! Voxel states are only stored for voxels in a non-default state (see `VoxelState`), as:
+//!
+//!   index (3 x i32), orientation (u8), damage (u8), data (u8)
+//!
+//! Version history:
+//!
+//!   1: bounds were: world_size (3 x u32), from the origin. Unbounded maps did not exist.
+//!   2: bounds, for unbounded maps.
+//!   3: voxel states.
+//!   4: palette. Before, voxel ids referred to the built-in voxel types.
+//!
+//! Legacy maps (gzipped JSON) are recognized by the gzip magic number.
+use super::internal::*;
+use flate2::read::GzDecoder;
+use std::io::prelude::*;
+
+const MAGIC: &[u8; 6] = b"HVAMAP";
+const GZIP_MAGIC: &[u8; 2] = &[0x1f, 0x8b];
+
+/// Current format version, written by `encode_map`.
+/// `decode_map` reads this and all older versions.
+pub const MAP_FORMAT_VERSION: u16 = 4;
+
+const CHUNK: i32 = 16;
+
+/// The voxel types of maps before version 4, by voxel id (starting from 1).
+const LEGACY_PALETTE: [&str; 10] = ["snow", "lava", "plasma", "whitestone", "greystone", "sand", "whiterstone", "stars", "sponge", "crystal"];
+
+/// Encode a map in the current binary format.
+pub fn encode_map(map: &Map) -> Vec<u8> {
+	let mut w = Vec::new();
+	w.extend_from_slice(MAGIC);
+	put_u16(&mut w, MAP_FORMAT_VERSION);
+
+	let palette = VoxelTypes::get().palette();
+	put_u16(&mut w, palette.len() as u16);
+	for name in &palette {
+		w.push(name.len() as u8);
+		w.extend_from_slice(name.as_bytes());
+	}
+
+	put_f32(&mut w, map.fog_dist);
+	put_vec3(&mut w, map.background_color);
+	put_vec3(&mut w, map.sun_dir);
+
+	let bounds = map.bounds();
+	w.push(if map.is_unbounded() { 0 } else { 1 });
+	for &v in &[bounds.min, bounds.max] {
+		put_ivec3(&mut w, v);
+	}
+
+	let chunks = encode_chunks(map);
+	put_u32(&mut w, chunks.len() as u32);
+	for (pos, runs) in chunks {
+		put_ivec3(&mut w, pos);
+		put_u32(&mut w, runs.len() as u32);
+		for (len, voxel) in runs {
+			put_u16(&mut w, len);
+			w.push(voxel.id());
+		}
+	}
+
+	let mut states = map.states().collect::<Vec<_>>();
+	states.sort_by_key(|(index, _)| (index.z, index.y, index.x)); // deterministic output
+	put_u32(&mut w, states.len() as u32);
+	for (index, state) in states {
+		put_ivec3(&mut w, index);
+		w.extend_from_slice(&[state.orientation, state.damage, state.data]);
+	}
+	w
+}
+
+/// Decode a map in any supported format:
+/// any version of the binary format, or legacy gzipped JSON.
+pub fn decode_map(data: &[u8]) -> Result<Map> {
+	if data.starts_with(GZIP_MAGIC) {
+		return Ok(serde_json::from_reader(GzDecoder::new(data))?);
+	}
+	if !data.starts_with(MAGIC) {
+		return err("decode map: unknown file format");
+	}
+
+	let r = &mut &data[MAGIC.len()..];
+	let version = get_u16(r)?;
+	if version == 0 || version > MAP_FORMAT_VERSION {
+		return err(format!("decode map: format version {} not supported (need <= {}), please upgrade", version, MAP_FORMAT_VERSION));
+	}
+
+	let palette = if version >= 4 { decode_palette(r)? } else { voxels_by_file_id(&LEGACY_PALETTE)? };
+	let mut map = if version == 1 { decode_v1_header(r)? } else { decode_header(r)? };
+	decode_chunks(r, &mut map, &palette)?;
+	if version >= 3 {
+		decode_states(r, &mut map)?;
+	}
+	Ok(map)
+}
+
+/// Format version of encoded map data. 0 means legacy gzipped JSON.
+pub fn map_format_version(data: &[u8]) -> Result<u16> {
+	if data.starts_with(GZIP_MAGIC) {
+		Ok(0)
+	} else if data.starts_with(MAGIC) {
+		get_u16(&mut &data[MAGIC.len()..])
+	} else {
+		err("unknown map file format")
+	}
+}
+
+// Environment and size, version 1.
+fn decode_v1_header(r: &mut &[u8]) -> Result<Map> {
+	let (fog_dist, background_color, sun_dir) = (get_f32(r)?, get_vec3(r)?, get_vec3(r)?);
+	let size = uvec3(get_u32(r)?, get_u32(r)?, get_u32(r)?);
+	if size.x == 0 || size.y == 0 || size.z == 0 {
+		return err(format!("decode map: bad size: {}", size));
+	}
+
+	let mut map = Map::new(size);
+	map.fog_dist = fog_dist;
+	map.background_color = background_color;
+	map.sun_dir = sun_dir;
+	Ok(map)
+}
+
+// Environment and bounds, version 2 and later.
+fn decode_header(r: &mut &[u8]) -> Result<Map> {
+	let (fog_dist, background_color, sun_dir) = (get_f32(r)?, get_vec3(r)?, get_vec3(r)?);
+	let bounded = get_u8(r)? != 0;
+	let bounds = VoxelBounds::new(get_ivec3(r)?, get_ivec3(r)?);
+
+	let mut map = if bounded {
+		if bounds.min != ivec3(0, 0, 0) || bounds.is_empty() {
+			return err(format!("decode map: bad bounds: {}", bounds));
+		}
+		Map::new(bounds.max.map(|v| v as u32))
+	} else {
+		Map::unbounded()
+	};
+	map.fog_dist = fog_dist;
+	map.background_color = background_color;
+	map.sun_dir = sun_dir;
+	Ok(map)
+}
+
+fn decode_palette(r: &mut &[u8]) -> Result<Vec<Voxel>> {
+	let num_types = get_u16(r)?;
+	let mut names = Vec::with_capacity(num_types as usize);
+	for _ in 0..num_types {
+		let len = get_u8(r)? as usize;
+		if r.len() < len {
+			return err("decode map: unexpected end of data");
+		}
+		let (name, rest) = r.split_at(len);
+		names.push(String::from_utf8_lossy(name).into_owned());
+		*r = rest;
+	}
+	voxels_by_file_id(&names)
+}
+
+// Voxels by their id in a map file (index 0 is EMPTY),
+// given the names of the voxel types used in the file (for ids 1, 2, ...).
+fn voxels_by_file_id<S: AsRef<str>>(palette: &[S]) -> Result<Vec<Voxel>> {
+	let types = VoxelTypes::get();
+	let mut voxels = vec![Voxel::EMPTY];
+	for name in palette.iter().map(AsRef::as_ref) {
+		voxels.push(types.by_name(name).ok_or_else(|| error(format!("decode map: unknown voxel type: {}", name)))?);
+	}
+	Ok(voxels)
+}
+
+fn decode_chunks(r: &mut &[u8], map: &mut Map, palette: &[Voxel]) -> Result<()> {
+	let num_chunks = get_u32(r)?;
+	for _ in 0..num_chunks {
+		let pos = get_ivec3(r)?;
+		if chunk_corner(pos).is_none() {
+			return err(format!("decode map: bad chunk position: {}", pos));
+		}
+		let num_runs = get_u32(r)?;
+		let mut voxels = Vec::with_capacity((CHUNK * CHUNK * CHUNK) as usize);
+		for _ in 0..num_runs {
+			let len = get_u16(r)?;
+			let id = get_u8(r)?;
+			let voxel = *palette.get(id as usize).ok_or_else(|| error(format!("decode map: chunk {}: bad voxel id: {}", pos, id)))?;
+			if voxels.len() + len as usize > (CHUNK * CHUNK * CHUNK) as usize {
+				return err(format!("decode map: chunk {}: more than {} voxels", pos, CHUNK * CHUNK * CHUNK));
+			}
+			voxels.extend(std::iter::repeat_n(voxel, len as usize));
+		}
+		if voxels.len() != (CHUNK * CHUNK * CHUNK) as usize {
+			return err(format!("decode map: chunk {}: got {} voxels, want {}", pos, voxels.len(), CHUNK * CHUNK * CHUNK));
+		}
+		for (i, index) in chunk_indices(pos).enumerate() {
+			if voxels[i] != Voxel::EMPTY {
+				map.set(index, voxels[i])
+			}
+		}
+	}
+	Ok(())
+}
+
+fn decode_states(r: &mut &[u8], map: &mut Map) -> Result<()> {
+	let num_states = get_u32(r)?;
+	for _ in 0..num_states {
+		let index = get_ivec3(r)?;
+		let [orientation, damage, data] = get_bytes(r)?;
+		map.set_state(index, VoxelState { orientation, damage, data });
+	}
+	Ok(())
+}
+
+// Run-length encoded non-empty chunks.
+fn encode_chunks(map: &Map) -> Vec<(ivec3, Vec<(u16, Voxel)>)> {
+	let bounds = map.bounds();
+	let min = bounds.min.map(|v| v.div_euclid(CHUNK));
+	let max = (bounds.max - ivec3(1, 1, 1)).map(|v| v.div_euclid(CHUNK)); // inclusive
+
+	let mut chunks = Vec::new();
+	for cz in min.z..=max.z {
+		for cy in min.y..=max.y {
+			for cx in min.x..=max.x {
+				let pos = ivec3(cx, cy, cz);
+				let runs = run_length_encode(chunk_indices(pos).map(|index| map.at(index)));
+				if !(runs.len() == 1 && runs[0].1 == Voxel::EMPTY) {
+					chunks.push((pos, runs))
+				}
+			}
+		}
+	}
+	chunks
+}
+
+// Index of the first voxel in the chunk at chunk position `pos`,
+// or `None` if the chunk's voxel indices would overflow.
+fn chunk_corner(pos: ivec3) -> Option<ivec3> {
+	let corner = |v: i32| v.checked_mul(CHUNK).filter(|c| c.checked_add(CHUNK - 1).is_some());
+	Some(ivec3(corner(pos.x)?, corner(pos.y)?, corner(pos.z)?))
+}
+
+// Voxel indices inside the chunk at chunk position `pos`, in X, Z, Y order.
+fn chunk_indices(pos: ivec3) -> impl Iterator<Item = ivec3> {
+	let corner = pos * CHUNK;
+	(0..CHUNK).flat_map(move |iy| (0..CHUNK).flat_map(move |iz| (0..CHUNK).map(move |ix| corner + ivec3(ix, iy, iz))))
+}
+
+fn run_length_encode(voxels: impl Iterator<Item = Voxel>) -> Vec<(u16, Voxel)> {
+	let mut runs: Vec<(u16, Voxel)> = Vec::new();
+	for v in voxels {
+		match runs.last_mut() {
+			Some((len, last)) if *last == v && *len < u16::MAX => *len += 1,
+			_ => runs.push((1, v)),
+		}
+	}
+	runs
+}
+
+fn put_u16(w: &mut Vec<u8>, v: u16) {
+	w.extend_from_slice(&v.to_le_bytes())
+}
+
+fn put_u32(w: &mut Vec<u8>, v: u32) {
+	w.extend_from_slice(&v.to_le_bytes())
+}
+
+fn put_i32(w: &mut Vec<u8>, v: i32) {
+	w.extend_from_slice(&v.to_le_bytes())
+}
+
+fn put_f32(w: &mut Vec<u8>, v: f32) {
+	w.extend_from_slice(&v.to_le_bytes())
+}
+
+fn put_ivec3(w: &mut Vec<u8>, v: ivec3) {
+	for &c in &[v.x, v.y, v.z] {
+		put_i32(w, c)
+	}
+}
+
+fn put_vec3(w: &mut Vec<u8>, v: vec3) {
+	for &c in &[v.x, v.y, v.z] {
+		put_f32(w, c)
+	}
+}
+
+fn get_bytes<const N: usize>(r: &mut &[u8]) -> Result<[u8; N]> {
+	let mut buf = [0u8; N];
+	r.read_exact(&mut buf).map_err(|_| error("decode map: unexpected end of data"))?;
+	Ok(buf)
+}
+
+fn get_u8(r: &mut &[u8]) -> Result<u8> {
+	Ok(get_bytes::<1>(r)?[0])
+}
+
+fn get_u16(r: &mut &[u8]) -> Result<u16> {
+	Ok(u16::from_le_bytes(get_bytes(r)?))
+}
+
+fn get_u32(r: &mut &[u8]) -> Result<u32> {
+	Ok(u32::from_le_bytes(get_bytes(r)?))
+}
+
+fn get_i32(r: &mut &[u8]) -> Result<i32> {
+	Ok(i32::from_le_bytes(get_bytes(r)?))
+}
+
+fn get_f32(r: &mut &[u8]) -> Result<f32> {
+	Ok(f32::from_le_bytes(get_bytes(r)?))
+}
+
+fn get_ivec3(r: &mut &[u8]) -> Result<ivec3> {
+	Ok(ivec3(get_i32(r)?, get_i32(r)?, get_i32(r)?))
+}
+
+fn get_vec3(r: &mut &[u8]) -> Result<vec3> {
+	Ok(vec3(get_f32(r)?, get_f32(r)?, get_f32(r)?))
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use flate2::write::GzEncoder;
+	use flate2::Compression;
+
+	fn test_map() -> Map {
+		let mut map = Map::flat(uvec3(32, 40, 48));
+		map.fog_dist = 42.0;
+		map.set(ivec3(1, 2, 3), Voxel::LAVA);
+		map.set(ivec3(31, 39, 47), Voxel::CRYSTAL);
+		map
+	}
+
+	fn assert_same(a: &Map, b: &Map) {
+		assert_eq!(a.bounds(), b.bounds());
+		assert_eq!(a.is_unbounded(), b.is_unbounded());
+		assert_eq!(a.fog_dist, b.fog_dist);
+		assert_eq!(a.sun_dir, b.sun_dir);
+		let (min, max) = (a.bounds().min, a.bounds().max);
+		for iz in min.z..max.z {
+			for iy in min.y..max.y {
+				for ix in min.x..max.x {
+					let index = ivec3(ix, iy, iz);
+					assert_eq!(a.at(index), b.at(index), "at {}", index);
+					assert_eq!(a.state_at(index), b.state_at(index), "state at {}", index);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn round_trip() {
+		let mut map = test_map();
+		map.set_state(ivec3(31, 39, 47), VoxelState { orientation: 2, damage: 1, data: 0 });
+		map.set_state(ivec3(5, 0, 6), VoxelState { orientation: 0, damage: 0, data: 9 });
+		let data = encode_map(&map);
+		assert_eq!(map_format_version(&data).unwrap(), MAP_FORMAT_VERSION);
+		assert_same(&map, &decode_map(&data).unwrap());
+	}
+
+	#[test]
+	fn unbounded() {
+		let mut map = Map::flat_unbounded(20);
+		map.set(ivec3(-100, -7, 33), Voxel::LAVA);
+		map.set(ivec3(-1, -1, -1), Voxel::CRYSTAL);
+		map.set_state(ivec3(-1, -1, -1), VoxelState::oriented(VoxelState::DOWN));
+		assert_eq!(map.bounds(), VoxelBounds::new(ivec3(-100, -7, -1), ivec3(20, 1, 34)));
+		assert_same(&map, &decode_map(&encode_map(&map)).unwrap());
+	}
+
+	// A v1 map, 16x16x16, with a single chunk.
+	fn v1_map(pos: ivec3, runs: &[(u16, Voxel)]) -> Vec<u8> {
+		let mut data = MAGIC.to_vec();
+		put_u16(&mut data, 1);
+		for &v in &[1.0, 0.1, 0.2, 0.3, 0.0, 1.0, 0.0] {
+			put_f32(&mut data, v);
+		}
+		for &v in &[16, 16, 16, 1] {
+			put_u32(&mut data, v); // size, num chunks
+		}
+		put_ivec3(&mut data, pos);
+		put_u32(&mut data, runs.len() as u32);
+		for &(len, voxel) in runs {
+			put_u16(&mut data, len);
+			data.push(voxel.id());
+		}
+		data
+	}
+
+	#[test]
+	fn version_1() {
+		// one snow voxel at (1, 2, 3).
+		let offset = 1 + 3 * 16 + 2 * 16 * 16; // X, Z, Y order.
+		let data = v1_map(ivec3(0, 0, 0), &[(offset, Voxel::EMPTY), (1, Voxel::SNOW), (4096 - offset - 1, Voxel::EMPTY)]);
+
+		let map = decode_map(&data).unwrap();
+		assert_eq!(map.bounds(), VoxelBounds::with_size(uvec3(16, 16, 16)));
+		assert_eq!(map.at(ivec3(1, 2, 3)), Voxel::SNOW);
+		assert_eq!(map.at(ivec3(3, 2, 1)), Voxel::EMPTY);
+	}
+
+	#[test]
+	fn palette() {
+		assert_eq!(voxels_by_file_id(&["lava", "snow"]).unwrap(), vec![Voxel::EMPTY, Voxel::LAVA, Voxel::SNOW]);
+		assert!(voxels_by_file_id(&["lava", "unobtainium"]).is_err());
+
+		// a map where voxel id 1 means lava.
+		let mut data = MAGIC.to_vec();
+		put_u16(&mut data, MAP_FORMAT_VERSION);
+		put_u16(&mut data, 1);
+		data.push(4);
+		data.extend_from_slice(b"lava");
+		let rest = &encode_map(&Map::flat(uvec3(16, 16, 16)))[MAGIC.len() + 2..];
+		let skip_palette = 2 + VoxelTypes::get().palette().iter().map(|name| 1 + name.len()).sum::<usize>();
+		data.extend_from_slice(&rest[skip_palette..]);
+		assert_eq!(decode_map(&data).unwrap().at(ivec3(1, 0, 1)), Voxel::LAVA);
+	}
+
+	#[test]
+	fn legacy_json() {
+		let map = test_map();
+		let mut gz = GzEncoder::new(Vec::new(), Compression::fast());
+		serde_json::to_writer(&mut gz, &map).unwrap();
+		let data = gz.finish().unwrap();
+		assert_eq!(map_format_version(&data).unwrap(), 0);
+		assert_same(&map, &decode_map(&data).unwrap());
+	}
+
+	#[test]
+	fn bad_data() {
+		let mut data = encode_map(&test_map());
+		assert!(decode_map(&data[..data.len() - 1]).is_err()); // truncated
+		data[6] = 99; // future version
+		assert!(decode_map(&data).is_err());
+		assert!(decode_map(b"not a map").is_err());
+
+		// chunks that are too large (rejected before decoding them all), or too small.
+		let mut runs = vec![(u16::MAX, Voxel::SNOW); 1000];
+		assert!(decode_map(&v1_map(ivec3(0, 0, 0), &runs)).is_err());
+		runs.truncate(1);
+		runs[0].0 = 4095;
+		assert!(decode_map(&v1_map(ivec3(0, 0, 0), &runs)).is_err());
+
+		// chunk positions whose voxel indices overflow.
+		runs[0].0 = 4096;
+		assert!(decode_map(&v1_map(ivec3(0, 0, 0), &runs)).is_ok());
+		assert!(decode_map(&v1_map(ivec3(i32::MAX / 8, 0, 0), &runs)).is_err());
+		assert!(decode_map(&v1_map(ivec3(0, i32::MIN / 16 - 1, 0), &runs)).is_err());
+	}
+}
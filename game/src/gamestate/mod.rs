@@ -6,11 +6,15 @@ pub mod prelude;
 pub mod internal;
 
 mod boundingbox;
+mod collapse;
 mod effect;
+mod fixed_step;
+mod fluids;
 mod gamestate;
 mod key;
 mod keystates;
 mod map;
+mod map_format;
 mod message;
 mod model;
 mod player;
@@ -11,14 +11,37 @@ pub struct Model {
 	pub yaw: f32, // angle with -Z, CCW.
 	pub pitch: f32,
 	pub feet_phase: f32, // left foot's walking animation phase angle
+	pub state: MoveState,
 
 	pub head_mesh: usize,
 	pub foot_mesh: usize,
 	pub skin_tex: usize,
 }
 
+/// How a Model moves (see `Model::tick`).
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum MoveState {
+	/// On the ground, or falling.
+	#[default]
+	Walking,
+	/// Lower and slower, and not walking off edges.
+	Crouching,
+	/// In a fluid (like water) that is not solid: slowly sinking, moving up and down at will.
+	Swimming,
+	/// On a climbable voxel (like a ladder): not falling, moving up and down at will.
+	Climbing,
+}
+
 const STAIRCLIMB_SPEED: f32 = 1.5 * Player::SPRINT_SPEED;
 
+const CROUCH_HEIGHT: f32 = 0.65; // fraction of the standing height
+const CROUCH_SPEED: f32 = 0.4; // fraction of the walking speed
+const CLIMB_SPEED: f32 = 3.0; // blocks / sec, up or down
+const SWIM_SPEED: f32 = 3.0; // blocks / sec, up or down
+const SWIM_ACCEL: f32 = 4.0; // how quickly swimming approaches the desired speed (1 / sec)
+const WATER_DRAG: f32 = 2.0; // fraction of velocity lost per sec
+const BUOYANCY: f32 = 0.8; // fraction of gravity cancelled when swimming
+
 impl Model {
 	pub fn new(hsize: f32, vsize: f32, head_mesh: usize, foot_mesh: usize, skin_tex: usize) -> Self {
 		Model {
@@ -29,6 +52,7 @@ impl Model {
 			yaw: 0.0,
 			pitch: 0.0,
 			feet_phase: 0.0,
+			state: MoveState::Walking,
 			head_mesh,
 			foot_mesh,
 			skin_tex,
@@ -37,7 +61,32 @@ impl Model {
 	}
 
 	pub fn on_ground(&self, map: &Map) -> bool {
-		!self.pos_ok(map, self.pos - vec3(0.0, 0.05, 0.0))
+		self.on_ground_at(map, self.pos)
+	}
+
+	fn on_ground_at(&self, map: &Map, pos: vec3) -> bool {
+		!self.pos_ok(map, pos - vec3(0.0, 0.05, 0.0))
+	}
+
+	/// Current height: `vsize`, or less when crouching.
+	pub fn height(&self) -> f32 {
+		match self.state {
+			MoveState::Crouching => CROUCH_HEIGHT * self.vsize,
+			_ => self.vsize,
+		}
+	}
+
+	/// Is there room to stand up (to full height)?
+	fn can_stand(&self, map: &Map) -> bool {
+		!map.bumps(&self.bounds(self.pos, self.vsize))
+	}
+
+	/// Friction of the voxel right below the feet. 1 (no sliding) if there is none.
+	fn ground_friction(&self, map: &Map) -> f32 {
+		match map.at_pos(self.pos - vec3(0.0, 0.05, 0.0)) {
+			v if v.is_solid() => v.friction(),
+			_ => 1.0,
+		}
 	}
 
 	// _________________________________________________________ mutators
@@ -48,10 +97,49 @@ impl Model {
 		}
 	}
 
+	/// Crouch, or stand up again if there is room. Only when walking.
+	pub fn try_crouch(&mut self, map: &Map, crouch: bool) {
+		match self.state {
+			MoveState::Walking if crouch => self.state = MoveState::Crouching,
+			MoveState::Crouching if !crouch && self.can_stand(map) => self.state = MoveState::Walking,
+			_ => (),
+		}
+	}
+
+	/// Swim or climb up (`dir` 1), down (-1), or hold on (0, climbing only).
+	/// No effect unless swimming or climbing.
+	pub fn try_climb(&mut self, dir: f32) {
+		match self.state {
+			MoveState::Climbing => self.vel.y = CLIMB_SPEED * dir,
+			MoveState::Swimming if dir != 0.0 => self.vel.y = SWIM_SPEED * dir,
+			_ => (),
+		}
+	}
+
 	pub fn try_walk(&mut self, dt: f32, map: &Map, walk_speed: vec3) {
+		match self.state {
+			MoveState::Walking => self.try_walk_ground(dt, map, walk_speed),
+			MoveState::Crouching => self.try_walk_ground(dt, map, CROUCH_SPEED * walk_speed),
+			MoveState::Climbing => {
+				self.vel.x = 0.5 * walk_speed.x;
+				self.vel.z = 0.5 * walk_speed.z;
+			}
+			MoveState::Swimming => {
+				let k = f32::min(1.0, SWIM_ACCEL * dt);
+				self.vel.x += k * (0.5 * walk_speed.x - self.vel.x);
+				self.vel.z += k * (0.5 * walk_speed.z - self.vel.z);
+			}
+		}
+	}
+
+	fn try_walk_ground(&mut self, dt: f32, map: &Map, walk_speed: vec3) {
 		if self.on_ground(map) {
-			self.vel.x = walk_speed.x;
-			self.vel.z = walk_speed.z;
+			// On slippery ground (friction < 1), approach walking speed gradually:
+			// close a fraction `friction` of the gap every 1/60 s.
+			let friction = self.ground_friction(map);
+			let k = if friction >= 1.0 { 1.0 } else { 1.0 - (1.0 - friction).powf(60.0 * dt) };
+			self.vel.x += k * (walk_speed.x - self.vel.x);
+			self.vel.z += k * (walk_speed.z - self.vel.z);
 		} else {
 			let airctl = 1.0; // blocks / sec2. compare to G
 			self.vel += airctl * walk_speed * dt;
@@ -63,6 +151,7 @@ impl Model {
 	// ___________________________________________________________ tick
 
 	pub fn tick(&mut self, dt: f32, map: &Map) {
+		self.tick_state(map);
 		self.tick_gravity(dt, map);
 		self.tick_rescue(dt, map);
 		self.tick_respawn(dt, map);
@@ -76,11 +165,40 @@ impl Model {
 		// TODO: feet pos
 	}
 
+	// start or stop swimming or climbing, depending on the voxels we're in.
+	// (crouching is controlled by `try_crouch`).
+	fn tick_state(&mut self, map: &Map) {
+		let at = |h: f32| map.at_pos(self.pos + vec3(0.0, h, 0.0));
+		let waist = at(0.5 * self.height());
+		let next = if waist.is_fluid() && !waist.is_solid() {
+			MoveState::Swimming
+		} else if waist.is_climbable() || at(0.1).is_climbable() {
+			MoveState::Climbing
+		} else if self.state == MoveState::Crouching {
+			MoveState::Crouching
+		} else {
+			MoveState::Walking
+		};
+		// crouching: only stand up for swimming or climbing if there is room.
+		if next != self.state && (self.state != MoveState::Crouching || self.can_stand(map)) {
+			self.state = next;
+		}
+	}
+
 	// apply gravitational acceleration
 	fn tick_gravity(&mut self, dt: f32, _map: &Map) {
-		self.vel.y -= GRAVITY * dt;
-		let damp = 0.05;
-		self.vel *= 1.0 - damp * dt;
+		match self.state {
+			MoveState::Climbing => (), // held, see `try_climb`
+			MoveState::Swimming => {
+				self.vel.y -= (1.0 - BUOYANCY) * GRAVITY * dt;
+				self.vel *= f32::max(0.0, 1.0 - WATER_DRAG * dt);
+			}
+			MoveState::Walking | MoveState::Crouching => {
+				self.vel.y -= GRAVITY * dt;
+				let damp = 0.05;
+				self.vel *= 1.0 - damp * dt;
+			}
+		}
 	}
 
 	// rescue player if somehow stuck inside a block: move them up.
@@ -93,17 +211,24 @@ impl Model {
 	// rescue player if somehow fallen off the map: reset position.
 	// TODO: move to server logic!
 	fn tick_respawn(&mut self, _dt: f32, map: &Map) {
-		if self.pos.y < -30.0 {
-			let (nx, ny, nz) = map.size().map(|v| v as f32).into();
-			let pos = vec3(rand(0.0, nx), ny, rand(0.0, nz));
+		let bounds = map.bounds();
+		if self.pos.y < (bounds.min.y as f32) - 30.0 {
+			let (min, max) = (bounds.min.to_vec(), bounds.max.to_vec());
+			let pos = vec3(rand(min.x, max.x), max.y, rand(min.z, max.z));
 			self.pos = pos;
 		}
 	}
 
 	// advance animation
 	fn tick_anim(&mut self, dt: f32, _map: &Map) {
+		let speed = match self.state {
+			MoveState::Walking => 8.0,
+			MoveState::Crouching => 4.0,
+			MoveState::Swimming => 3.0,
+			MoveState::Climbing => 3.0 * self.vel.y.abs(),
+		};
 		if self.vel != vec3::ZERO {
-			self.feet_phase += 8.0 * dt;
+			self.feet_phase += speed * dt;
 			if self.feet_phase > 2.0 * PI {
 				self.feet_phase -= 2.0 * PI;
 			}
@@ -114,41 +239,37 @@ impl Model {
 	// Zero velocity component if bumping into wall.
 	// Do not move through walls.
 	fn tick_move(&mut self, dt: f32, map: &Map) {
-		let mut xbump = false;
-		let mut zbump = false;
 		let delta = self.vel * dt;
-		let sub_delta = delta / 16.0;
-
-		for _i in 0..16 {
-			let dx = vec3(sub_delta.x, 0.0, 0.0);
-			let dy = vec3(0.0, sub_delta.y, 0.0);
-			let dz = vec3(0.0, 0.0, sub_delta.z);
-
-			if self.pos_ok(map, self.pos + dx) {
-				self.pos += dx;
-			} else {
-				xbump = true;
-			}
-
-			if self.pos_ok(map, self.pos + dy) {
-				self.pos += dy;
-			} else {
-				self.vel.y = 0.0;
+		let sweep = map.sweep(&self.bounds_for(self.pos), delta);
+
+		// crouching: do not walk off edges (stop moving along an axis that would leave no ground below).
+		let mut moved = sweep.delta;
+		if self.state == MoveState::Crouching && self.on_ground(map) {
+			if !self.on_ground_at(map, self.pos + vec3(moved.x, moved.y, 0.0)) {
+				moved.x = 0.0;
+				self.vel.x = 0.0;
 			}
-
-			if self.pos_ok(map, self.pos + dz) {
-				self.pos += dz;
-			} else {
-				zbump = true;
+			if !self.on_ground_at(map, self.pos + moved) {
+				moved.z = 0.0;
+				self.vel.z = 0.0;
 			}
 		}
+		self.pos += moved;
+		if sweep.normal.y != 0 {
+			self.vel.y = 0.0;
+		}
+		let xbump = sweep.normal.x != 0;
+		let zbump = sweep.normal.z != 0;
 
 		// starirclimb
 		let on_ground = !self.pos_ok(map, self.pos - vec3(0.0, 0.05, 0.0));
 		if (xbump || zbump) && self.vel.y >= 0.0 && (on_ground) {
-			let probe_pos = self.pos + vec3(delta.x, 1.1, delta.z); // what if we kept moving horizontally and took one step up?
-			if self.pos_ok(map, probe_pos) {
-				self.pos += vec3(delta.x, 0.0, delta.z);
+			// what if we took one step up and kept moving horizontally?
+			let step = vec3(0.0, 1.1, 0.0);
+			let rest = vec3(delta.x - sweep.delta.x, 0.0, delta.z - sweep.delta.z);
+			let climb = map.sweep(&self.bounds_for(self.pos + step), rest);
+			if self.pos_ok(map, self.pos + step) && climb.normal.x == 0 && climb.normal.z == 0 {
+				self.pos += rest; // into the step, `tick_rescue` lifts us on top.
 			} else {
 				if xbump {
 					self.vel.x = 0.0;
@@ -168,12 +289,17 @@ impl Model {
 
 	// bounding box for a player at position `pos`.
 	fn bounds_for(&self, pos: vec3) -> BoundingBox {
+		self.bounds(pos, self.height())
+	}
+
+	fn bounds(&self, pos: vec3, height: f32) -> BoundingBox {
 		let min = pos - vec3(self.hsize / 2.0, 0.0, self.hsize / 2.0);
-		let max = pos + vec3(self.hsize / 2.0, self.vsize, self.hsize / 2.0);
+		let max = pos + vec3(self.hsize / 2.0, height, self.hsize / 2.0);
 		BoundingBox::new(min, max)
 	}
 
 	/// Fully draw this model, as seen from a 3rd-person perspective.
+	#[cfg(feature = "gl")]
 	pub fn draw_third_person(&self, ctx: &GLContext) {
 		let (shader, meshes) = self.draw_setup(ctx);
 		self.draw_head(shader, meshes);
@@ -181,11 +307,13 @@ impl Model {
 	}
 
 	/// Partially draw this model, as seen from a 1rd-person perspective.
+	#[cfg(feature = "gl")]
 	pub fn draw_first_person(&self, ctx: &GLContext) {
 		let (shader, meshes) = self.draw_setup(ctx);
 		self.draw_feet(shader, meshes);
 	}
 
+	#[cfg(feature = "gl")]
 	fn draw_setup<'a>(&self, ctx: &'a GLContext) -> (&'a AnimShader, &'a MeshPack) {
 		ctx.set_depth_test(true);
 		let shader = ctx.shaders().bind_anim_shader();
@@ -194,6 +322,7 @@ impl Model {
 		(shader, ctx.meshes())
 	}
 
+	#[cfg(feature = "gl")]
 	fn draw_head(&self, shader: &AnimShader, meshes: &MeshPack) {
 		let head = self.head_pos_internal();
 		let head_pitch = self.pitch / 2.0; // reduced head pitch looks less silly
@@ -201,10 +330,12 @@ impl Model {
 		meshes.heads[self.head_mesh].bind_and_draw();
 	}
 
+	#[cfg(feature = "gl")]
 	fn head_pos_internal(&self) -> vec3 {
-		vec3(0.0, self.vsize - 0.5 * self.hsize, 0.0)
+		vec3(0.0, self.height() - 0.5 * self.hsize, 0.0)
 	}
 
+	#[cfg(feature = "gl")]
 	fn draw_feet(&self, shader: &AnimShader, meshes: &MeshPack) {
 		let (foot1, foot2) = self.feet_pos_internal();
 		shader.set_transform(0.0, foot1, self.yaw, self.pos);
@@ -213,10 +344,15 @@ impl Model {
 		meshes.feet[self.foot_mesh].bind_and_draw();
 	}
 
+	#[cfg(feature = "gl")]
 	fn feet_pos_internal(&self) -> (vec3, vec3) {
 		let anim_r = 0.2;
 		let c = anim_r * self.feet_phase.cos();
 		let s = anim_r * self.feet_phase.sin();
+		if self.state == MoveState::Climbing {
+			// stepping up and down the rungs.
+			return (vec3(-0.35 * self.hsize, anim_r + s, 0.0), vec3(0.35 * self.hsize, anim_r - s, 0.0));
+		}
 		(
 			vec3(-0.35 * self.hsize, f32::max(0.0, s), c),  // left
 			vec3(0.35 * self.hsize, f32::max(0.0, -s), -c), // right
@@ -281,4 +417,131 @@ mod test {
 		s.yaw = 180.0 * DEG;
 		assert_eq!(s.look_dir().map(|v| v.round()), vec3(0.0, 0.0, 1.0));
 	}
+
+	#[test]
+	fn tick_move() {
+		let mut map = Map::flat(uvec3(32, 32, 32));
+		for iz in 0..32 {
+			for iy in 1..4 {
+				map.set(ivec3(20, iy, iz), Voxel::GREYSTONE); // wall, too high to climb
+			}
+		}
+
+		// walking into the wall: ends up touching it, at any frame rate.
+		for &dt in &[1.0 / 60.0, 1.0 / 15.0, 0.5] {
+			let mut m = Model::new(0.8, 1.8, 0, 0, 0);
+			m.pos = vec3(10.0, 1.0, 10.0);
+			for _ in 0..((4.0 / dt) as usize) {
+				m.vel = vec3(5.0, 0.0, 0.0);
+				m.tick_move(dt, &map);
+			}
+			assert!((m.pos - vec3(19.6, 1.0, 10.0)).len() < 1e-3, "dt {}: {}", dt, m.pos);
+			assert!(m.pos_ok(&map, m.pos));
+			assert!(m.on_ground(&map));
+		}
+
+		// climbing a step.
+		for iz in 0..32 {
+			map.set(ivec3(15, 1, iz), Voxel::GREYSTONE);
+		}
+		let mut m = Model::new(0.8, 1.8, 0, 0, 0);
+		m.pos = vec3(10.0, 1.0, 10.0);
+		for _ in 0..120 {
+			m.vel.x = 3.0;
+			m.tick(1.0 / 60.0, &map);
+		}
+		assert!(m.pos.x > 15.0 && m.pos.y >= 2.0 && m.on_ground(&map), "{}", m.pos); // on top
+
+		// falling fast: lands on the floor, does not tunnel through it.
+		let mut m = Model::new(0.8, 1.8, 0, 0, 0);
+		m.pos = vec3(10.0, 25.0, 10.0);
+		m.vel = vec3(0.0, -1000.0, 0.0);
+		m.tick_move(0.1, &map);
+		assert_eq!(m.pos, vec3(10.0, 1.0, 10.0));
+		assert_eq!(m.vel.y, 0.0);
+	}
+
+	#[test]
+	fn move_states() {
+		let dt = FixedStep::DT;
+		let mut map = Map::flat(uvec3(32, 32, 32));
+
+		// crouching: lower, slower, and not walking off the edge of a platform.
+		for iz in 0..32 {
+			for ix in 0..8 {
+				map.set(ivec3(ix, 4, iz), Voxel::GREYSTONE);
+			}
+		}
+		let mut m = Model::new(0.8, 1.8, 0, 0, 0);
+		m.pos = vec3(4.0, 5.0, 10.0);
+		m.try_crouch(&map, true);
+		assert_eq!(m.state, MoveState::Crouching);
+		assert!(m.height() < 1.5);
+		for _ in 0..120 {
+			m.try_walk(dt, &map, vec3(6.0, 0.0, 0.0));
+			m.tick(dt, &map);
+		}
+		assert_eq!(m.pos.y, 5.0);
+		assert!(m.pos.x > 8.0 && m.pos.x < 8.4, "{}", m.pos); // overhanging, but not falling
+		assert!(m.on_ground(&map));
+		m.try_crouch(&map, false);
+		assert_eq!(m.state, MoveState::Walking);
+
+		// no room to stand up under a low ceiling.
+		for iz in 0..32 {
+			map.set(ivec3(12, 2, iz), Voxel::GREYSTONE);
+		}
+		let mut m = Model::new(0.8, 1.8, 0, 0, 0);
+		m.pos = vec3(12.5, 1.0, 10.0);
+		m.state = MoveState::Crouching;
+		m.try_crouch(&map, false);
+		assert_eq!(m.state, MoveState::Crouching);
+
+		// climbing a ladder: up, holding on, down.
+		let ladder = VoxelTypes::get().by_name("ladder").unwrap();
+		for iy in 1..8 {
+			map.set(ivec3(20, iy, 10), ladder);
+		}
+		let mut m = Model::new(0.8, 1.8, 0, 0, 0);
+		m.pos = vec3(20.5, 1.0, 10.5);
+		for _ in 0..60 {
+			m.try_climb(1.0);
+			m.tick(dt, &map);
+		}
+		assert_eq!(m.state, MoveState::Climbing);
+		assert!((m.pos.y - 4.0).abs() < 0.1, "{}", m.pos);
+		for _ in 0..60 {
+			m.try_climb(0.0);
+			m.tick(dt, &map);
+		}
+		assert!((m.pos.y - 4.0).abs() < 0.1, "{}", m.pos);
+		for _ in 0..120 {
+			m.try_climb(-1.0);
+			m.tick(dt, &map);
+		}
+		assert_eq!(m.pos.y, 1.0);
+
+		// swimming: sinking slowly, swimming up.
+		let water = VoxelTypes::get().by_name("water").unwrap();
+		for iy in 1..10 {
+			for iz in 20..30 {
+				for ix in 20..30 {
+					map.set(ivec3(ix, iy, iz), water);
+				}
+			}
+		}
+		let mut m = Model::new(0.8, 1.8, 0, 0, 0);
+		m.pos = vec3(25.0, 5.0, 25.0);
+		for _ in 0..60 {
+			m.try_climb(0.0);
+			m.tick(dt, &map);
+		}
+		assert_eq!(m.state, MoveState::Swimming);
+		assert!(m.pos.y < 5.0 && m.pos.y > 4.0, "{}", m.pos);
+		for _ in 0..30 {
+			m.try_climb(1.0);
+			m.tick(dt, &map);
+		}
+		assert!(m.pos.y > 5.0, "{}", m.pos);
+	}
 }
@@ -5,15 +5,17 @@ pub struct Player {
 	pub model: Model,
 	pub weapons: [Weapon; 2],
 	pub selected_weapon: usize,
+	pub health: f32,
 }
 
 impl Player {
 	const H_SIZE: f32 = 0.8;
 	const V_SIZE: f32 = 1.9;
-	const CAM_HEIGHT: f32 = Self::V_SIZE - 0.05;
+	const CAM_BELOW_TOP: f32 = 0.05;
 	pub const WALK_SPEED: f32 = 6.0;
 	pub const JUMP_SPEED: f32 = 9.0;
 	pub const SPRINT_SPEED: f32 = 9.0; // Used by Model stairclimb. TODO: remove dependency
+	pub const MAX_HEALTH: f32 = 100.0;
 
 	pub fn new(skin: usize) -> Self {
 		let model = Model::new(Self::H_SIZE, Self::V_SIZE, skin, skin, skin);
@@ -21,6 +23,7 @@ impl Player {
 			model,
 			weapons: [Weapon::laser(), Weapon::snow_cannon()],
 			selected_weapon: 0,
+			health: Self::MAX_HEALTH,
 		}
 	}
 
@@ -38,6 +41,22 @@ impl Player {
 		self.model.look_dir()
 	}
 
+	/// Is the player standing in (or on) lava?
+	pub fn in_lava(&self, map: &Map) -> bool {
+		let feet = self.bottom_pos();
+		map.at_pos(feet + vec3(0.0, 0.1, 0.0)) == Voxel::LAVA || map.at_pos(feet - vec3(0.0, 0.1, 0.0)) == Voxel::LAVA
+	}
+
+	/// Lose `damage` health. When all health is lost, respawn with full health.
+	pub fn take_damage(&mut self, damage: f32, map: &Map) {
+		self.health -= damage;
+		if self.health <= 0.0 {
+			self.health = Self::MAX_HEALTH;
+			self.model.pos = map.spawn_point();
+			self.model.vel = vec3::ZERO;
+		}
+	}
+
 	// ___________________________________ movement
 
 	pub fn jump(&mut self, gs: &GameState) {
@@ -51,6 +70,16 @@ impl Player {
 		self.model.try_walk(dt, gs.map(), speed * dir.safe_normalized());
 	}
 
+	/// Crouch, or stand up again (see `Model::try_crouch`).
+	pub fn crouch(&mut self, crouch: bool, gs: &GameState) {
+		self.model.try_crouch(gs.map(), crouch)
+	}
+
+	/// Swim or climb up (`dir` 1) or down (-1), when swimming or climbing.
+	pub fn climb(&mut self, dir: f32) {
+		self.model.try_climb(dir)
+	}
+
 	/// Rotate the player's view direction by `yaw`, `pitch` radians.
 	pub fn rotate(&mut self, yaw: f32, pitch: f32) {
 		self.model.rotate(yaw, pitch)
@@ -67,6 +96,7 @@ impl Player {
 		(self.weapon_pos_abs(), self.view_pos(), self.look_dir())
 	}
 
+	#[cfg(feature = "gl")]
 	fn selected_weapon(&self) -> &Weapon {
 		&self.weapons[self.selected_weapon]
 	}
@@ -80,6 +110,7 @@ impl Player {
 		//vec3(, self.model.vsize * 0.7, 0.0) + WEAPON_DIST * self.model.look_right()
 	}
 
+	#[cfg(feature = "gl")]
 	fn weapon_pos_internal(&self) -> vec3 {
 		Self::WEAPON_POS_INTERNAL
 	}
@@ -93,11 +124,12 @@ impl Player {
 
 	/// Position of the player's eye (world coordinates).
 	pub fn view_pos(&self) -> vec3 {
-		self.bottom_pos() + Self::CAM_HEIGHT * vec3::EY
+		self.bottom_pos() + (self.model.height() - Self::CAM_BELOW_TOP) * vec3::EY
 	}
 
 	/// Draw the player from a 3rd-person perspective.
 	/// I.e.: draw the entire model, but not the crosshair.
+	#[cfg(feature = "gl")]
 	pub fn draw_third_person(&self, ctx: &GLContext) {
 		self.model.draw_third_person(ctx);
 		self.draw_weapon(ctx);
@@ -105,11 +137,13 @@ impl Player {
 
 	/// Draw the player from a 1st-person perspective.
 	/// I.e.: draw the player-visible part of the model, and the weapon and crosshair.
+	#[cfg(feature = "gl")]
 	pub fn draw_first_person(&self, ctx: &GLContext) {
 		self.model.draw_first_person(ctx);
 		self.draw_weapon(ctx);
 	}
 
+	#[cfg(feature = "gl")]
 	fn draw_weapon(&self, ctx: &GLContext) {
 		self.selected_weapon().draw(ctx, (self.model.pitch, self.weapon_pos_internal(), self.model.yaw, self.model.pos));
 	}
@@ -21,6 +21,10 @@ impl Players {
 		&self.0[&player_id]
 	}
 
+	pub fn contains(&self, player_id: ID) -> bool {
+		self.0.contains_key(&player_id)
+	}
+
 	pub fn get_mut(&mut self, player_id: ID) -> &mut Player {
 		self.0.get_mut(&player_id).expect("BUG: player not found")
 	}
@@ -41,10 +45,13 @@ impl Players {
 		self.0.len()
 	}
 
-	pub fn draw(&self, player_id: usize, ctx: &GLContext) {
+	/// Draw all players. Player `first_person`, if any,
+	/// is drawn as seen through their own eyes.
+	#[cfg(feature = "gl")]
+	pub fn draw(&self, first_person: Option<ID>, ctx: &GLContext) {
 		ctx.textures().bind_skins();
 		for (&i, player) in self.0.iter() {
-			if i == player_id {
+			if Some(i) == first_person {
 				player.draw_first_person(ctx)
 			} else {
 				player.draw_third_person(ctx)
@@ -20,24 +20,27 @@ impl EditGun {
 		}
 	}
 
+	#[cfg(feature = "gl")]
 	pub fn draw(&self, _ctx: &GLContext, _orientation: (f32, vec3, f32, vec3)) {
 		// not visible
 	}
 
 	fn add_block(&mut self, (_pos, view, dir): WeaponOrientation, gs: &GameState, updates: &mut Updates) {
-		if let Some(hit) = Weapon::hit_point(gs, view, dir, true /*bw_offset*/) {
+		if let Some(hit) = Weapon::hit(gs, view, dir) {
 			updates.push(UpdateMap {
-				index: Map::voxel_index(hit),
+				index: hit.before(), // against the face that was hit
 				voxel: self.selected_block,
+				state: VoxelState::oriented(VoxelState::orientation_of(-dir)), // facing the player
 			});
 		}
 	}
 
 	fn remove_block(&mut self, (_pos, view, dir): WeaponOrientation, gs: &GameState, updates: &mut Updates) {
-		if let Some(hit) = Weapon::hit_point(gs, view, dir, false /*bw_offset*/) {
+		if let Some(hit) = Weapon::hit(gs, view, dir) {
 			updates.push(UpdateMap {
-				index: Map::voxel_index(hit),
+				index: hit.index,
 				voxel: Voxel::EMPTY,
+				state: VoxelState::default(),
 			});
 		}
 		// TODO: debris effect
@@ -26,9 +26,9 @@ impl Laser {
 		}
 		self.last_shoot_time = gs.time;
 
-		if let Some(hit) = Weapon::hit_point(gs, view, dir, false /*bw_offset*/) {
-			self.explode(hit, &gs.map, updates);
-			updates.push(self.laserbeam_effect(pos, hit));
+		if let Some(hit) = Weapon::hit(gs, view, dir) {
+			self.explode(hit.pos, &gs.map, updates);
+			updates.push(self.laserbeam_effect(pos, hit.pos));
 		} else {
 			updates.push(self.laserbeam_effect(pos, view + Weapon::SHOOT_DIST * dir));
 		}
@@ -49,12 +49,30 @@ impl Laser {
 					let ipos = icenter + ivec3(dx, dy, dz);
 					let pos = ipos.map(|v| v as f32 + 0.5);
 					let dist = (pos - center).len() + rand(-0.5, 0.5);
-					if dist < r_outer && dist > r_inner && map.at(ipos) != Voxel::EMPTY {
-						updates.push(UpdateMap { index: ipos, voxel: Voxel::LAVA });
+					let voxel = map.at(ipos);
+					if voxel == Voxel::EMPTY {
+						continue;
 					}
-					if dist < r_inner && map.at(ipos) != Voxel::EMPTY {
-						updates.push(UpdateMap { index: ipos, voxel: Voxel::EMPTY });
-						updates.push(AddEffect(Self::explosion_effect(pos)));
+					if dist < r_outer && dist > r_inner && voxel.melts() {
+						updates.push(UpdateMap {
+							index: ipos,
+							voxel: Voxel::LAVA,
+							state: VoxelState::default(),
+						});
+					}
+					// hard voxels (e.g. stone) crack over several shots.
+					if dist < r_inner {
+						let state = map.state_at(ipos).damaged(1);
+						if state.damage >= voxel.hit_points() {
+							updates.push(UpdateMap {
+								index: ipos,
+								voxel: Voxel::EMPTY,
+								state: VoxelState::default(),
+							});
+							updates.push(AddEffect(Self::explosion_effect(pos)));
+						} else {
+							updates.push(UpdateMap { index: ipos, voxel, state });
+						}
 					}
 				}
 			}
@@ -76,6 +94,7 @@ impl Laser {
 		AddEffect(Effect::TrailEffect(Trail::new(start, stop, RED)))
 	}
 
+	#[cfg(feature = "gl")]
 	pub fn draw(&self, ctx: &GLContext, (pitch, int, yaw, ext): (f32, vec3, f32, vec3)) {
 		// TODO: Weapon::draw()
 		let shader = ctx.shaders().bind_anim_shader();
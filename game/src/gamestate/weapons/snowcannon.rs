@@ -24,9 +24,9 @@ impl SnowCannon {
 		}
 		self.last_shoot_time = gs.time;
 
-		if let Some(hit) = Weapon::hit_point(gs, view, dir, true /*bw_offset*/) {
-			self.explode(hit, &gs.map, updates);
-			updates.push(Self::snowbeam_effect(pos, hit));
+		if let Some(hit) = Weapon::hit(gs, view, dir) {
+			self.explode(hit.pos, &gs.map, updates);
+			updates.push(Self::snowbeam_effect(pos, hit.pos));
 		} else {
 			updates.push(Self::snowbeam_effect(pos, view + Weapon::SHOOT_DIST * dir));
 		}
@@ -49,7 +49,11 @@ impl SnowCannon {
 					let pos = ipos.map(|v| v as f32 + 0.5);
 					let dist = (pos - center).len() + rand(-0.1, 0.1);
 					if dist < r && map.at(ipos) == Voxel::EMPTY {
-						updates.push(UpdateMap { index: ipos, voxel: Voxel::SNOW });
+						updates.push(UpdateMap {
+							index: ipos,
+							voxel: Voxel::SNOW,
+							state: VoxelState::default(),
+						});
 						updates.push(AddEffect(Self::snow_effect(pos)));
 					}
 				}
@@ -72,6 +76,7 @@ impl SnowCannon {
 		AddEffect(Effect::TrailEffect(Trail::new(start, stop, WHITE)))
 	}
 
+	#[cfg(feature = "gl")]
 	pub fn draw(&self, ctx: &GLContext, (pitch, int, yaw, ext): (f32, vec3, f32, vec3)) {
 		let shader = ctx.shaders().bind_anim_shader();
 		shader.set_transform(pitch, int, yaw, ext);
@@ -31,6 +31,7 @@ impl Weapon {
 		}
 	}
 
+	#[cfg(feature = "gl")]
 	pub fn draw(&self, ctx: &GLContext, orientation: (f32, vec3, f32, vec3)) {
 		match self {
 			Weapon::Laser(w) => w.draw(ctx, orientation),
@@ -41,25 +42,8 @@ impl Weapon {
 
 	pub const SHOOT_DIST: f32 = 300.0;
 
-	pub fn hit_point(gs: &GameState, start: vec3, dir: vec3, bw_offset: bool) -> Option<vec3> {
-		gs.map().intersect(start, dir, Self::SHOOT_DIST, bw_offset)
-		// let map = &gs.map;
-		// // TODO: hit other players;
-		// let delta = 0.1;
-		// let mut t = 0.0;
-		// for _i in 0..1000 {
-		// 	// TODO: observe SHOOT_DIST
-		// 	t += delta;
-		// 	let probe = start + t * dir;
-		// 	let probe_idx = Map::voxel_index(probe);
-		// 	if map.at(probe_idx) != Voxel::EMPTY {
-		// 		if bw_offset {
-		// 			return Some(start + (t - delta) * dir);
-		// 		} else {
-		// 			return Some(probe);
-		// 		}
-		// 	}
-		// }
-		// None
+	/// Where a shot from `start` in direction `dir` hits the map, if within `SHOOT_DIST`.
+	pub fn hit(gs: &GameState, start: vec3, dir: vec3) -> Option<RayHit> {
+		gs.map().raycast(start, dir, Self::SHOOT_DIST)
 	}
 }
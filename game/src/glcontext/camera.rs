@@ -35,6 +35,7 @@ pub fn camera_matrix(viewport: (u32, u32), pos: vec3, yaw: f32, pitch: f32) -> m
 	matrix
 }
 
+#[cfg(feature = "gl")]
 pub fn isometric_matrix(viewport: (u32, u32)) -> mat4 {
 	// Scale geometry and far plane by s.
 	// Equivalent to brining near plane closer to the lens while keeping the FOV.
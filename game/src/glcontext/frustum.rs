@@ -0,0 +1,88 @@
+use super::internal::*;
+
+/// The region of space visible through a camera (see `camera_matrix`),
+/// for skipping objects that are off-screen.
+///
+/// Bounded by 6 planes (left, right, bottom, top, near, far),
+/// each with its normal pointing inwards.
+#[derive(Clone, Debug)]
+pub struct Frustum {
+	planes: [(vec3, f32); 6], // (normal, offset): `normal.dot(p) + offset >= 0` for points `p` inside.
+}
+
+impl Frustum {
+	/// Extract the frustum planes from a projection (times view) matrix.
+	/// (Gribb & Hartmann, "Fast Extraction of Viewing Frustum Planes from the World-View-Projection Matrix").
+	pub fn from_matrix(m: &mat4) -> Self {
+		let a = m.as_array(); // column-major
+		let row = |r: usize| [a[r], a[4 + r], a[8 + r], a[12 + r]];
+		let plane = |p: [f32; 4]| (vec3(p[0], p[1], p[2]), p[3]);
+		let add = |a: [f32; 4], b: [f32; 4], sign: f32| [a[0] + sign * b[0], a[1] + sign * b[1], a[2] + sign * b[2], a[3] + sign * b[3]];
+
+		let w = row(3);
+		Self {
+			planes: [
+				plane(add(w, row(0), 1.0)),  // left
+				plane(add(w, row(0), -1.0)), // right
+				plane(add(w, row(1), 1.0)),  // bottom
+				plane(add(w, row(1), -1.0)), // top
+				plane(add(w, row(2), 1.0)),  // near
+				plane(add(w, row(2), -1.0)), // far
+			],
+		}
+	}
+
+	/// The frustum of `camera_matrix`.
+	pub fn for_camera(viewport: (u32, u32), pos: vec3, yaw: f32, pitch: f32) -> Self {
+		Self::from_matrix(&camera_matrix(viewport, pos, yaw, pitch))
+	}
+
+	/// Does the axis-aligned box from `min` to `max` (partially) overlap the frustum?
+	/// Conservative: may return true for some boxes just outside of the corners.
+	pub fn intersects_box(&self, min: vec3, max: vec3) -> bool {
+		self.planes.iter().all(|&(n, d)| {
+			// the box corner furthest along the normal must be inside.
+			let p = vec3(
+				if n.x >= 0.0 { max.x } else { min.x },
+				if n.y >= 0.0 { max.y } else { min.y },
+				if n.z >= 0.0 { max.z } else { min.z },
+			);
+			n.dot(p) + d >= 0.0
+		})
+	}
+
+	/// Is a point inside the frustum?
+	pub fn contains(&self, p: vec3) -> bool {
+		self.intersects_box(p, p)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn frustum() {
+		// camera at the origin, looking down -z.
+		let f = Frustum::for_camera((800, 600), vec3::ZERO, 0.0, 0.0);
+		assert!(f.contains(vec3(0.0, 0.0, -10.0)));
+		assert!(!f.contains(vec3(0.0, 0.0, 10.0))); // behind
+		assert!(!f.contains(vec3(0.0, 0.0, -0.01))); // before the near plane
+		assert!(!f.contains(vec3(-30.0, 0.0, -10.0))); // left
+		assert!(!f.contains(vec3(30.0, 0.0, -10.0))); // right
+		assert!(!f.contains(vec3(0.0, 20.0, -10.0))); // above
+		assert!(!f.contains(vec3(0.0, 0.0, -1.0e6))); // beyond the far plane
+
+		// a box straddling the edge of the view is visible, if only partially.
+		assert!(f.intersects_box(vec3(-30.0, -1.0, -11.0), vec3(0.0, 1.0, -9.0)));
+		assert!(!f.intersects_box(vec3(-30.0, -1.0, -11.0), vec3(-20.0, 1.0, -9.0)));
+		// a box around the camera is always visible.
+		assert!(f.intersects_box(vec3(-1.0, -1.0, -1.0), vec3(1.0, 1.0, 1.0)));
+
+		// moved and turned: looking down +x (see `Model::look_dir`) from (100, 0, 0).
+		let f = Frustum::for_camera((800, 600), vec3(100.0, 0.0, 0.0), -90.0 * DEG, 0.0);
+		assert!(f.contains(vec3(110.0, 0.0, 0.0)));
+		assert!(!f.contains(vec3(90.0, 0.0, 0.0)));
+		assert!(!f.contains(vec3(100.0, 0.0, -10.0)));
+	}
+}
@@ -28,6 +28,11 @@ impl GLContext {
 		//glViewport(0, 0, self.viewport.0 as i32, self.viewport.1 as i32);
 	}
 
+	/// The region visible from a camera (position, yaw, pitch), as set by `set_matrix`.
+	pub fn frustum(&self, (pos, yaw, pitch): (vec3, f32, f32)) -> Frustum {
+		Frustum::for_camera(self.viewport, pos, yaw, pitch)
+	}
+
 	pub fn shaders(&self) -> &ShaderPack {
 		&self.shaders
 	}
@@ -69,6 +74,12 @@ impl GLContext {
 		}
 	}
 
+	/// Enable or disable writing into the depth buffer (while keeping depth testing),
+	/// e.g. for drawing transparent objects.
+	pub fn set_depth_write(&self, enable: bool) {
+		glDepthMask(enable)
+	}
+
 	pub fn set_cull_face(&self, enable: bool) {
 		match enable {
 			true => glEnable(gl::CULL_FACE),
@@ -1,14 +1,24 @@
 /// Internal prelude.
 pub use super::super::prelude::*;
+#[cfg(feature = "gl")]
 pub use gl_obj::*;
 pub use gl_vec::*;
 
+#[cfg(feature = "gl")]
 pub use std::path::Path;
 
 pub use super::camera::*;
+#[cfg(feature = "gl")]
+pub use super::frustum::*;
+#[cfg(feature = "gl")]
 pub use super::glcontext::*;
+#[cfg(feature = "gl")]
 pub use super::imageio::*;
+#[cfg(feature = "gl")]
 pub use super::mesh::*;
+#[cfg(feature = "gl")]
 pub use super::meshpack::*;
+#[cfg(feature = "gl")]
 pub use super::shaderpack::*;
+#[cfg(feature = "gl")]
 pub use super::texturepack::*;
@@ -39,7 +39,7 @@ impl MeshPack {
 		let obj = match wavefrontobj::parse_file(&fname) {
 			Ok(obj) => obj,
 			Err(e) => {
-				eprintln!("load {}: {}", fname.to_string_lossy(), e);
+				warn!("load {}: {}", fname.to_string_lossy(), e);
 				MeshBuffer::cube()
 			}
 		};
@@ -1,10 +1,21 @@
+//! Rendering (with the `gl` feature).
+//! The camera geometry (`Frustum`, ...) is available headless.
+
 mod internal;
 pub mod prelude;
 
 mod camera;
+mod frustum;
+
+#[cfg(feature = "gl")]
 mod glcontext;
+#[cfg(feature = "gl")]
 mod imageio;
+#[cfg(feature = "gl")]
 mod mesh;
+#[cfg(feature = "gl")]
 mod meshpack;
+#[cfg(feature = "gl")]
 mod shaderpack;
+#[cfg(feature = "gl")]
 mod texturepack;
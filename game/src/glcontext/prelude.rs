@@ -1,6 +1,12 @@
-pub use super::glcontext::*;
+pub use super::frustum::*;
 
+#[cfg(feature = "gl")]
+pub use super::glcontext::*;
+#[cfg(feature = "gl")]
 pub use super::mesh::*;
+#[cfg(feature = "gl")]
 pub use super::meshpack::*;
+#[cfg(feature = "gl")]
 pub use super::shaderpack::*;
+#[cfg(feature = "gl")]
 pub use super::texturepack::*;
@@ -20,6 +20,7 @@ impl ShaderPack {
 	const SUN_DIR: u32 = 8; // amin.frag
 	const VIEW_POS: u32 = 10; // anim.vert
 	const FOG_COLOR: u32 = 11; // anim.frag, voxel.frag
+	const ALPHA: u32 = 14; // voxel.frag
 
 	pub fn new() -> Self {
 		ShaderPack {
@@ -155,15 +156,21 @@ impl VoxelShader {
 
 	fn bind(&self) {
 		self.prog.use_program();
-		self.set_texture(0);
+		self.reset();
 	}
 
 	pub fn set_texture(&self, texid: usize) {
 		self.prog.uniform1i(ShaderPack::TEXTURE, texid as i32)
 	}
 
+	/// Opacity of the voxels drawn next (for transparent voxels).
+	pub fn set_alpha(&self, alpha: f32) {
+		self.prog.uniform1f(ShaderPack::ALPHA, alpha)
+	}
+
 	pub fn reset(&self) {
 		self.set_texture(0);
+		self.set_alpha(1.0);
 	}
 }
 
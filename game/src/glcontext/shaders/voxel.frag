@@ -12,6 +12,7 @@ layout(location = 6, binding = 0) uniform sampler2D  tex;
 layout(location = 7) uniform float inv_view_dist_sq = 1e-6;
 layout(location = 10) uniform vec3 view_pos;
 layout(location = 11) uniform vec3 fog_color;
+layout(location = 14) uniform float alpha = 1.0;
 
 void main() {
 	vec3 view_delta = frag_pos - view_pos;
@@ -19,5 +20,5 @@ void main() {
 	float fog = min(dist, 1.0);
 	vec3 tex = texture(tex, frag_tex_coord).rgb;
 	vec3 color = (1.0 - fog) * (tex * frag_light) + fog * fog_color;
-	output_color = vec4(color, 1.0);
+	output_color = vec4(color, alpha);
 }
\ No newline at end of file
@@ -7,21 +7,9 @@ pub struct TexturePack {
 
 impl TexturePack {
 	pub fn new(texture_dir: &Path) -> Self {
-		let voxels = Self::load(
-			texture_dir,
-			&[
-				("snow", WHITE),             //
-				("lava", RED),               //
-				("plasma", GREEN),           //
-				("whitestone2", LIGHT_GREY), //
-				("greystone", DARK_GREY),    //
-				("sand", YELLOW),            //
-				("whiterstone", GREY),       //
-				("stars", MAGENTA),          //
-				("sponge", ORANGE),          //
-				("crystal", BLUE),           //
-			],
-		);
+		// one texture per voxel type, in id order (see Voxel::tex_id).
+		let voxel_textures = VoxelTypes::get().iter().map(|(_, t)| (t.texture.as_str(), t.color)).collect::<Vec<_>>();
+		let voxels = Self::load(texture_dir, &voxel_textures);
 		let skins = Self::load(
 			texture_dir,
 			&[
@@ -59,11 +47,11 @@ impl TexturePack {
 
 	fn load_mipmapped(dir: &Path, base: &str, replacement_color: vec3) -> Texture {
 		let fname = dir.join(base).with_extension("jpg");
-		println!("loading {}", fname.to_string_lossy());
+		debug!("loading {}", fname.to_string_lossy());
 
 		match load_texture(&fname) {
 			Err(e) => {
-				eprintln!("load {}: {}", fname.to_string_lossy(), e);
+				warn!("load {}: {}", fname.to_string_lossy(), e);
 				Self::fallback_texture(replacement_color)
 			}
 			Ok(tex) => tex
@@ -0,0 +1,55 @@
+use super::internal::*;
+
+/// Anything the main loop can drive:
+/// receives keyboard/mouse input, advances time, draws.
+pub trait Controller {
+	fn record_key(&mut self, key: Key, pressed: bool);
+	fn record_mouse(&mut self, delta: (f64, f64));
+	fn tick(&mut self, dt: f32);
+	fn draw(&self, ctx: &GLContext);
+}
+
+impl Controller for LocalPlayer {
+	fn record_key(&mut self, key: Key, pressed: bool) {
+		LocalPlayer::record_key(self, key, pressed)
+	}
+	fn record_mouse(&mut self, delta: (f64, f64)) {
+		LocalPlayer::record_mouse(self, delta)
+	}
+	fn tick(&mut self, dt: f32) {
+		LocalPlayer::tick(self, dt)
+	}
+	fn draw(&self, ctx: &GLContext) {
+		LocalPlayer::draw(self, ctx)
+	}
+}
+
+impl Controller for ReplayViewer {
+	fn record_key(&mut self, key: Key, pressed: bool) {
+		ReplayViewer::record_key(self, key, pressed)
+	}
+	fn record_mouse(&mut self, delta: (f64, f64)) {
+		ReplayViewer::record_mouse(self, delta)
+	}
+	fn tick(&mut self, dt: f32) {
+		ReplayViewer::tick(self, dt)
+	}
+	fn draw(&self, ctx: &GLContext) {
+		ReplayViewer::draw(self, ctx)
+	}
+}
+
+impl<W: Watchable> Controller for Spectator<W> {
+	fn record_key(&mut self, key: Key, pressed: bool) {
+		Spectator::record_key(self, key, pressed)
+	}
+	fn record_mouse(&mut self, delta: (f64, f64)) {
+		Spectator::record_mouse(self, delta)
+	}
+	fn tick(&mut self, dt: f32) {
+		Spectator::tick(self, dt)
+	}
+	fn draw(&self, ctx: &GLContext) {
+		Spectator::draw(self, ctx)
+	}
+}
@@ -1,5 +1,6 @@
 pub use crate::client::prelude::*;
 pub use crate::prelude::*;
 
+pub use super::controller::*;
 pub use super::framerate_counter::*;
 pub use super::keymap::*;
@@ -22,6 +22,11 @@ pub fn keymap(code: VirtualKeyCode) -> Option<Key> {
 		Key8 => Some(Key::Key8),
 		Key9 => Some(Key::Key9),
 		Key0 => Some(Key::Key0),
+		P => Some(Key::Pause),
+		Minus => Some(Key::Slower),
+		Equals => Some(Key::Faster),
+		Comma => Some(Key::SeekBack),
+		Period => Some(Key::SeekForward),
 		_ => None,
 	}
 }
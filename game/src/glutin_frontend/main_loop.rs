@@ -57,6 +57,10 @@ struct Args {
 	#[structopt(long, default_value = "8")]
 	pub msaa: u16,
 
+	/// How voxel meshes are built: greedy (merge faces, fewer vertices) or naive (DEBUG).
+	#[structopt(long, default_value = "greedy")]
+	pub meshing: Meshing,
+
 	/// Texture directory.
 	#[structopt(long, default_value = "assets/textures/hi/")]
 	pub textures: String,
@@ -76,13 +80,67 @@ struct Args {
 	/// Server address
 	#[structopt(long, default_value = "localhost:3344")]
 	pub server: String,
+
+	/// Play offline on this map file, with a server running inside the client, instead of connecting to `--server`.
+	#[structopt(long)]
+	pub offline: Option<PathBuf>,
+
+	/// Watch the game as a spectator, without joining as a player.
+	#[structopt(long)]
+	pub spectate: bool,
+
+	/// Record the game to this file.
+	#[structopt(long)]
+	pub record: Option<PathBuf>,
+
+	/// Watch a recording instead of connecting to a server.
+	#[structopt(long)]
+	pub replay: Option<PathBuf>,
+
+	/// Voxel types data file, instead of the built-in types.
+	/// Must be the same as the server's.
+	#[structopt(long)]
+	pub voxels: Option<PathBuf>,
+
+	/// Log levels, e.g. "info" or "warn,server=debug".
+	#[structopt(long, default_value = "info")]
+	pub log: String,
+
+	/// Log as JSON, one object per line.
+	#[structopt(long)]
+	pub log_json: bool,
 }
 
 pub fn main_loop() -> Result<()> {
 	let args = Args::from_args();
-
-	let client = Client::connect(&args.server, args.skin)?;
-	let mut controller = LocalPlayer::new(client);
+	crate::logging::init(&args.log, args.log_json)?;
+	if let Some(file) = &args.voxels {
+		VoxelTypes::init(file)?;
+	}
+	args.meshing.set();
+
+	let mut local_server = None; // for playing offline, runs as long as the game.
+	let mut controller: Box<dyn Controller> = if let Some(file) = &args.replay {
+		Box::new(ReplayViewer::new(Replay::load(file)?))
+	} else {
+		let mut client = match &args.offline {
+			Some(map_file) => {
+				info!("playing offline on {}", map_file.to_string_lossy());
+				let server = local_server.insert(Server::spawn(ServerOpts::local(map_file.clone()))?);
+				Client::join(server.connect()?, args.skin, args.spectate)?
+			}
+			None if args.spectate => Client::spectate(&args.server)?,
+			None => Client::connect(&args.server, args.skin)?,
+		};
+		if let Some(file) = &args.record {
+			client.record(file)?;
+		}
+		if args.spectate {
+			Box::new(Spectator::new(client))
+		} else {
+			Box::new(LocalPlayer::new(client))
+		}
+	};
 
 	// this initializes the GL context, has to be called before any other GL calls.
 	let (win, event_loop) = init_gl_window(&args);
@@ -107,7 +165,10 @@ pub fn main_loop() -> Result<()> {
 	event_loop.run(move |event, _, control_flow| {
 		*control_flow = ControlFlow::Wait;
 		match event {
-			Event::LoopDestroyed => *control_flow = ControlFlow::Exit,
+			Event::LoopDestroyed => {
+				local_server.take(); // stop it
+				*control_flow = ControlFlow::Exit
+			}
 			Event::UserEvent(_) => win.window().request_redraw(), // empty user event used to signal redraw request.
 			Event::RedrawRequested(_) => {
 				{
@@ -173,9 +234,9 @@ pub fn main_loop() -> Result<()> {
 #[must_use]
 fn grab_input(win: &Window, input_grabbed: bool) -> bool {
 	if !input_grabbed {
-		println!("Press ESC to release mouse");
+		info!("Press ESC to release mouse");
 		if let Err(e) = win.window().set_cursor_grab(true) {
-			eprintln!("failed to grab curor: {}", e);
+			warn!("failed to grab cursor: {}", e);
 		}
 		win.window().set_cursor_visible(false);
 	}
@@ -1,3 +1,4 @@
+mod controller;
 mod framerate_counter;
 mod internal;
 mod keymap;
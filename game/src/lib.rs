@@ -1,3 +1,6 @@
+#[macro_use]
+pub mod logging;
+
 pub mod prelude;
 
 mod client;
@@ -7,13 +10,16 @@ mod glcontext;
 mod local_player;
 mod meshbuffer;
 mod random;
+mod replay;
 mod server;
+mod spectator;
 mod util;
 mod vertex;
 mod voxelbox;
 mod zombie;
 
 pub mod bots;
+#[cfg(feature = "gl")]
 pub mod glutin_frontend;
 pub mod netpipe;
 pub mod procedural_worlds;
@@ -11,6 +11,19 @@ pub struct LocalPlayer {
 	can_bunny_hop: bool,
 
 	jump_armed: bool,
+
+	// movement runs at a fixed time step, independent of the frame rate.
+	physics: FixedStep,
+	shown: Option<Interpolated>,
+}
+
+// The player's positions after the last two physics steps,
+// and the position in between them that was shown (see `LocalPlayer::tick`).
+#[derive(Clone, Copy)]
+struct Interpolated {
+	prev: vec3,
+	curr: vec3,
+	shown: vec3,
 }
 
 impl LocalPlayer {
@@ -20,6 +33,8 @@ impl LocalPlayer {
 			keys: KeyStates::new(),
 			can_bunny_hop: false,
 			jump_armed: true,
+			physics: FixedStep::new(),
+			shown: None,
 		}
 	}
 
@@ -42,6 +57,7 @@ impl LocalPlayer {
 		// control a copy of the player (not allowed to mutate game state in-place)
 		// record changes to the player and Map as update Messages.
 		let mut player = self.player().clone();
+		self.restore_physics_pos(&mut player);
 		let mut updates = Updates::new();
 		self.control(&mut player, dt, &mut updates);
 
@@ -52,6 +68,16 @@ impl LocalPlayer {
 		self.keys.clear(); // must be last
 	}
 
+	/// The player's position is shown (and sent) interpolated between physics steps.
+	/// Continue physics from the last step, unless the player was moved by someone else meanwhile
+	/// (e.g. respawned).
+	fn restore_physics_pos(&mut self, player: &mut Player) {
+		match self.shown {
+			Some(s) if player.model.pos == s.shown => player.model.pos = s.curr,
+			_ => self.shown = None,
+		}
+	}
+
 	/// Control the player over interval of `dt` seconds.
 	/// Record changes to map in `updates`.
 	/// Player state (position, etc) is updated in-place.
@@ -87,7 +113,26 @@ impl LocalPlayer {
 		}
 	}
 
+	/// Move the player in fixed time steps (see `FixedStep`),
+	/// then show the player in between the last two steps.
 	fn control_movement(&mut self, player: &mut Player, dt: f32) {
+		let mut prev = self.shown.map(|s| s.prev).unwrap_or(player.model.pos);
+		for _ in 0..self.physics.advance(dt) {
+			prev = player.model.pos;
+			self.step_movement(player, FixedStep::DT);
+		}
+		let curr = player.model.pos;
+		let shown = prev + self.physics.alpha() * (curr - prev);
+		player.model.pos = shown;
+		self.shown = Some(Interpolated { prev, curr, shown });
+	}
+
+	/// One physics step.
+	fn step_movement(&mut self, player: &mut Player, dt: f32) {
+		// crouch
+		let crouch = self.keys.is_down(Key::Crouch);
+		player.crouch(crouch, self.gs());
+
 		// jump
 		let on_ground = player.model.on_ground(self.map());
 
@@ -99,6 +144,16 @@ impl LocalPlayer {
 			player.jump(self.gs());
 		}
 
+		// swim/climb up or down
+		let up = self.keys.is_down(Key::Jump);
+		player.climb(if up {
+			1.0
+		} else if crouch {
+			-1.0
+		} else {
+			0.0
+		});
+
 		// walk/fly
 		let walk_dir = Self::walk_dir(player, &self.keys);
 		let sprint = self.keys.is_down(Key::Sprint);
@@ -145,6 +200,7 @@ impl LocalPlayer {
 		&self.gs().map
 	}
 
+	#[cfg(feature = "gl")]
 	pub fn draw(&self, ctx: &GLContext) {
 		self.client.draw(ctx)
 	}
@@ -0,0 +1,256 @@
+//! Leveled, structured logging to stderr.
+//!
+//! Use the macros `error!`, `warn!`, `info!`, `debug!`, `trace!`,
+//! optionally prefixed by context fields in square brackets:
+//!
+//!   info!("listening on {}", addr);
+//!   info!([client = id, addr = conn.addr] "dropping client");
+//!
+//! Each message is tagged with its target, the module it was logged from
+//! (e.g. `server::server`), so that levels can be set per module (see `init`).
+//! Output is either human-readable text, or one JSON object per line:
+//!
+//!   2026-10-18T12:01:02.345Z INFO  server::server: dropping client client=3 addr=10.0.0.2:50114
+//!   {"addr":"10.0.0.2:50114","client":"3","level":"info","msg":"dropping client","target":"server::server","ts":"2026-10-18T12:01:02.345Z"}
+use generic_result::*;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+	Error,
+	Warn,
+	Info,
+	Debug,
+	Trace,
+}
+
+impl Level {
+	fn name(self) -> &'static str {
+		use Level::*;
+		match self {
+			Error => "error",
+			Warn => "warn",
+			Info => "info",
+			Debug => "debug",
+			Trace => "trace",
+		}
+	}
+}
+
+impl FromStr for Level {
+	type Err = String;
+	fn from_str(s: &str) -> std::result::Result<Self, String> {
+		use Level::*;
+		match s.to_ascii_lowercase().as_str() {
+			"error" => Ok(Error),
+			"warn" => Ok(Warn),
+			"info" => Ok(Info),
+			"debug" => Ok(Debug),
+			"trace" => Ok(Trace),
+			bad => Err(format!("unknown log level: {}", bad)),
+		}
+	}
+}
+
+impl fmt::Display for Level {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.pad(&self.name().to_ascii_uppercase())
+	}
+}
+
+/// Logging configuration, set by `init`.
+struct Config {
+	level: Level,                  // default level
+	targets: Vec<(String, Level)>, // per-module levels, most specific first.
+	json: bool,
+}
+
+static CONFIG: RwLock<Config> = RwLock::new(Config {
+	level: Level::Info,
+	targets: Vec::new(),
+	json: false,
+});
+
+/// Configure logging. `spec` is a comma-separated list of
+/// a default level and/or per-module levels. E.g.:
+///
+///   info
+///   warn,server=debug,netpipe=trace
+///
+/// A module level also applies to its submodules (`server` covers `server::status`).
+/// Output is one JSON object per line if `json` == true.
+pub fn init(spec: &str, json: bool) -> Result<()> {
+	let mut level = Level::Info;
+	let mut targets = Vec::new();
+	for item in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+		match item.split_once('=') {
+			None => level = item.parse()?,
+			Some((target, l)) => targets.push((target.trim().to_owned(), l.trim().parse()?)),
+		}
+	}
+	targets.sort_by_key(|(t, _)| std::cmp::Reverse(t.len()));
+
+	*CONFIG.write().unwrap() = Config { level, targets, json };
+	Ok(())
+}
+
+/// Would a message at `level`, logged from module `target`, be output?
+pub fn enabled(level: Level, target: &str) -> bool {
+	level <= CONFIG.read().unwrap().level_for(target)
+}
+
+impl Config {
+	fn level_for(&self, target: &str) -> Level {
+		for (t, l) in &self.targets {
+			if target == t || (target.starts_with(t.as_str()) && target[t.len()..].starts_with("::")) {
+				return *l;
+			}
+		}
+		self.level
+	}
+}
+
+/// Strip the crate name from a module path, e.g. `game::server::server` -> `server::server`.
+/// Use via the logging macros.
+pub fn target(module_path: &'static str) -> &'static str {
+	match module_path.split_once("::") {
+		Some(("game", rest)) => rest,
+		_ => module_path,
+	}
+}
+
+/// Output a log message. Use via the logging macros.
+pub fn log(level: Level, target: &str, fields: &[(&str, &dyn fmt::Display)], msg: fmt::Arguments) {
+	let line = if CONFIG.read().unwrap().json {
+		format_json(&timestamp(), level, target, fields, msg)
+	} else {
+		format_text(&timestamp(), level, target, fields, msg)
+	};
+	let _ = writeln!(std::io::stderr().lock(), "{}", line); // nowhere to report a failure to log.
+}
+
+fn format_text(ts: &str, level: Level, target: &str, fields: &[(&str, &dyn fmt::Display)], msg: fmt::Arguments) -> String {
+	let mut line = format!("{} {:5} {}: {}", ts, level, target, msg);
+	for (k, v) in fields {
+		line += &format!(" {}={}", k, v);
+	}
+	line
+}
+
+fn format_json(ts: &str, level: Level, target: &str, fields: &[(&str, &dyn fmt::Display)], msg: fmt::Arguments) -> String {
+	let mut obj = serde_json::Map::new();
+	obj.insert("ts".into(), ts.into());
+	obj.insert("level".into(), level.name().into());
+	obj.insert("target".into(), target.into());
+	obj.insert("msg".into(), msg.to_string().into());
+	for (k, v) in fields {
+		obj.insert(k.to_string(), v.to_string().into());
+	}
+	serde_json::Value::Object(obj).to_string()
+}
+
+// Current UTC time in RFC 3339 format, e.g. 2026-10-18T12:01:02.345Z.
+fn timestamp() -> String {
+	let t = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+	format_time(t.as_secs(), t.subsec_millis())
+}
+
+fn format_time(unix_secs: u64, millis: u32) -> String {
+	let (days, secs) = ((unix_secs / 86400) as i64, unix_secs % 86400);
+	let (y, m, d) = civil_from_days(days);
+	format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", y, m, d, secs / 3600, (secs / 60) % 60, secs % 60, millis)
+}
+
+// Year, month, day from days since 1970-01-01 (proleptic Gregorian calendar).
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+	let z = days + 719468;
+	let era = z.div_euclid(146097);
+	let doe = z.rem_euclid(146097);
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+	let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+	let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+	(y, m, d)
+}
+
+/// Log at the given level. Prefer `error!`, `warn!`, `info!`, `debug!`, `trace!`.
+#[macro_export]
+macro_rules! log {
+	($level:expr, [$($key:ident = $value:expr),* $(,)?] $($arg:tt)+) => {{
+		let target = $crate::logging::target(module_path!());
+		if $crate::logging::enabled($level, target) {
+			$crate::logging::log($level, target, &[$((stringify!($key), &$value as &dyn std::fmt::Display)),*], format_args!($($arg)+))
+		}
+	}};
+	($level:expr, $($arg:tt)+) => {
+		$crate::log!($level, [] $($arg)+)
+	};
+}
+
+#[macro_export]
+macro_rules! error {
+	($($arg:tt)+) => { $crate::log!($crate::logging::Level::Error, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! warn {
+	($($arg:tt)+) => { $crate::log!($crate::logging::Level::Warn, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! info {
+	($($arg:tt)+) => { $crate::log!($crate::logging::Level::Info, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! debug {
+	($($arg:tt)+) => { $crate::log!($crate::logging::Level::Debug, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! trace {
+	($($arg:tt)+) => { $crate::log!($crate::logging::Level::Trace, $($arg)+) };
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn level_for_target() {
+		let c = Config {
+			level: Level::Warn,
+			targets: vec![("server::status".into(), Level::Trace), ("server".into(), Level::Debug)],
+			json: false,
+		};
+		assert_eq!(c.level_for("server"), Level::Debug);
+		assert_eq!(c.level_for("server::server"), Level::Debug);
+		assert_eq!(c.level_for("server::status"), Level::Trace);
+		assert_eq!(c.level_for("serverx"), Level::Warn);
+		assert_eq!(c.level_for("netpipe::netpipe"), Level::Warn);
+	}
+
+	#[test]
+	fn format() {
+		assert_eq!(format_time(0, 0), "1970-01-01T00:00:00.000Z");
+		assert_eq!(format_time(1_792_324_862, 345), "2026-10-18T12:01:02.345Z");
+
+		let id = 3;
+		let fields: &[(&str, &dyn fmt::Display)] = &[("client", &id), ("addr", &"10.0.0.2:50114")];
+		assert_eq!(
+			format_text("T", Level::Info, "server", fields, format_args!("dropping {}", "client")),
+			"T INFO  server: dropping client client=3 addr=10.0.0.2:50114"
+		);
+		assert_eq!(
+			format_json("T", Level::Warn, "server", fields, format_args!("quote\"")),
+			r#"{"addr":"10.0.0.2:50114","client":"3","level":"warn","msg":"quote\"","target":"server","ts":"T"}"#
+		);
+	}
+}
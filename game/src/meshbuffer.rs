@@ -43,6 +43,7 @@ impl MeshBuffer {
 		self
 	}
 
+	#[cfg(feature = "gl")]
 	pub fn build(&self) -> Mesh {
 		Mesh::new(&self.vertices) //
 			.with_tex_coords(&self.texcoords)
@@ -1,7 +1,10 @@
 pub use super::super::prelude::*;
 pub use super::netpipe::*;
+pub use super::stats::*;
 
 pub use std::io::{BufReader, BufWriter, Write};
 pub use std::net::TcpStream;
+pub use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering::Relaxed};
 pub use std::sync::mpsc::{channel, Receiver, Sender, TryIter};
+pub use std::sync::Arc;
 pub use std::thread::spawn;
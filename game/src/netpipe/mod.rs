@@ -3,3 +3,4 @@ pub mod prelude;
 mod internal;
 
 mod netpipe;
+mod stats;
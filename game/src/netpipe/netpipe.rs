@@ -1,14 +1,40 @@
 use super::internal::*;
 
+/// A two-way connection exchanging Messages, over TCP or in memory (see `loopback`).
+/// Sending never blocks, messages are queued and transmitted by worker threads.
 pub struct NetPipe {
 	pub send: NetSender,
 	pub recv: NetReceiver,
+	peer_addr: String,
 }
 
 impl NetPipe {
 	pub fn new(tcp_stream: TcpStream) -> Self {
+		let peer_addr = match tcp_stream.peer_addr() {
+			Ok(addr) => addr.to_string(),
+			Err(_) => String::from("?"),
+		};
 		let (send, recv) = netpipe(tcp_stream);
-		Self { send, recv }
+		Self { send, recv, peer_addr }
+	}
+
+	/// Two connected pipes, in memory: what is sent on one is received on the other,
+	/// in order, without serialization. For a client and server in the same process.
+	/// When one end is dropped, the other end's `recv` returns an error, like for TCP.
+	pub fn loopback() -> (Self, Self) {
+		let (send_a, recv_b) = loopback();
+		let (send_b, recv_a) = loopback();
+		let pipe = |send, recv| Self {
+			send,
+			recv,
+			peer_addr: String::from("loopback"),
+		};
+		(pipe(send_a, recv_a), pipe(send_b, recv_b))
+	}
+
+	/// Address of the other side of the connection, for logging.
+	pub fn peer_addr(&self) -> &str {
+		&self.peer_addr
 	}
 
 	pub fn split(self) -> (NetSender, NetReceiver) {
@@ -28,7 +54,10 @@ impl NetPipe {
 	}
 }
 
-pub struct NetSender(Sender<Message>);
+pub struct NetSender {
+	send: Sender<Message>,
+	stats: Arc<PipeStats>,
+}
 
 pub struct NetReceiver(Receiver<Message>);
 
@@ -38,11 +67,22 @@ pub fn netpipe(tcp_stream: TcpStream) -> (NetSender, NetReceiver) {
 
 	let (send, worker_recv) = channel();
 	let (worker_send, recv) = channel();
+	let stats = Arc::new(PipeStats::default());
 
-	start_upload(tcp_stream, worker_recv);
-	start_download(tcp_stream2, worker_send);
+	start_upload(tcp_stream, worker_recv, stats.clone());
+	start_download(tcp_stream2, worker_send, stats.clone());
 
-	(NetSender(send), NetReceiver(recv))
+	(NetSender { send, stats }, NetReceiver(recv))
+}
+
+// One direction of an in-memory pipe (see `NetPipe::loopback`).
+// No bytes are counted, as messages are not serialized.
+fn loopback() -> (NetSender, NetReceiver) {
+	let (send, worker_recv) = channel();
+	let (worker_send, recv) = channel();
+	let stats = Arc::new(PipeStats::default());
+	start_forward(worker_recv, worker_send, stats.clone());
+	(NetSender { send, stats }, NetReceiver(recv))
 }
 
 impl NetSender {
@@ -54,10 +94,16 @@ impl NetSender {
 	// processed the message, it may have crashed right after receiving.
 	// Only a server response guarantees success.)
 	pub fn send(&mut self, msg: Message) {
-		if let Err(e) = self.0.send(msg) {
-			eprintln!("netpipe: send: {}", e)
+		self.stats.message_queued();
+		if let Err(e) = self.send.send(msg) {
+			debug!("send: {}", e)
 		}
 	}
+
+	/// Traffic statistics for this pipe (both directions).
+	pub fn stats(&self) -> &PipeStats {
+		&self.stats
+	}
 }
 
 impl NetReceiver {
@@ -81,8 +127,8 @@ impl NetReceiver {
 // (Because even a successful send would not guarantee that the server successfully
 // processed the message, it may have crashed right after receiving.
 // Only a server response guarantees success.)
-fn start_upload(tcp_stream: TcpStream, worker_recv: Receiver<Message>) {
-	let mut buf = BufWriter::new(tcp_stream);
+fn start_upload(tcp_stream: TcpStream, worker_recv: Receiver<Message>, stats: Arc<PipeStats>) {
+	let mut buf = BufWriter::new(Counting::new(tcp_stream, stats.clone()));
 
 	spawn(move || {
 		if let Err(e) = || -> Result<()> {
@@ -93,13 +139,15 @@ fn start_upload(tcp_stream: TcpStream, worker_recv: Receiver<Message>) {
 				// flush them all together.
 				let msg = worker_recv.recv()?;
 				msg.serialize(&mut buf)?;
+				stats.message_sent();
 				for msg in worker_recv.try_iter() {
 					msg.serialize(&mut buf)?;
+					stats.message_sent();
 				}
 				buf.flush()?;
 			}
 		}() {
-			eprintln!("netpipe: upload: {}", e);
+			debug!("upload: {}", e);
 			return;
 		}
 	});
@@ -107,19 +155,54 @@ fn start_upload(tcp_stream: TcpStream, worker_recv: Receiver<Message>) {
 
 // Spawn a loop deserializing messages from `tcp_stream` and sending them to `worker_send`.
 // Drops `worker_send` on error, causing future `recv` calls to error out.
-fn start_download(tcp_stream: TcpStream, worker_send: Sender<Message>) {
-	let mut buf = BufReader::new(tcp_stream);
+fn start_download(tcp_stream: TcpStream, worker_send: Sender<Message>, stats: Arc<PipeStats>) {
+	let mut buf = BufReader::new(Counting::new(tcp_stream, stats));
 
 	spawn(move || {
-		if let Err(_e) = || -> Result<()> {
+		if let Err(e) = || -> Result<()> {
 			// wrapper so we can use ? operator
 			loop {
 				let msg = Message::deserialize(&mut buf)?;
 				worker_send.send(msg)?;
 			}
 		}() {
-			//eprintln!("netpipe: download: {}", e);
+			debug!("download: {}", e);
 			return;
 		}
 	});
 }
+
+// Spawn a loop passing messages from `worker_recv` on to `worker_send` (the other end's receiver).
+// Stops when either end is dropped, causing the other end's `recv` calls to error out.
+fn start_forward(worker_recv: Receiver<Message>, worker_send: Sender<Message>, stats: Arc<PipeStats>) {
+	spawn(move || {
+		for msg in worker_recv.iter() {
+			stats.message_sent();
+			if worker_send.send(msg).is_err() {
+				debug!("loopback: receiver dropped");
+				return;
+			}
+		}
+	});
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn loopback() {
+		let (mut a, mut b) = NetPipe::loopback();
+		a.send(Message::DropPlayer { player_id: 1 });
+		a.send(Message::DropPlayer { player_id: 2 });
+		b.send(Message::DropPlayer { player_id: 3 });
+		assert!(matches!(b.recv().unwrap(), Message::DropPlayer { player_id: 1 }));
+		assert!(matches!(b.recv().unwrap(), Message::DropPlayer { player_id: 2 }));
+		assert!(matches!(a.recv().unwrap(), Message::DropPlayer { player_id: 3 }));
+		assert_eq!(a.peer_addr(), "loopback");
+
+		// hanging up.
+		drop(a);
+		assert!(b.recv().is_err());
+	}
+}
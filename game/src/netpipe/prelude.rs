@@ -1 +1,2 @@
 pub use super::netpipe::*;
+pub use super::stats::PipeStats;
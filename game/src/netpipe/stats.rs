@@ -0,0 +1,68 @@
+use super::internal::*;
+use std::io::{self, Read};
+
+/// Traffic statistics of a NetPipe, updated live by its worker threads.
+#[derive(Default)]
+pub struct PipeStats {
+	queued: AtomicUsize,
+	bytes_sent: AtomicU64,
+	bytes_received: AtomicU64,
+}
+
+impl PipeStats {
+	/// Number of messages waiting to be sent.
+	pub fn queue_depth(&self) -> usize {
+		self.queued.load(Relaxed)
+	}
+
+	/// Total number of bytes sent so far.
+	pub fn bytes_sent(&self) -> u64 {
+		self.bytes_sent.load(Relaxed)
+	}
+
+	/// Total number of bytes received so far.
+	pub fn bytes_received(&self) -> u64 {
+		self.bytes_received.load(Relaxed)
+	}
+
+	pub(super) fn message_queued(&self) {
+		self.queued.fetch_add(1, Relaxed);
+	}
+
+	pub(super) fn message_sent(&self) {
+		self.queued.fetch_sub(1, Relaxed);
+	}
+}
+
+/// Wraps a stream, counting the bytes written to it (`bytes_sent`)
+/// or read from it (`bytes_received`).
+pub(super) struct Counting<T> {
+	inner: T,
+	stats: Arc<PipeStats>,
+}
+
+impl<T> Counting<T> {
+	pub fn new(inner: T, stats: Arc<PipeStats>) -> Self {
+		Self { inner, stats }
+	}
+}
+
+impl<T: Write> Write for Counting<T> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let n = self.inner.write(buf)?;
+		self.stats.bytes_sent.fetch_add(n as u64, Relaxed);
+		Ok(n)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.inner.flush()
+	}
+}
+
+impl<T: Read> Read for Counting<T> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let n = self.inner.read(buf)?;
+		self.stats.bytes_received.fetch_add(n as u64, Relaxed);
+		Ok(n)
+	}
+}
@@ -11,7 +11,9 @@ pub use super::local_player::*;
 pub use super::meshbuffer::*;
 pub use super::netpipe::*;
 pub use super::random::*;
+pub use super::replay::*;
 pub use super::server::prelude::*;
+pub use super::spectator::*;
 pub use super::util::*;
 pub use super::vertex::*;
 pub use super::zombie::*;
@@ -28,5 +30,6 @@ pub const DEG: f32 = PI / 180.0;
 pub use std::path::{Path, PathBuf};
 
 pub type HashMap<K, V> = fnv::FnvHashMap<K, V>;
+pub type HashSet<K> = fnv::FnvHashSet<K>;
 
 pub use serde::{Deserialize, Serialize};
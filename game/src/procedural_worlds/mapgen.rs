@@ -2,11 +2,12 @@ use crate::prelude::*;
 
 pub fn heightmap<F: Fn(f32, f32) -> f32>(m: &mut Map, pos: vec3, size: ivec2, fill: Voxel, top: Voxel, f: F) {
 	let pos = pos.map(|v| v as i32);
-	let (nx, _, nz) = m.size().as_ivec().into();
+	let b = m.bounds();
+	let (cx, cz) = ((b.min.x + b.max.x) / 2, (b.min.z + b.max.z) / 2);
 	for iz in (pos.z - size.y / 2)..(pos.z + size.y / 2) {
 		for ix in (pos.x - size.x / 2)..(pos.x + size.x / 2) {
-			let x = (ix - nx / 2) as f32;
-			let z = (iz - nz / 2) as f32;
+			let x = (ix - cx) as f32;
+			let z = (iz - cz) as f32;
 			let h = f(x, z);
 			let iy = h as i32 + pos.y;
 			for y in 0..iy {
@@ -18,8 +19,8 @@ pub fn heightmap<F: Fn(f32, f32) -> f32>(m: &mut Map, pos: vec3, size: ivec2, fi
 }
 
 pub fn floor(m: &mut Map, height: i32, v: Voxel) {
-	let (nx, _, nz) = m.size().as_ivec().into();
-	range(m, ivec3(0, 0, 0), ivec3(nx, height, nz), v)
+	let b = m.bounds();
+	range(m, ivec3(b.min.x, 0, b.min.z), ivec3(b.max.x, height, b.max.z), v)
 }
 
 pub fn slab(m: &mut Map, pos: ivec3, size: ivec3, v: Voxel) {
@@ -0,0 +1,4 @@
+pub use crate::prelude::*;
+pub use std::fs::File;
+pub use std::io::{BufReader, BufWriter, Read, Write};
+pub use std::time::Instant;
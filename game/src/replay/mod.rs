@@ -0,0 +1,9 @@
+mod internal;
+
+mod playback;
+mod recording;
+mod viewer;
+
+pub use playback::*;
+pub use recording::*;
+pub use viewer::*;
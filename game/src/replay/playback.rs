@@ -0,0 +1,234 @@
+use super::internal::*;
+
+/// Plays back a recorded game (see `Recorder`),
+/// with pause, seek and playback speed controls.
+pub struct Replay {
+	records: Vec<(f32, Message)>, // (timestamp, message), excluding the initial `Accepted`.
+	initial_map: Map,
+	initial_players: Players,
+	player_id: ID, // who made the recording
+
+	game_state: GameState, // state at playback position `time`
+	next: usize,           // index of the next record to apply
+	time: f32,             // playback position, in seconds
+
+	/// Playback speed (1 = real time).
+	pub speed: f32,
+	pub paused: bool,
+}
+
+impl Replay {
+	/// Load a recording written by `Recorder`.
+	pub fn load<P: AsRef<Path>>(file: P) -> Result<Self> {
+		Self::new(read_recording(file)?)
+	}
+
+	/// Replay (timestamp, message) pairs, starting with `Accepted`.
+	pub fn new(mut records: Vec<(f32, Message)>) -> Result<Self> {
+		if records.is_empty() {
+			return err("replay: empty recording");
+		}
+		let (initial_map, initial_players, player_id) = match records.remove(0) {
+			(_, Message::Accepted { player_id, players, map_data }) => (Map::from_bytes(&map_data)?, players, player_id),
+			(_, bad) => return err(format!("replay: recording starts with {} instead of Accepted", &bad)),
+		};
+
+		Ok(Self {
+			game_state: GameState::new(initial_map.clone(), initial_players.clone()),
+			records,
+			initial_map,
+			initial_players,
+			player_id,
+			next: 0,
+			time: 0.0,
+			speed: 1.0,
+			paused: false,
+		})
+	}
+
+	/// The player who made the recording
+	/// (meaningless for server recordings).
+	pub fn recorded_player(&self) -> ID {
+		self.player_id
+	}
+
+	/// Current playback position, in seconds.
+	pub fn time(&self) -> f32 {
+		self.time
+	}
+
+	/// Length of the recording, in seconds.
+	pub fn duration(&self) -> f32 {
+		self.records.last().map(|(t, _)| *t).unwrap_or(0.0)
+	}
+
+	/// Advance the playback position by `dt` seconds of wall time
+	/// (scaled by `speed`), unless paused.
+	pub fn tick(&mut self, dt: f32) {
+		if !self.paused {
+			self.advance(dt * self.speed)
+		}
+	}
+
+	/// Jump to playback position `time` (seconds).
+	/// Seeking backwards replays the recording from the start.
+	pub fn seek(&mut self, time: f32) {
+		let time = clamp(time, 0.0, self.duration());
+		if time < self.time {
+			self.game_state = GameState::new(self.initial_map.clone(), self.initial_players.clone());
+			self.next = 0;
+			self.time = 0.0;
+		}
+		self.advance(time - self.time);
+	}
+
+	// Apply all messages recorded up to `time + dt`, each at its own time,
+	// so that players are only extrapolated from their latest update (also when seeking).
+	fn advance(&mut self, dt: f32) {
+		let end = self.time + dt;
+		while let Some((t, msg)) = self.records.get(self.next) {
+			if *t > end {
+				break;
+			}
+			let (t, msg) = (*t, msg.clone());
+			self.step(t - self.time);
+			self.apply(msg);
+			self.next += 1;
+		}
+		self.step(end - self.time);
+	}
+
+	// Advance time by `dt` seconds in between messages:
+	// tick effects and extrapolate players (like `Client::tick`).
+	fn step(&mut self, dt: f32) {
+		let dt = f32::max(0.0, dt);
+		self.time += dt;
+		self.game_state.time += dt;
+		for (_, player) in self.game_state.players.iter_mut() {
+			player.model.extrapolate(dt)
+		}
+		self.game_state.effects.tick(dt, &self.game_state.map);
+	}
+
+	fn apply(&mut self, msg: Message) {
+		use Message::*;
+		match msg {
+			UpdateMap { index, voxel, state } => self.game_state.update_map(index, voxel, state),
+			UpdateMapBatch(edits) => self.game_state.apply_map_edits(&edits),
+			DamagePlayer { .. } => (), // followed by the player's update
+			UpdatePlayer { player_id, player } => self.game_state.update_player(player_id, player),
+			DropPlayer { player_id } => self.game_state.drop_player(player_id),
+			AddEffect(e) => self.game_state.effects.push(e),
+			bad => warn!("replay: ignoring unexpected message: {}", &bad),
+		}
+	}
+
+	/// The game state at the current playback position.
+	pub fn game_state(&self) -> &GameState {
+		&self.game_state
+	}
+}
+
+impl Watchable for Replay {
+	fn tick(&mut self, dt: f32) {
+		Replay::tick(self, dt)
+	}
+	fn game_state(&self) -> &GameState {
+		Replay::game_state(self)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn record_and_seek() {
+		let file = std::env::temp_dir().join(format!("hva_replay_test_{}.rec", std::process::id()));
+		let gs = GameState::new(Map::flat(uvec3(16, 16, 16)), Players::new());
+		let mut rec = Recorder::create(&file, &gs, 7).unwrap();
+		rec.record(&Message::UpdateMap {
+			index: ivec3(1, 10, 1),
+			voxel: Voxel::LAVA,
+			state: VoxelState::default(),
+		})
+		.unwrap();
+		drop(rec);
+
+		// rewrite timestamps: recording takes less than a millisecond.
+		let mut records = read_recording(&file).unwrap();
+		std::fs::remove_file(&file).unwrap();
+		assert_eq!(records.len(), 2);
+		records[1].0 = 2.0;
+
+		let mut replay = Replay::new(records).unwrap();
+		assert_eq!(replay.recorded_player(), 7);
+		assert_eq!(replay.duration(), 2.0);
+		let at = |r: &Replay| r.game_state().map().at(ivec3(1, 10, 1));
+		assert_eq!(at(&replay), Voxel::EMPTY);
+
+		replay.tick(1.0);
+		assert_eq!(at(&replay), Voxel::EMPTY);
+
+		replay.speed = 2.0;
+		replay.tick(0.5);
+		assert_eq!(replay.time(), 2.0);
+		assert_eq!(at(&replay), Voxel::LAVA);
+
+		replay.seek(0.5);
+		assert_eq!(at(&replay), Voxel::EMPTY);
+
+		replay.paused = true;
+		replay.tick(10.0);
+		assert_eq!(replay.time(), 0.5);
+	}
+
+	#[test]
+	fn seek_players() {
+		// player 1 walks along x at 1 m/s from t=1 until it stops at t=3.
+		let walking = |x: f32, vx: f32| {
+			let mut p = Player::new(0);
+			p.model.pos = vec3(x, 1.0, 0.0);
+			p.model.vel = vec3(vx, 0.0, 0.0);
+			p
+		};
+		let mut players = Players::new();
+		players.set(1, walking(0.0, 0.0));
+		let records = vec![
+			(
+				0.0,
+				Message::Accepted {
+					player_id: 1,
+					map_data: Map::flat(uvec3(16, 16, 16)).to_bytes(),
+					players,
+				},
+			),
+			(
+				1.0,
+				Message::UpdatePlayer {
+					player_id: 1,
+					player: walking(0.0, 1.0),
+				},
+			),
+			(
+				3.0,
+				Message::UpdatePlayer {
+					player_id: 1,
+					player: walking(2.0, 0.0),
+				},
+			),
+			(10.0, Message::DropPlayer { player_id: 2 }), // no such player: only sets the duration
+		];
+		let mut replay = Replay::new(records).unwrap();
+		let x = |r: &Replay| r.game_state().players.get(1).model.pos.x;
+
+		replay.seek(2.0);
+		assert!((x(&replay) - 1.0).abs() < 1e-4, "x = {}", x(&replay));
+		replay.seek(9.0);
+		assert_eq!(x(&replay), 2.0); // stopped, not extrapolated from t=1
+		replay.seek(1.5);
+		assert!((x(&replay) - 0.5).abs() < 1e-4, "x = {}", x(&replay));
+		replay.seek(0.5);
+		assert_eq!(x(&replay), 0.0);
+	}
+}
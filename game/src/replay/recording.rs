@@ -0,0 +1,83 @@
+use super::internal::*;
+use std::time::Duration;
+
+/// A recording is a file starting with `MAGIC`,
+/// followed by (timestamp, Message) pairs, bincode-encoded.
+/// Timestamps are seconds since the start of the recording.
+///
+/// The first message is always `Accepted`, holding the initial game state.
+/// (For server recordings, its `player_id` is meaningless).
+const MAGIC: &[u8; 8] = b"HVAREC02";
+
+/// Writes a recording of all messages that mutate a GameState.
+pub struct Recorder {
+	w: BufWriter<File>,
+	start: Instant,
+	last_flush: Instant,
+}
+
+impl Recorder {
+	// Flush at least this often, so that a crash loses little of the recording.
+	const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+	/// Create a recording in `file`, starting from the current `game_state`,
+	/// as seen by `player_id`.
+	pub fn create<P: AsRef<Path>>(file: P, game_state: &GameState, player_id: ID) -> Result<Self> {
+		let file = file.as_ref();
+		let mut w = BufWriter::new(File::create(file).map_err(|e| error(format!("record {}: {}", file.to_string_lossy(), e)))?);
+		w.write_all(MAGIC)?;
+		let mut recorder = Self {
+			w,
+			start: Instant::now(),
+			last_flush: Instant::now(),
+		};
+		recorder.record(&Message::Accepted {
+			player_id,
+			players: game_state.players().clone(),
+			map_data: game_state.map().to_bytes(),
+		})?;
+		recorder.flush()?;
+		Ok(recorder)
+	}
+
+	/// Append a message, timestamped with the current time.
+	pub fn record(&mut self, msg: &Message) -> Result<()> {
+		let t = self.start.elapsed().as_secs_f32();
+		bincode::serialize_into(&mut self.w, &(t, msg))?;
+		if self.last_flush.elapsed() > Self::FLUSH_INTERVAL {
+			self.flush()?;
+		}
+		Ok(())
+	}
+
+	pub fn flush(&mut self) -> Result<()> {
+		self.last_flush = Instant::now();
+		Ok(self.w.flush()?)
+	}
+}
+
+/// Read all (timestamp, Message) pairs from a recording.
+///
+/// A truncated last message (e.g. the recording process crashed) is ignored.
+pub fn read_recording<P: AsRef<Path>>(file: P) -> Result<Vec<(f32, Message)>> {
+	let file = file.as_ref();
+	let mut r = BufReader::new(File::open(file).map_err(|e| error(format!("replay {}: {}", file.to_string_lossy(), e)))?);
+
+	let mut magic = [0u8; 8];
+	r.read_exact(&mut magic)?;
+	if &magic != MAGIC {
+		return err(format!("replay {}: not a recording", file.to_string_lossy()));
+	}
+
+	let mut records = Vec::new();
+	loop {
+		match bincode::deserialize_from::<_, (f32, Message)>(&mut r) {
+			Ok(record) => records.push(record),
+			Err(e) => match e.as_ref() {
+				bincode::ErrorKind::Io(io) if io.kind() == std::io::ErrorKind::UnexpectedEof => break,
+				_ => return Err(e.into()),
+			},
+		}
+	}
+	Ok(records)
+}
@@ -0,0 +1,80 @@
+use super::internal::*;
+
+/// Watches a `Replay` like a `Spectator`, with additional playback controls:
+///
+///   P:     pause / resume
+///   -, =:  half / double speed
+///   ,, .:  seek 10s back / forward
+pub struct ReplayViewer {
+	spectator: Spectator<Replay>,
+	keys: KeyStates,
+}
+
+impl ReplayViewer {
+	const SEEK_STEP: f32 = 10.0;
+	const MIN_SPEED: f32 = 1.0 / 16.0;
+	const MAX_SPEED: f32 = 16.0;
+
+	/// Start watching through the eyes of whoever made the recording
+	/// (if they're in the game, i.e. for client recordings).
+	pub fn new(replay: Replay) -> Self {
+		let player_id = replay.recorded_player();
+		let follow = if replay.game_state().players.contains(player_id) { Some(player_id) } else { None };
+		let mut spectator = Spectator::new(replay);
+		spectator.follow(follow);
+		Self { spectator, keys: KeyStates::new() }
+	}
+
+	/// Record that `key` was pressed or released.
+	pub fn record_key(&mut self, key: Key, pressed: bool) {
+		self.keys.record(key, pressed);
+		self.spectator.record_key(key, pressed);
+	}
+
+	/// Record that the mouse was moved by `(delta_x, delta_y)`.
+	pub fn record_mouse(&mut self, delta: (f64, f64)) {
+		self.spectator.record_mouse(delta)
+	}
+
+	/// Advance playback (unless paused), move the camera.
+	pub fn tick(&mut self, dt: f32) {
+		self.control_playback();
+		self.spectator.tick(dt);
+		self.keys.clear(); // must be last
+	}
+
+	fn control_playback(&mut self) {
+		let keys = &self.keys;
+		let replay = self.spectator.source_mut();
+		let mut changed = true;
+
+		if keys.was_pressed(Key::Pause) {
+			replay.paused = !replay.paused;
+		} else if keys.was_pressed(Key::Slower) {
+			replay.speed = f32::max(replay.speed / 2.0, Self::MIN_SPEED);
+		} else if keys.was_pressed(Key::Faster) {
+			replay.speed = f32::min(replay.speed * 2.0, Self::MAX_SPEED);
+		} else if keys.was_pressed(Key::SeekBack) {
+			replay.seek(replay.time() - Self::SEEK_STEP);
+		} else if keys.was_pressed(Key::SeekForward) {
+			replay.seek(replay.time() + Self::SEEK_STEP);
+		} else {
+			changed = false;
+		}
+
+		if changed {
+			info!(
+				"replay {:.1}/{:.1}s, speed {}x{}",
+				replay.time(),
+				replay.duration(),
+				replay.speed,
+				if replay.paused { " (paused)" } else { "" }
+			);
+		}
+	}
+
+	#[cfg(feature = "gl")]
+	pub fn draw(&self, ctx: &GLContext) {
+		self.spectator.draw(ctx)
+	}
+}
@@ -0,0 +1,107 @@
+use super::internal::*;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// Servers announce themselves on the LAN by broadcasting
+/// a ServerInfo (as JSON) to this UDP port.
+pub const DISCOVERY_PORT: u16 = 3346;
+
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// What a server browser needs to know about a server.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ServerInfo {
+	pub name: String,
+	pub port: u16, // game port, the address is that of the sender.
+	pub players: usize,
+	pub spectators: usize,
+	pub map_bounds: VoxelBounds, // for unbounded maps: at the time the server started.
+}
+
+impl ServerInfo {
+	/// Encode for broadcasting (as JSON).
+	pub fn encode(&self) -> Vec<u8> {
+		serde_json::to_vec(self).expect("serialize ServerInfo")
+	}
+
+	/// Decode a broadcast. `None` if it is not a ServerInfo
+	/// (e.g. from an incompatible server version).
+	pub fn decode(data: &[u8]) -> Option<Self> {
+		serde_json::from_slice(data).ok()
+	}
+}
+
+/// Spawn a loop that periodically broadcasts our ServerInfo on the LAN.
+pub(super) fn spawn_announce_loop(name: String, port: u16, map_bounds: VoxelBounds, server: Sender<ServerEvent>) -> Result<()> {
+	let socket = UdpSocket::bind("0.0.0.0:0")?;
+	socket.set_broadcast(true)?;
+	info!("announcing on LAN port {}", DISCOVERY_PORT);
+	std::thread::spawn(move || loop {
+		let status = match query_status(&server) {
+			Ok(status) => status,
+			Err(_) => return, // server quit, so stop worker thread.
+		};
+		let info = ServerInfo {
+			name: name.clone(),
+			port,
+			players: status.players,
+			spectators: status.spectators,
+			map_bounds,
+		};
+		if let Err(e) = socket.send_to(&info.encode(), ("255.255.255.255", DISCOVERY_PORT)) {
+			warn!("announce: {}", e) // e.g. no network, try again later.
+		}
+		std::thread::sleep(ANNOUNCE_INTERVAL);
+	});
+	Ok(())
+}
+
+/// Listen for server announcements on the LAN for `timeout`.
+/// Returns the game address and info of each server heard from.
+pub fn discover_servers(timeout: Duration) -> Result<Vec<(SocketAddr, ServerInfo)>> {
+	let socket = UdpSocket::bind(("0.0.0.0", DISCOVERY_PORT))?;
+	let deadline = Instant::now() + timeout;
+	let mut found: Vec<(SocketAddr, ServerInfo)> = Vec::new();
+	let mut buf = [0u8; 2048];
+
+	loop {
+		let now = Instant::now();
+		if now >= deadline {
+			break;
+		}
+		socket.set_read_timeout(Some(deadline - now))?;
+		let (n, from) = match socket.recv_from(&mut buf) {
+			Ok(r) => r,
+			Err(_) => break, // timeout
+		};
+		if let Some(info) = ServerInfo::decode(&buf[..n]) {
+			let addr = SocketAddr::new(from.ip(), info.port);
+			match found.iter_mut().find(|(a, _)| *a == addr) {
+				Some(entry) => entry.1 = info,
+				None => found.push((addr, info)),
+			}
+		}
+	}
+	Ok(found)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn server_info() {
+		let info = ServerInfo {
+			name: "my server".into(),
+			port: 3344,
+			players: 3,
+			spectators: 1,
+			map_bounds: VoxelBounds::new(ivec3(-16, 0, -16), ivec3(16, 8, 16)),
+		};
+		let data = info.encode();
+		assert!(data.len() < 2048); // fits `discover_servers`'s receive buffer
+		assert_eq!(ServerInfo::decode(&data), Some(info));
+		assert_eq!(ServerInfo::decode(b"{\"name\": 1}"), None);
+		assert_eq!(ServerInfo::decode(b"garbage"), None);
+	}
+}
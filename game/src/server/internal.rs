@@ -1,5 +1,9 @@
+pub use super::discovery::*;
+pub use super::map_cache::*;
 pub use super::message::*;
+pub use super::metrics::*;
 pub use super::server::*;
+pub use super::status::*;
 
 pub use crate::netpipe::prelude::*;
 pub use crate::prelude::*;
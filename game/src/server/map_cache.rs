@@ -0,0 +1,100 @@
+use super::internal::*;
+use std::sync::Arc;
+
+/// Caches the serialized map that is sent to joining clients,
+/// so that it does not need to be re-compressed for every join.
+///
+/// Serialization happens in a background thread, on a snapshot of the map.
+/// Edits made after the snapshot was taken are logged,
+/// and replayed to clients who receive the (slightly outdated) cached map.
+pub struct MapCache {
+	version: u64,                              // number of map edits so far
+	cached: Option<(u64, Arc<Vec<u8>>)>,       // serialized map + the version it was taken at
+	log: Vec<(u64, ivec3, Voxel, VoxelState)>, // edits made after the cached (or in-flight) version
+	in_flight: bool,                           // a snapshot is being serialized
+}
+
+impl MapCache {
+	// Drop the cache when it would take more than this many edits to catch up,
+	// re-serializing is cheaper then.
+	const MAX_LOG: usize = 4096;
+
+	pub fn new() -> Self {
+		Self {
+			version: 0,
+			cached: None,
+			log: Vec::new(),
+			in_flight: false,
+		}
+	}
+
+	/// Record that the map has been edited.
+	pub fn record_edit(&mut self, index: ivec3, voxel: Voxel, state: VoxelState) {
+		self.version += 1;
+		if self.cached.is_none() && !self.in_flight {
+			return; // nobody needs to catch up
+		}
+		self.log.push((self.version, index, voxel, state));
+
+		if self.log.len() > Self::MAX_LOG && !self.in_flight {
+			self.cached = None;
+			self.log.clear();
+		}
+	}
+
+	/// Is there a serialized map available?
+	pub fn is_ready(&self) -> bool {
+		self.cached.is_some()
+	}
+
+	/// The serialized map, and the edits needed to bring it up to date.
+	/// `None` if the map needs to be serialized first.
+	pub fn get(&self) -> Option<(Arc<Vec<u8>>, Updates)> {
+		let (_, bytes) = self.cached.as_ref()?;
+		let catch_up = self.log.iter().map(|&(_, index, voxel, state)| Message::UpdateMap { index, voxel, state }).collect();
+		Some((bytes.clone(), catch_up))
+	}
+
+	/// Mark that a snapshot is about to be serialized.
+	/// Returns the snapshot's version (to be passed to `finish_snapshot`),
+	/// or `None` if a snapshot is already in flight.
+	pub fn begin_snapshot(&mut self) -> Option<u64> {
+		if self.in_flight {
+			return None;
+		}
+		self.in_flight = true;
+		Some(self.version)
+	}
+
+	/// Store a serialized snapshot taken at `version`.
+	pub fn finish_snapshot(&mut self, version: u64, bytes: Vec<u8>) {
+		self.in_flight = false;
+		self.cached = Some((version, Arc::new(bytes)));
+		self.log.retain(|&(v, _, _, _)| v > version);
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn catch_up() {
+		let mut c = MapCache::new();
+		c.record_edit(ivec3(1, 2, 3), Voxel::SNOW, VoxelState::default()); // nobody is listening yet
+		assert!(!c.is_ready());
+
+		let version = c.begin_snapshot().unwrap();
+		assert!(c.begin_snapshot().is_none());
+		c.record_edit(ivec3(4, 5, 6), Voxel::LAVA, VoxelState::default()); // made while serializing
+		c.finish_snapshot(version, vec![42]);
+
+		let (bytes, catch_up) = c.get().unwrap();
+		assert_eq!(*bytes, vec![42]);
+		assert_eq!(catch_up.len(), 1);
+		match catch_up[0] {
+			Message::UpdateMap { index, voxel, .. } => assert_eq!((index, voxel), (ivec3(4, 5, 6), Voxel::LAVA)),
+			_ => panic!("expected UpdateMap"),
+		}
+	}
+}
@@ -6,9 +6,11 @@ use std::io::prelude::*;
 /// Messages broadcast by the server to clients.
 #[derive(Serialize, Deserialize, Clone)]
 pub enum Message {
-	/// Initial client request
+	/// Initial client request.
+	/// Spectators receive all updates, but do not get a Player in the game.
 	Join {
 		skin: usize,
+		spectator: bool,
 	},
 
 	/// Serer response to `Join`,
@@ -18,16 +20,23 @@ pub enum Message {
 		map_data: Vec<u8>,
 	},
 
-	/// A small update to the map (e.g. Voxel added/removed).
+	/// A small update to the map (e.g. Voxel added/removed/damaged).
 	/// Applied incrementally on top of the initial map data.
+	/// Replaces the voxel and its state.
 	///
 	/// Sent by client and server.
 	/// TODO: UpdateMap(MapDelta), Map.apply(MapDelta).
 	UpdateMap {
 		index: ivec3,
 		voxel: Voxel,
+		state: VoxelState,
 	},
 
+	/// Many map updates at once, e.g. by the fluid simulation.
+	///
+	/// Sent by the server.
+	UpdateMapBatch(Vec<MapEdit>),
+
 	/// New position, velocity, ... for a player.
 	/// Replaces previous player data.
 	///
@@ -44,6 +53,15 @@ pub enum Message {
 	},
 
 	AddEffect(Effect),
+
+	/// A player got hurt, e.g. by standing in lava.
+	/// Applied by the client controlling the player (see `Player::take_damage`).
+	///
+	/// Sent by the server.
+	DamagePlayer {
+		player_id: ID,
+		damage: f32,
+	},
 }
 
 impl Message {
@@ -54,20 +72,25 @@ impl Message {
 	pub fn deserialize<R: Read>(r: &mut R) -> Result<Self> {
 		Ok(bincode::deserialize_from(r)?)
 	}
-}
 
-impl fmt::Display for &Message {
-	fn fmt(&self, w: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+	/// Name of the Message variant, for logging and metrics.
+	pub fn name(&self) -> &'static str {
 		use Message::*;
-		let s = match self {
+		match self {
 			Join { .. } => "Join",
 			Accepted { .. } => "Accepted",
 			UpdateMap { .. } => "UpdateMap",
+			UpdateMapBatch(_) => "UpdateMapBatch",
 			UpdatePlayer { .. } => "UpdatePlayer",
 			DropPlayer { .. } => "DropPlayer",
 			AddEffect(_) => "AddEffect",
-		};
+			DamagePlayer { .. } => "DamagePlayer",
+		}
+	}
+}
 
-		write!(w, "{}", s)
+impl fmt::Display for &Message {
+	fn fmt(&self, w: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+		write!(w, "{}", self.name())
 	}
 }
@@ -0,0 +1,243 @@
+use super::internal::*;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Server health metrics, collected by the manager loop
+/// and reported by the status endpoint (see `status.rs`).
+pub struct Metrics {
+	started: Instant,
+	messages_in: BTreeMap<&'static str, Rate>,  // received from clients, by Message variant
+	messages_out: BTreeMap<&'static str, Rate>, // sent to clients, by Message variant
+	map_edits: Rate,
+	tick: TickTimes,
+}
+
+impl Metrics {
+	pub fn new() -> Self {
+		Self {
+			started: Instant::now(),
+			messages_in: BTreeMap::new(),
+			messages_out: BTreeMap::new(),
+			map_edits: Rate::new(),
+			tick: TickTimes::new(),
+		}
+	}
+
+	pub fn uptime(&self) -> Duration {
+		self.started.elapsed()
+	}
+
+	/// Record a message received from a client.
+	pub fn message_in(&mut self, msg: &Message) {
+		self.messages_in.entry(msg.name()).or_insert_with(Rate::new).add(1)
+	}
+
+	/// Record a message sent to a client.
+	pub fn message_out(&mut self, msg: &Message) {
+		self.messages_out.entry(msg.name()).or_insert_with(Rate::new).add(1)
+	}
+
+	pub fn map_edit(&mut self) {
+		self.map_edits.add(1)
+	}
+
+	/// Record how long the manager loop took to handle one event.
+	pub fn tick(&mut self, duration: Duration) {
+		self.tick.record(duration)
+	}
+
+	pub fn messages_in_per_sec(&mut self) -> BTreeMap<&'static str, f32> {
+		self.messages_in.iter_mut().map(|(&k, v)| (k, v.per_sec())).collect()
+	}
+
+	pub fn messages_out_per_sec(&mut self) -> BTreeMap<&'static str, f32> {
+		self.messages_out.iter_mut().map(|(&k, v)| (k, v.per_sec())).collect()
+	}
+
+	pub fn map_edits_per_sec(&mut self) -> f32 {
+		self.map_edits.per_sec()
+	}
+
+	pub fn tick_times(&mut self) -> TickStatus {
+		self.tick.status()
+	}
+}
+
+/// Counts events per second, over consecutive windows of `WINDOW` length.
+/// Reports the rate of the last completed window.
+struct Rate {
+	window_start: Instant,
+	count: u64,
+	last_rate: f32,
+}
+
+const WINDOW: Duration = Duration::from_secs(5);
+
+impl Rate {
+	fn new() -> Self {
+		Self::starting_at(Instant::now())
+	}
+
+	fn starting_at(now: Instant) -> Self {
+		Self {
+			window_start: now,
+			count: 0,
+			last_rate: 0.0,
+		}
+	}
+
+	fn add(&mut self, n: u64) {
+		self.add_at(n, Instant::now())
+	}
+
+	fn add_at(&mut self, n: u64, now: Instant) {
+		self.roll(now);
+		self.count += n;
+	}
+
+	fn per_sec(&mut self) -> f32 {
+		self.per_sec_at(Instant::now())
+	}
+
+	fn per_sec_at(&mut self, now: Instant) -> f32 {
+		self.roll(now);
+		self.last_rate
+	}
+
+	// start a new window if the current one is complete.
+	fn roll(&mut self, now: Instant) {
+		let elapsed = now - self.window_start;
+		if elapsed >= WINDOW {
+			self.last_rate = (self.count as f64 / elapsed.as_secs_f64()) as f32;
+			self.count = 0;
+			self.window_start = now;
+		}
+	}
+}
+
+/// Duration of the manager loop's event handling ("ticks"),
+/// over the current and the last completed window of `WINDOW` length.
+struct TickTimes {
+	last: Duration,
+	current: Vec<Duration>,   // in the current window
+	completed: Vec<Duration>, // in the last completed window
+	window_start: Instant,
+}
+
+/// Tick durations in milliseconds, as reported by the status endpoint.
+/// Percentiles and maximum are over the last 5 to 10 seconds.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TickStatus {
+	pub last_ms: f32,
+	pub p50_ms: f32,
+	pub p99_ms: f32,
+	pub max_ms: f32,
+}
+
+impl TickTimes {
+	/// Keep at most this many durations per window, ignore the rest.
+	const MAX_SAMPLES: usize = 1 << 16;
+
+	fn new() -> Self {
+		Self::starting_at(Instant::now())
+	}
+
+	fn starting_at(now: Instant) -> Self {
+		Self {
+			last: Duration::default(),
+			current: Vec::new(),
+			completed: Vec::new(),
+			window_start: now,
+		}
+	}
+
+	fn record(&mut self, duration: Duration) {
+		self.record_at(duration, Instant::now())
+	}
+
+	fn record_at(&mut self, duration: Duration, now: Instant) {
+		self.roll(now);
+		self.last = duration;
+		if self.current.len() < Self::MAX_SAMPLES {
+			self.current.push(duration);
+		}
+	}
+
+	fn status(&mut self) -> TickStatus {
+		self.status_at(Instant::now())
+	}
+
+	fn status_at(&mut self, now: Instant) -> TickStatus {
+		self.roll(now);
+		let mut sorted = self.completed.iter().chain(&self.current).copied().collect::<Vec<_>>();
+		sorted.sort();
+		TickStatus {
+			last_ms: ms(self.last),
+			p50_ms: ms(percentile(&sorted, 0.50)),
+			p99_ms: ms(percentile(&sorted, 0.99)),
+			max_ms: ms(sorted.last().copied().unwrap_or_default()),
+		}
+	}
+
+	fn roll(&mut self, now: Instant) {
+		if now - self.window_start >= WINDOW {
+			self.completed = std::mem::take(&mut self.current);
+			self.window_start = now;
+		}
+	}
+}
+
+// Nearest-rank percentile (`p` in 0..=1) of sorted durations. Zero if there are none.
+fn percentile(sorted: &[Duration], p: f32) -> Duration {
+	if sorted.is_empty() {
+		return Duration::default();
+	}
+	let rank = (p * sorted.len() as f32).ceil() as usize;
+	sorted[usize::clamp(rank, 1, sorted.len()) - 1]
+}
+
+fn ms(d: Duration) -> f32 {
+	(d.as_secs_f64() * 1000.0) as f32
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn rate() {
+		let t0 = Instant::now();
+		let at = |secs: f32| t0 + Duration::from_secs_f32(secs);
+		let mut r = Rate::starting_at(t0);
+		r.add_at(10, at(1.0));
+		r.add_at(40, at(4.0));
+		assert_eq!(r.per_sec_at(at(4.5)), 0.0); // no window completed yet
+
+		r.add_at(1, at(5.0)); // completes the first window, counts in the second
+		assert_eq!(r.per_sec_at(at(6.0)), 10.0);
+		assert_eq!(r.per_sec_at(at(9.0)), 10.0);
+		assert_eq!(r.per_sec_at(at(10.0)), 0.2);
+		assert_eq!(r.per_sec_at(at(20.0)), 0.0); // idle
+	}
+
+	#[test]
+	fn tick_times() {
+		let t0 = Instant::now();
+		let at = |secs: f32| t0 + Duration::from_secs_f32(secs);
+		let mut t = TickTimes::starting_at(t0);
+		assert_eq!(t.status_at(t0).max_ms, 0.0);
+
+		// 1..=100 ms
+		for i in 1..=100 {
+			t.record_at(Duration::from_millis(i), at(i as f32 * 0.01));
+		}
+		let s = t.status_at(at(2.0));
+		assert_eq!((s.last_ms, s.p50_ms, s.p99_ms, s.max_ms), (100.0, 50.0, 99.0, 100.0));
+
+		// still reported during the next window, forgotten after that.
+		t.record_at(Duration::from_millis(1), at(6.0));
+		assert_eq!(t.status_at(at(9.0)).max_ms, 100.0);
+		let s = t.status_at(at(11.0));
+		assert_eq!((s.last_ms, s.p50_ms, s.p99_ms, s.max_ms), (1.0, 1.0, 1.0, 1.0));
+	}
+}
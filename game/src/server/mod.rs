@@ -1,5 +1,9 @@
 mod internal;
 pub mod prelude;
 
+mod discovery;
+mod map_cache;
 mod message;
+mod metrics;
 mod server;
+mod status;
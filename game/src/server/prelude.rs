@@ -1,4 +1,7 @@
+pub use super::discovery::*;
 pub use super::message::*;
+pub use super::metrics::TickStatus;
 pub use super::server::*;
+pub use super::status::*;
 
 pub use super::super::prelude::*;
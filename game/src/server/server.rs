@@ -1,8 +1,11 @@
 use super::internal::*;
+use std::collections::BTreeMap;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use Message::*;
 
 pub struct Server {
-	clients: HashMap<usize, NetSender>,
+	clients: HashMap<usize, Conn>,
 	events: Receiver<ServerEvent>,
 
 	_clients_send: Sender<ServerEvent>,
@@ -11,29 +14,178 @@ pub struct Server {
 	game_state: GameState,
 	map_file: PathBuf,
 	autosave: bool,
+	map_cache: MapCache,
+	metrics: Metrics,
+	recorder: Option<Recorder>,
+	fluids: FluidSim,
+	collapse: Option<Collapse>,
 }
 
-enum ServerEvent {
+/// Server command-line options.
+pub struct ServerOpts {
+	/// Listen for game connections on this address, if any.
+	/// Clients in the same process can always connect (see `Server::spawn`).
+	pub addr: Option<String>,
+	/// Serve the map loaded from this file.
+	pub map_file: PathBuf,
+	/// Save map edits on client disconnect.
+	pub autosave: bool,
+	/// Serve the status endpoint on this address (see `status.rs`), if any.
+	pub status_addr: Option<String>,
+	/// Announce the server on the LAN (see `discovery.rs`).
+	pub announce: bool,
+	/// Record the game to this file (see `Replay`), if any.
+	pub record: Option<PathBuf>,
+	/// Make voxels that lose their support fall (see `Collapse`), if set.
+	pub collapse: Option<CollapseLimits>,
+}
+
+impl ServerOpts {
+	/// Options for a server embedded in a client (e.g. for playing offline):
+	/// no network, no autosave.
+	pub fn local(map_file: PathBuf) -> Self {
+		Self {
+			addr: None,
+			map_file,
+			autosave: false,
+			status_addr: None,
+			announce: false,
+			record: None,
+			collapse: None,
+		}
+	}
+}
+
+/// A server running in a background thread of this process (see `Server::spawn`).
+/// Dropping it stops the server.
+pub struct LocalServer {
+	events: Sender<ServerEvent>,
+	thread: Option<JoinHandle<()>>,
+}
+
+impl LocalServer {
+	/// Connect to the server in memory, without the network.
+	/// Pass the result to `Client::join`.
+	pub fn connect(&self) -> Result<NetPipe> {
+		let (client, server) = NetPipe::loopback();
+		self.events.send(ServerEvent::Conn(server)).map_err(|_| error("local server: not running"))?;
+		Ok(client)
+	}
+
+	/// The server's current status (as reported by the status endpoint).
+	pub fn status(&self) -> Result<ServerStatus> {
+		query_status(&self.events)
+	}
+}
+
+impl Drop for LocalServer {
+	fn drop(&mut self) {
+		let _ = self.events.send(ServerEvent::Quit); // may have quit already, after an error.
+		if let Some(thread) = self.thread.take() {
+			let _ = thread.join();
+		}
+	}
+}
+
+/// A client connection, and how far it got in the join handshake.
+struct Conn {
+	send: NetSender,
+	state: ConnState,
+	spectator: bool, // watches the game, but has no player in it.
+	addr: String,
+	connected: Instant,
+}
+
+/// Connections go through a join handshake:
+///
+///   Pending -> Accepted -> Active
+///
+/// so that a slow client, or serializing a large map,
+/// never blocks the server's manager loop.
+#[derive(Debug)]
+enum ConnState {
+	/// Connected, waiting for the client's `Join` message.
+	Pending,
+	/// `Join` received, waiting for the map to be serialized.
+	Accepted { skin: usize },
+	/// `Accepted` sent. The client is in the game and receives all broadcasts.
+	Active,
+}
+
+pub(super) enum ServerEvent {
 	Conn(NetPipe),
 	Drop(ID),
 	ClientMessage((ID, Message)),
+	MapSerialized((u64, Vec<u8>)),
+	Status(Sender<ServerStatus>),
+	Timer,
+	/// Stop serving (see `LocalServer`).
+	Quit,
 }
 
 impl Server {
-	/// Listen for connections on `address`,
-	/// serve a game with map loaded from `map_file`.
-	/// Save map edits on client disconnect if `autosave` == true.
+	/// Health lost per second standing in lava.
+	const LAVA_DAMAGE: f32 = 40.0;
+
+	/// Serve a game as specified by `opts`.
 	///
 	/// This function does not return unless there's an error.
-	pub fn serve(address: &str, map_file: PathBuf, autosave: bool) -> Result<()> {
+	pub fn serve(opts: ServerOpts) -> Result<()> {
+		Self::new(opts)?.serve_loop()
+	}
+
+	/// Serve a game in a background thread.
+	/// Clients in this process connect through the returned `LocalServer`,
+	/// others through `opts.addr`, if set.
+	pub fn spawn(opts: ServerOpts) -> Result<LocalServer> {
+		let mut server = Self::new(opts)?;
+		let events = server._clients_send.clone();
+		let thread = std::thread::Builder::new().name("server".into()).spawn(move || {
+			if let Err(e) = server.serve_loop() {
+				error!("server: {}", e)
+			}
+		})?;
+		Ok(LocalServer { events, thread: Some(thread) })
+	}
+
+	// Load the map, start the worker threads (listening, timer, ...).
+	fn new(opts: ServerOpts) -> Result<Self> {
+		let ServerOpts {
+			addr,
+			map_file,
+			autosave,
+			status_addr,
+			announce,
+			record,
+			collapse,
+		} = opts;
+
 		let map = Map::load(&map_file)?;
+		let map_bounds = map.bounds();
 		let players = Players::new();
 		let game_state = GameState::new(map, players);
 
+		let recorder = match record {
+			Some(file) => Some(Recorder::create(file, &game_state, 0)?),
+			None => None,
+		};
+
 		let (clients_send, server_recv) = channel();
-		Self::spawn_listen_loop(address, clients_send.clone())?;
+		let port = match addr {
+			Some(addr) => Some(Self::spawn_listen_loop(&addr, clients_send.clone())?),
+			None => None,
+		};
+		if let Some(status_addr) = status_addr {
+			spawn_status_listener(&status_addr, clients_send.clone())?;
+		}
+		if announce {
+			let port = port.ok_or_else(|| error("announce: not listening on any address"))?;
+			let name = map_file.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+			spawn_announce_loop(name, port, map_bounds, clients_send.clone())?;
+		}
+		Self::spawn_timer(Duration::from_secs_f32(FluidSim::TICK), clients_send.clone());
 
-		let mut server = Self {
+		Ok(Self {
 			clients: HashMap::default(),
 			events: server_recv,
 			_clients_send: clients_send,
@@ -41,91 +193,200 @@ impl Server {
 			game_state,
 			map_file,
 			autosave,
-		};
-
-		server.serve_loop()
+			map_cache: MapCache::new(),
+			metrics: Metrics::new(),
+			recorder,
+			fluids: FluidSim::new(),
+			collapse: collapse.map(Collapse::new),
+		})
 	}
 
 	// Run the "manager task", who exclusively controls the shared state
 	// (game state + client connections) via message passing.
+	// Returns on `ServerEvent::Quit`, which drops all connections, and so stops the worker threads.
 	fn serve_loop(&mut self) -> Result<()> {
 		use ServerEvent::*;
 		loop {
-			match self.events.recv()? {
+			let event = self.events.recv()?;
+			let start = Instant::now(); // time handling the event, not waiting for it.
+			match event {
 				Conn(netpipe) => self.handle_conn(netpipe),
 				Drop(id) => Ok(self.drop_client(id)),
 				ClientMessage((id, msg)) => self.handle_client_msg(id, msg),
+				MapSerialized((version, bytes)) => self.handle_map_serialized(version, bytes),
+				Status(reply) => {
+					self.handle_status(reply);
+					Ok(())
+				}
+				Timer => {
+					self.handle_timer();
+					Ok(())
+				}
+				Quit => {
+					info!("quitting");
+					return Ok(());
+				}
 			}?;
+			self.metrics.tick(start.elapsed());
 		}
 	}
 
 	// Handle a connection event:
-	// add new player to the game, send them the full state.
-	fn handle_conn(&mut self, mut netpipe: NetPipe) -> Result<()> {
-		// First incoming message should be `Join`,
-		// sending us player settings.
-		let skin = match netpipe.recv()? {
-			Join { skin } => skin,
-			bad => return err(format!("server: handle_conn: expected Join, got {}", &bad)),
-		};
-
-		// Add new player to game
-		let mut player = Player::new(skin);
-		player.model.pos = (self.game_state.map().size() / 2).map(|v| v as f32);
-		let player_id = self.next_player_id;
+	// register the connection as pending, and start receiving its messages.
+	// The first message should be `Join` (see `handle_join`).
+	fn handle_conn(&mut self, netpipe: NetPipe) -> Result<()> {
+		let client_id = self.next_player_id;
 		self.next_player_id += 1;
-		self.game_state.update_player(player_id, player);
 
-		// Add new client to clients list
+		let addr = netpipe.peer_addr().to_owned();
 		let (send, recv) = netpipe.split();
-		assert!(!self.clients.contains_key(&player_id));
-		self.clients.insert(player_id, send);
-		Self::start_pipe(player_id, recv, self._clients_send.clone());
-
-		// Respond with full map, player list, new player's ID.
-		self.send(
-			player_id,
-			Accepted {
-				map_data: self.game_state.map().to_bytes(),
-				players: self.game_state.players().clone(),
-				player_id,
+		self.clients.insert(
+			client_id,
+			Conn {
+				send,
+				state: ConnState::Pending,
+				spectator: false,
+				addr,
+				connected: Instant::now(),
 			},
 		);
+		Self::start_pipe(client_id, recv, self._clients_send.clone());
+		Ok(())
+	}
+
+	// Handle a `Join` message from a pending connection.
+	// The client is accepted right away if we have a recent serialized map,
+	// otherwise it has to wait until the map has been serialized in the background.
+	fn handle_join(&mut self, client_id: ID, skin: usize, spectator: bool) {
+		match self.clients.get_mut(&client_id) {
+			Some(conn) if matches!(conn.state, ConnState::Pending) => {
+				conn.state = ConnState::Accepted { skin };
+				conn.spectator = spectator;
+			}
+			_ => {
+				warn!([client = client_id] "sent Join twice, dropping");
+				return self.drop_client(client_id);
+			}
+		}
+
+		if self.map_cache.is_ready() {
+			self.activate_accepted();
+		} else {
+			self.start_serialize_map();
+		}
+	}
 
+	// Serialize a snapshot of the map in a background thread,
+	// which sends the server a `ServerEvent::MapSerialized` when done.
+	// Does nothing if a serialization is already in progress.
+	fn start_serialize_map(&mut self) {
+		if let Some(version) = self.map_cache.begin_snapshot() {
+			let snapshot = self.game_state.map().clone();
+			let send = self._clients_send.clone();
+			std::thread::spawn(move || {
+				let bytes = snapshot.to_bytes();
+				let _ = send.send(ServerEvent::MapSerialized((version, bytes))); // server may have quit.
+			});
+		}
+	}
+
+	fn handle_map_serialized(&mut self, version: u64, bytes: Vec<u8>) -> Result<()> {
+		self.map_cache.finish_snapshot(version, bytes);
+		self.activate_accepted();
 		Ok(())
 	}
 
+	// Add all accepted clients to the game, send them the full state.
+	fn activate_accepted(&mut self) {
+		let (map_data, catch_up) = self.map_cache.get().expect("BUG: activate_accepted: no map data");
+
+		let accepted: Vec<(ID, usize, bool)> = self
+			.clients
+			.iter()
+			.filter_map(|(&id, conn)| match conn.state {
+				ConnState::Accepted { skin } => Some((id, skin, conn.spectator)),
+				_ => None,
+			})
+			.collect();
+
+		for (player_id, skin, spectator) in accepted {
+			// Add new player to game (spectators only watch).
+			if !spectator {
+				let mut player = Player::new(skin);
+				player.model.pos = self.game_state.map().spawn_point();
+				self.game_state.update_player(player_id, player);
+			}
+
+			// Respond with full map, player list, new player's ID.
+			// Followed by the map edits made since the map was serialized.
+			self.send(
+				player_id,
+				Accepted {
+					map_data: map_data.to_vec(),
+					players: self.game_state.players().clone(),
+					player_id,
+				},
+			);
+			for msg in &catch_up {
+				self.send(player_id, msg.clone());
+			}
+
+			self.clients.get_mut(&player_id).unwrap().state = ConnState::Active;
+		}
+	}
+
 	// Handle a dropped connection event:
 	// remove the player from the players list,
 	// and broadcast this to all remaining clients.
 	//
 	// Also save the map if `autosave` == true.
 	fn drop_client(&mut self, player_id: ID) {
-		println!("dropping player {}", player_id);
-		self.clients.remove(&player_id);
-		self.game_state.drop_player(player_id);
-		self.broadcast(DropPlayer { player_id });
+		let conn = match self.clients.remove(&player_id) {
+			Some(conn) => conn,
+			None => return, // already dropped
+		};
+		info!([client = player_id, addr = conn.addr] "dropping client");
 
-		self.trigger_autosave();
+		// Only active, non-spectating clients have a player in the game.
+		if let (ConnState::Active, false) = (conn.state, conn.spectator) {
+			self.game_state.drop_player(player_id);
+			self.broadcast(DropPlayer { player_id });
+			self.trigger_autosave();
+		}
 	}
 
 	fn trigger_autosave(&self) {
 		if !self.autosave {
 			return;
 		}
-		println!("autosaving {}", &self.map_file.to_string_lossy());
+		info!("autosaving {}", &self.map_file.to_string_lossy());
 		if let Err(e) = self.game_state.map().save(&self.map_file) {
 			// There is not much the server can do when an autosave fails.
 			// Aborting would end the game and prevent an future autosave attempt.
-			eprintln!("[!] save error: {}", e)
+			error!("save {}: {}", &self.map_file.to_string_lossy(), e)
 		}
 	}
 
 	/// Handle an incoming game state mutation from one of the connected clients.
 	fn handle_client_msg(&mut self, client_id: ID, msg: Message) -> Result<()> {
 		use Message::*;
+		self.metrics.message_in(&msg);
+
+		if let Join { skin, spectator } = msg {
+			self.handle_join(client_id, skin, spectator);
+			return Ok(());
+		}
+
+		// Clients may only mutate the game state once they have joined,
+		// and spectators may not mutate it at all.
+		if !self.is_player(client_id) {
+			warn!([client = client_id] "sent {} but is not playing, dropping", &msg);
+			self.drop_client(client_id);
+			return Ok(());
+		}
+
 		match msg {
-			UpdateMap { index, voxel } => Ok(self.update_map(index, voxel)),
+			UpdateMap { index, voxel, state } => Ok(self.update_map(index, voxel, state)),
 			UpdatePlayer { player_id, player } => Ok(self.update_player(client_id, player_id, player)),
 			AddEffect(e) => Ok(self.broadcast(AddEffect(e))),
 			bad => err(format!("server: handle_msg: not allowed: {}", &bad)),
@@ -135,9 +396,54 @@ impl Server {
 	/// Handle a map mutation message:
 	///   * Mutate the server's map.
 	///   * Broadcast the mutation to all clients.
-	fn update_map(&mut self, index: ivec3, voxel: Voxel) {
-		self.game_state.update_map(index, voxel);
-		self.broadcast(UpdateMap { index, voxel });
+	fn update_map(&mut self, index: ivec3, voxel: Voxel, state: VoxelState) {
+		self.apply_edit(MapEdit { index, voxel, state });
+		self.broadcast(UpdateMap { index, voxel, state });
+	}
+
+	// Mutate the server's map, without broadcasting.
+	fn apply_edit(&mut self, MapEdit { index, voxel, state }: MapEdit) {
+		let old = self.game_state.map().at(index);
+		self.game_state.update_map(index, voxel, state);
+		self.map_cache.record_edit(index, voxel, state);
+		self.metrics.map_edit();
+		self.fluids.activate(index);
+		if let Some(collapse) = &mut self.collapse {
+			collapse.notify(self.game_state.map(), index, old);
+		}
+	}
+
+	/// Advance the server-side simulation by one `FluidSim::TICK`:
+	///   * Let fluids flow, broadcast the resulting map edits as one batch.
+	///   * Let unsupported voxels fall (if enabled), likewise.
+	///   * Hurt players standing in lava.
+	fn handle_timer(&mut self) {
+		let mut edits = self.fluids.tick(self.game_state.map());
+		for &e in &edits {
+			self.apply_edit(e);
+		}
+
+		if let Some(collapse) = &mut self.collapse {
+			let (collapsed, effects) = collapse.tick(FluidSim::TICK, self.game_state.map());
+			for &e in &collapsed {
+				self.apply_edit(e);
+			}
+			edits.extend(collapsed);
+			for e in effects {
+				self.broadcast(AddEffect(e));
+			}
+		}
+
+		if !edits.is_empty() {
+			self.broadcast(UpdateMapBatch(edits));
+		}
+
+		let map = self.game_state.map();
+		let in_lava = self.game_state.players().iter().filter(|(_, p)| p.in_lava(map)).map(|(&id, _)| id).collect::<Vec<_>>();
+		for player_id in in_lava {
+			let damage = Self::LAVA_DAMAGE * FluidSim::TICK;
+			self.broadcast(DamagePlayer { player_id, damage });
+		}
 	}
 
 	/// Handle a player state mutation (e.g. moving, looking around):
@@ -148,7 +454,7 @@ impl Server {
 	fn update_player(&mut self, client_id: ID, player_id: ID, player: Player) {
 		if client_id != player_id {
 			// clients can't move other players but themselves.
-			println!("client {} attempted to update player {}, dropping", client_id, player_id);
+			warn!([client = client_id] "attempted to update player {}, dropping", player_id);
 			self.drop_client(client_id);
 			return;
 		}
@@ -159,13 +465,78 @@ impl Server {
 
 	/// Send a game state mutation to one client.
 	fn send(&mut self, client_id: ID, msg: Message) {
-		self.clients.get_mut(&client_id).unwrap().send(msg)
+		self.metrics.message_out(&msg);
+		self.clients.get_mut(&client_id).unwrap().send.send(msg)
 	}
 
-	/// Send a message to all connected clients.
+	/// Send a message to all clients that have joined the game.
+	/// (Clients that are still joining will receive the full state later).
 	fn broadcast(&mut self, msg: Message) {
-		for (_client_id, netpipe) in &mut self.clients {
-			netpipe.send(msg.clone())
+		self.record(&msg);
+		for (_client_id, conn) in &mut self.clients {
+			if let ConnState::Active = conn.state {
+				self.metrics.message_out(&msg);
+				conn.send.send(msg.clone())
+			}
+		}
+	}
+
+	// Record a game state mutation, if recording.
+	fn record(&mut self, msg: &Message) {
+		if let Some(recorder) = &mut self.recorder {
+			if let Err(e) = recorder.record(msg) {
+				error!("recording stopped: {}", e);
+				self.recorder = None;
+			}
+		}
+	}
+
+	// Handle a status query (from the status endpoint or LAN announcer).
+	fn handle_status(&mut self, reply: Sender<ServerStatus>) {
+		let _ = reply.send(self.status()); // querying thread may have quit.
+	}
+
+	fn status(&mut self) -> ServerStatus {
+		let mut clients: Vec<ClientStatus> = self
+			.clients
+			.iter()
+			.map(|(&id, conn)| {
+				let stats = conn.send.stats();
+				let secs = conn.connected.elapsed().as_secs_f32();
+				ClientStatus {
+					id,
+					addr: conn.addr.clone(),
+					state: format!("{:?}", conn.state),
+					spectator: conn.spectator,
+					connected_secs: secs,
+					bytes_sent: stats.bytes_sent(),
+					bytes_received: stats.bytes_received(),
+					send_bytes_per_sec: stats.bytes_sent() as f32 / secs,
+					recv_bytes_per_sec: stats.bytes_received() as f32 / secs,
+					queue_depth: stats.queue_depth(),
+				}
+			})
+			.collect();
+		clients.sort_by_key(|c| c.id);
+
+		let by_name = |m: BTreeMap<&'static str, f32>| m.into_iter().map(|(k, v)| (k.to_owned(), v)).collect();
+		ServerStatus {
+			uptime_secs: self.metrics.uptime().as_secs_f32(),
+			players: self.game_state.players().iter().count(),
+			spectators: self.clients.values().filter(|c| c.spectator).count(),
+			clients,
+			messages_in_per_sec: by_name(self.metrics.messages_in_per_sec()),
+			messages_out_per_sec: by_name(self.metrics.messages_out_per_sec()),
+			map_edits_per_sec: self.metrics.map_edits_per_sec(),
+			tick: self.metrics.tick_times(),
+		}
+	}
+
+	// Has `client_id` joined the game as a player (not a spectator)?
+	fn is_player(&self, client_id: ID) -> bool {
+		match self.clients.get(&client_id) {
+			Some(conn) => matches!(conn.state, ConnState::Active) && !conn.spectator,
+			None => false,
 		}
 	}
 
@@ -174,34 +545,45 @@ impl Server {
 	fn start_pipe(player_id: ID, mut recv: NetReceiver, send: Sender<ServerEvent>) {
 		std::thread::spawn(move || loop {
 			match recv.recv() {
-				Err(_e) => {
-					//eprintln!("server: pipe messages from client {}: {}", player_id, e);
-					send.send(ServerEvent::Drop(player_id)).unwrap();
+				Err(e) => {
+					debug!([client = player_id] "receive: {}", e);
+					let _ = send.send(ServerEvent::Drop(player_id)); // server may have quit.
 					return;
 				}
 				Ok(msg) => match send.send(ServerEvent::ClientMessage((player_id, msg))) {
 					Ok(()) => (),
-					Err(_e) => {
-						//eprintln!("server: pipe messages from client {}: {}", player_id, e);
-						send.send(ServerEvent::Drop(player_id)).unwrap();
-						return;
+					Err(e) => {
+						debug!([client = player_id] "forward to server: {}", e);
+						return; // server quit.
 					}
 				},
 			}
 		});
 	}
 
+	// Spawn a loop that sends the server a `ServerEvent::Timer` event every `interval`.
+	fn spawn_timer(interval: Duration, send: Sender<ServerEvent>) {
+		std::thread::spawn(move || loop {
+			std::thread::sleep(interval);
+			if send.send(ServerEvent::Timer).is_err() {
+				return; // server quit, so stop worker thread.
+			}
+		});
+	}
+
 	// Spawn a loop that accepts incoming connections,
 	// sends the server a `ServerEvent::Conn` event for each accepted connection.
-	fn spawn_listen_loop(address: &str, clients_send: Sender<ServerEvent>) -> Result<()> {
+	// Returns the port we're listening on.
+	fn spawn_listen_loop(address: &str, clients_send: Sender<ServerEvent>) -> Result<u16> {
 		let listener = TcpListener::bind(address)?;
-		println!("listening on {}", listener.local_addr().unwrap());
+		let port = listener.local_addr()?.port();
+		info!("listening on {}", listener.local_addr().unwrap());
 		std::thread::spawn(move || {
 			for stream in listener.incoming() {
 				match stream {
-					Err(e) => eprintln!("{}", e), // client failed to connect, server carries on.
+					Err(e) => warn!("accept: {}", e), // client failed to connect, server carries on.
 					Ok(tcp_stream) => {
-						println!("connected to {}", tcp_stream.peer_addr().unwrap());
+						info!("connected to {}", tcp_stream.peer_addr().unwrap());
 						let pipe = NetPipe::new(tcp_stream);
 						if clients_send.send(ServerEvent::Conn(pipe)).is_err() {
 							return; // server quit, so stop worker thread.
@@ -210,6 +592,98 @@ impl Server {
 				}
 			}
 		});
-		Ok(())
+		Ok(port)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	// Poll until `done` holds, or give up after a few seconds.
+	fn wait(mut done: impl FnMut() -> bool) -> bool {
+		let start = Instant::now();
+		while start.elapsed() < Duration::from_secs(5) {
+			if done() {
+				return true;
+			}
+			std::thread::sleep(Duration::from_millis(1));
+		}
+		false
+	}
+
+	// Tick `client` until `done` holds, or give up after a few seconds.
+	fn wait_for(client: &mut Client, done: impl Fn(&Client) -> bool) -> bool {
+		wait(|| {
+			client.tick(0.0);
+			done(client)
+		})
+	}
+
+	fn edit(index: ivec3, voxel: Voxel) -> Message {
+		Message::UpdateMap {
+			index,
+			voxel,
+			state: VoxelState::default(),
+		}
+	}
+
+	// A server and its clients in one process, connected in memory.
+	#[test]
+	fn loopback() {
+		let tmp = |ext: &str| std::env::temp_dir().join(format!("hva_server_test_{}.{}", std::process::id(), ext));
+		let map_file = tmp("bin");
+		Map::flat(uvec3(32, 16, 32)).save(&map_file).unwrap();
+		let server = Server::spawn(ServerOpts::local(map_file.clone())).unwrap();
+		std::fs::remove_file(&map_file).unwrap();
+
+		let mut a = Client::join(server.connect().unwrap(), 1, false).unwrap();
+		let recording = tmp("rec");
+		a.record(&recording).unwrap();
+		let mut watcher = Client::join(server.connect().unwrap(), 0, true).unwrap();
+		let mut b = Client::join(server.connect().unwrap(), 2, false).unwrap();
+		assert_eq!(b.other_players().count(), 1); // b sees a in the state it joined with, spectators have no player
+		let status = server.status().unwrap();
+		assert_eq!((status.players, status.spectators), (2, 1));
+
+		// a moves and builds, b and the spectator see it.
+		let mut player = a.player().clone();
+		player.model.pos = vec3(5.0, 1.0, 7.0);
+		a.update_player(player);
+		let index = ivec3(3, 4, 5);
+		a.send_updates(vec![edit(index, Voxel::LAVA)]);
+		for c in [&mut b, &mut watcher] {
+			assert!(wait_for(c, |c| c.game_state().players.iter().any(|(_, p)| p.model.pos == vec3(5.0, 1.0, 7.0))));
+			assert!(wait_for(c, |c| c.game_state().map().at(index) == Voxel::LAVA));
+		}
+
+		// the edit is broadcast back to its author too.
+		assert!(wait_for(&mut a, |a| a.game_state().map().at(index) == Voxel::LAVA));
+
+		// spectators that try to change the game are dropped. Spectators leaving is not broadcast.
+		watcher.send_updates(vec![edit(ivec3(9, 9, 9), Voxel::SNOW)]);
+		let mut watcher2 = Client::join(server.connect().unwrap(), 0, true).unwrap();
+		watcher2.send_updates(vec![Message::UpdatePlayer {
+			player_id: 0,
+			player: Player::new(0),
+		}]);
+		drop(Client::join(server.connect().unwrap(), 0, true).unwrap());
+		assert!(wait(|| server.status().unwrap().clients.len() == 2));
+		let status = server.status().unwrap();
+		assert_eq!((status.players, status.spectators), (2, 0));
+
+		// once a sees its own later edit, it has received everything caused by the spectators.
+		a.send_updates(vec![edit(ivec3(1, 4, 1), Voxel::SNOW)]);
+		assert!(wait_for(&mut a, |a| a.game_state().map().at(ivec3(1, 4, 1)) == Voxel::SNOW));
+		assert_eq!(a.game_state().map().at(ivec3(9, 9, 9)), Voxel::EMPTY);
+		drop(a); // flushes the recording
+		let records = read_recording(&recording).unwrap();
+		std::fs::remove_file(&recording).unwrap();
+		assert!(!records.iter().any(|(_, msg)| matches!(msg, Message::DropPlayer { .. })));
+
+		// dropping the server stops it (it no longer takes events).
+		let events = server.events.clone();
+		drop(server);
+		assert!(events.send(ServerEvent::Timer).is_err());
 	}
 }
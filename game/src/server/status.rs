@@ -0,0 +1,133 @@
+use super::internal::*;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+
+/// Server health, as reported by the status endpoint.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ServerStatus {
+	pub uptime_secs: f32,
+	pub players: usize,
+	pub spectators: usize,
+	pub clients: Vec<ClientStatus>,
+	pub messages_in_per_sec: BTreeMap<String, f32>,
+	pub messages_out_per_sec: BTreeMap<String, f32>,
+	pub map_edits_per_sec: f32,
+	pub tick: TickStatus,
+}
+
+/// Per-client part of the ServerStatus.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ClientStatus {
+	pub id: ID,
+	pub addr: String,
+	pub state: String,
+	pub spectator: bool,
+	pub connected_secs: f32,
+	pub bytes_sent: u64,
+	pub bytes_received: u64,
+	pub send_bytes_per_sec: f32, // average since connecting
+	pub recv_bytes_per_sec: f32, // average since connecting
+	pub queue_depth: usize,
+}
+
+/// Spawn a loop serving a line-based status endpoint on `address`.
+///
+/// Clients send one command per line, and get a one-line response:
+///
+///   status   ->  ServerStatus as JSON
+///
+/// E.g.: `echo status | nc localhost 3345`.
+pub(super) fn spawn_status_listener(address: &str, server: Sender<ServerEvent>) -> Result<()> {
+	let listener = TcpListener::bind(address)?;
+	info!("status endpoint on {}", listener.local_addr().unwrap());
+	std::thread::spawn(move || {
+		for stream in listener.incoming() {
+			match stream {
+				Err(e) => warn!("accept: {}", e),
+				Ok(stream) => {
+					let server = server.clone();
+					std::thread::spawn(move || {
+						if let Err(e) = serve_status(stream, server) {
+							debug!("serve: {}", e)
+						}
+					});
+				}
+			}
+		}
+	});
+	Ok(())
+}
+
+fn serve_status(stream: TcpStream, server: Sender<ServerEvent>) -> Result<()> {
+	let mut w = stream.try_clone()?;
+	for line in BufReader::new(stream).lines() {
+		if let Some(response) = respond(&line?, &server)? {
+			writeln!(w, "{}", response)?;
+		}
+	}
+	Ok(())
+}
+
+// The response to a command line, if any (none for empty lines).
+fn respond(line: &str, server: &Sender<ServerEvent>) -> Result<Option<String>> {
+	Ok(Some(match line.trim() {
+		"status" => serde_json::to_string(&query_status(server)?)?,
+		"" => return Ok(None),
+		bad => serde_json::to_string(&format!("unknown command: {}", bad))?,
+	}))
+}
+
+/// Ask the server's manager loop for its current status.
+pub(super) fn query_status(server: &Sender<ServerEvent>) -> Result<ServerStatus> {
+	let (send, recv) = channel();
+	server.send(ServerEvent::Status(send)).map_err(|_| error("server has quit"))?;
+	Ok(recv.recv()?)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	// Stands in for the server's manager loop: answers status queries.
+	fn fake_server() -> Sender<ServerEvent> {
+		let (send, recv) = channel();
+		std::thread::spawn(move || {
+			for event in recv {
+				if let ServerEvent::Status(reply) = event {
+					let status = ServerStatus {
+						uptime_secs: 12.0,
+						players: 2,
+						spectators: 1,
+						clients: Vec::new(),
+						messages_in_per_sec: BTreeMap::from([("UpdatePlayer".to_string(), 60.0)]),
+						messages_out_per_sec: BTreeMap::new(),
+						map_edits_per_sec: 0.5,
+						tick: TickStatus {
+							last_ms: 0.1,
+							p50_ms: 0.1,
+							p99_ms: 2.0,
+							max_ms: 3.0,
+						},
+					};
+					reply.send(status).unwrap();
+				}
+			}
+		});
+		send
+	}
+
+	#[test]
+	fn status_commands() {
+		let server = fake_server();
+
+		let reply = respond(" status\r", &server).unwrap().unwrap();
+		assert!(!reply.contains('\n'));
+		let status: ServerStatus = serde_json::from_str(&reply).unwrap();
+		assert_eq!((status.players, status.spectators), (2, 1));
+		assert_eq!(status.messages_in_per_sec["UpdatePlayer"], 60.0);
+		assert_eq!(status.tick.p99_ms, 2.0);
+
+		assert_eq!(respond("reboot", &server).unwrap().unwrap(), r#""unknown command: reboot""#);
+		assert_eq!(respond("", &server).unwrap(), None);
+	}
+}
@@ -0,0 +1,238 @@
+use super::prelude::*;
+
+/// Watches a game via local keyboard/mouse input, without taking part in it
+/// (as opposed to `LocalPlayer`, which controls a player).
+/// The game is either live (`Client`), or recorded (`Replay`).
+///
+/// The camera either flies around freely, or follows a player:
+///
+///   1..9:   follow the 1st..9th player (in order of joining)
+///   0:      free camera
+///   Mouse1: follow the next player
+///   Mouse3: free camera
+pub struct Spectator<W: Watchable = Client> {
+	source: W,
+
+	// record key presses and mouse movements in between ticks (redraws).
+	keys: KeyStates,
+
+	// free camera position, yaw, pitch.
+	pos: vec3,
+	yaw: f32,
+	pitch: f32,
+
+	// player being followed, if any.
+	follow: Option<ID>,
+}
+
+/// A game that runs by itself, and can be watched by a `Spectator`.
+pub trait Watchable {
+	/// Advance time by `dt` seconds.
+	fn tick(&mut self, dt: f32);
+	fn game_state(&self) -> &GameState;
+}
+
+impl Watchable for Client {
+	fn tick(&mut self, dt: f32) {
+		Client::tick(self, dt)
+	}
+	fn game_state(&self) -> &GameState {
+		Client::game_state(self)
+	}
+}
+
+impl<W: Watchable> Spectator<W> {
+	const FLY_SPEED: f32 = 20.0;
+	const SPRINT_FLY_SPEED: f32 = 60.0;
+
+	pub fn new(source: W) -> Self {
+		let bounds = source.game_state().map().bounds();
+		let center = bounds.center();
+		Self {
+			source,
+			keys: KeyStates::new(),
+			pos: vec3(center.x, bounds.max.y as f32, center.z),
+			yaw: 0.0,
+			pitch: 45.0 * DEG,
+			follow: None,
+		}
+	}
+
+	/// Record that `key` was pressed or released.
+	pub fn record_key(&mut self, key: Key, pressed: bool) {
+		self.keys.record(key, pressed)
+	}
+
+	/// Record that the mouse was moved by `(delta_x, delta_y)`.
+	pub fn record_mouse(&mut self, delta: (f64, f64)) {
+		self.keys.record_mouse(delta)
+	}
+
+	/// Advance time, moving the camera based on the key presses
+	/// recorded since the last call to `tick`.
+	pub fn tick(&mut self, dt: f32) {
+		// catch up with the server state (or recording)
+		self.source.tick(dt);
+
+		self.control_follow();
+		if self.follow.is_none() {
+			self.control_free_camera(dt);
+		}
+
+		self.keys.clear(); // must be last
+	}
+
+	/// Select which player to follow (if any).
+	fn control_follow(&mut self) {
+		let ids = self.player_ids();
+
+		for (i, &k) in Key::NUMERIC_KEYS.iter().take(9).enumerate() {
+			if let (true, Some(&id)) = (self.keys.was_pressed(k), ids.get(i)) {
+				self.follow = Some(id);
+			}
+		}
+
+		if self.keys.was_pressed(Key::Mouse1) {
+			let next = match self.follow {
+				None => ids.first(),
+				Some(current) => ids.iter().find(|&&id| id > current).or_else(|| ids.first()),
+			};
+			self.follow = next.copied();
+		}
+
+		if self.keys.was_pressed(Key::Mouse3) || self.keys.was_pressed(Key::Key0) {
+			self.follow = None;
+		}
+
+		// Followed player left the game: continue with a free camera from where they were.
+		if let Some(id) = self.follow {
+			if !self.gs().players.contains(id) {
+				self.follow = None;
+			}
+		}
+
+		// While following, keep the free camera at the player's eyes,
+		// so that switching back to free camera continues from there.
+		if let Some(id) = self.follow {
+			let (pos, yaw, pitch) = self.gs().players.get(id).camera();
+			self.pos = pos;
+			self.yaw = yaw;
+			self.pitch = pitch;
+		}
+	}
+
+	/// Fly the free camera around.
+	fn control_free_camera(&mut self, dt: f32) {
+		let (dx, dy) = self.keys.mouse_delta();
+		self.yaw = wrap_angle(self.yaw - dx); // positive yaw = CCW
+		self.pitch = clamp(self.pitch + dy, -PI / 2.0, PI / 2.0);
+
+		let mut dir = vec3::ZERO;
+		if self.keys.is_down(Key::Left) {
+			dir.x -= 1.0;
+		}
+		if self.keys.is_down(Key::Right) {
+			dir.x += 1.0;
+		}
+		if self.keys.is_down(Key::Forward) {
+			dir.z -= 1.0;
+		}
+		if self.keys.is_down(Key::Backward) {
+			dir.z += 1.0;
+		}
+		let mut dir = yaw_matrix(-self.yaw).transform_point_ignore_w(dir);
+		if self.keys.is_down(Key::Jump) {
+			dir.y += 1.0;
+		}
+		if self.keys.is_down(Key::Crouch) {
+			dir.y -= 1.0;
+		}
+
+		let speed = if self.keys.is_down(Key::Sprint) { Self::SPRINT_FLY_SPEED } else { Self::FLY_SPEED };
+		self.pos += (speed * dt) * dir.safe_normalized();
+	}
+
+	/// IDs of all players in the game, in order of joining.
+	fn player_ids(&self) -> Vec<ID> {
+		let mut ids: Vec<ID> = self.gs().players.iter().map(|(&id, _)| id).collect();
+		ids.sort_unstable();
+		ids
+	}
+
+	/// Follow player `id`, or fly freely if `None`.
+	pub fn follow(&mut self, id: Option<ID>) {
+		self.follow = id
+	}
+
+	/// The game being watched.
+	pub fn source_mut(&mut self) -> &mut W {
+		&mut self.source
+	}
+
+	pub fn gs(&self) -> &GameState {
+		self.source.game_state()
+	}
+
+	#[cfg(feature = "gl")]
+	pub fn draw(&self, ctx: &GLContext) {
+		match self.follow {
+			Some(id) => self.gs().draw(id, ctx),
+			None => self.gs().draw_from((self.pos, self.yaw, self.pitch), None, ctx),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	// A game where nothing happens, unless the test makes it.
+	struct Still(GameState);
+
+	impl Watchable for Still {
+		fn tick(&mut self, _dt: f32) {}
+		fn game_state(&self) -> &GameState {
+			&self.0
+		}
+	}
+
+	fn press(s: &mut Spectator<Still>, key: Key) -> Option<ID> {
+		s.record_key(key, true);
+		s.record_key(key, false);
+		s.tick(0.1);
+		s.follow
+	}
+
+	#[test]
+	fn control_follow() {
+		let mut players = Players::new();
+		for &id in &[8, 3, 5] {
+			let mut player = Player::new(0);
+			player.model.pos = vec3(id as f32, 1.0, 2.0);
+			players.set(id, player);
+		}
+		let mut s = Spectator::new(Still(GameState::new(Map::flat(uvec3(16, 16, 16)), players)));
+		assert_eq!(s.follow, None);
+
+		// number keys select players in order of joining.
+		assert_eq!(press(&mut s, Key::Key2), Some(5));
+		assert_eq!(press(&mut s, Key::Key9), Some(5)); // no 9th player
+		assert_eq!(press(&mut s, Key::Key0), None);
+
+		// Mouse1 cycles through the players.
+		assert_eq!(press(&mut s, Key::Mouse1), Some(3));
+		assert_eq!(press(&mut s, Key::Mouse1), Some(5));
+		assert_eq!(press(&mut s, Key::Mouse1), Some(8));
+		assert_eq!(press(&mut s, Key::Mouse1), Some(3));
+		assert_eq!(press(&mut s, Key::Mouse3), None);
+
+		// when the followed player leaves, the free camera continues from where they were.
+		assert_eq!(press(&mut s, Key::Key3), Some(8));
+		let eyes = s.gs().players.get(8).camera().0;
+		assert_eq!(s.pos, eyes);
+		s.source_mut().0.drop_player(8);
+		s.tick(0.1);
+		assert_eq!(s.follow, None);
+		assert_eq!(s.pos, eyes);
+	}
+}
@@ -0,0 +1,59 @@
+use super::internal::*;
+use std::fmt;
+
+/// Axis-aligned box of voxel indices, from `min` (inclusive) to `max` (exclusive).
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct VoxelBounds {
+	pub min: ivec3,
+	pub max: ivec3,
+}
+
+impl VoxelBounds {
+	/// A box that contains no voxels.
+	pub const EMPTY: Self = Self {
+		min: ivec3(0, 0, 0),
+		max: ivec3(0, 0, 0),
+	};
+
+	pub fn new(min: ivec3, max: ivec3) -> Self {
+		Self { min, max }
+	}
+
+	/// The box from the origin to `size`.
+	pub fn with_size(size: uvec3) -> Self {
+		Self::new(ivec3(0, 0, 0), size.as_ivec())
+	}
+
+	pub fn size(&self) -> ivec3 {
+		self.max - self.min
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.min.x >= self.max.x || self.min.y >= self.max.y || self.min.z >= self.max.z
+	}
+
+	/// Center, in world coordinates.
+	pub fn center(&self) -> vec3 {
+		(self.min.to_vec() + self.max.to_vec()) * 0.5
+	}
+
+	pub fn contains(&self, index: ivec3) -> bool {
+		index.x >= self.min.x && index.y >= self.min.y && index.z >= self.min.z && index.x < self.max.x && index.y < self.max.y && index.z < self.max.z
+	}
+
+	/// Grow the box (if needed) to contain `index`.
+	pub fn add(&mut self, index: ivec3) {
+		if self.is_empty() {
+			*self = Self::new(index, index + ivec3(1, 1, 1));
+			return;
+		}
+		self.min = ivec3(i32::min(self.min.x, index.x), i32::min(self.min.y, index.y), i32::min(self.min.z, index.z));
+		self.max = ivec3(i32::max(self.max.x, index.x + 1), i32::max(self.max.y, index.y + 1), i32::max(self.max.z, index.z + 1));
+	}
+}
+
+impl fmt::Display for VoxelBounds {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}..{}", self.min, self.max)
+	}
+}
@@ -10,22 +10,30 @@ use super::internal::*;
 // }
 
 pub fn cube_at(corner_pos: vec3) -> [Quad; 6] {
+	box_at(corner_pos, vec3::ONES)
+}
+
+/// The faces of a box with given corner and size, in the same order as `cube_at`.
+/// Texture coordinates depend on the position, so that they tile seamlessly
+/// (e.g. a 2x1 box face shows the texture like two cube faces).
+pub fn box_at(corner_pos: vec3, size: vec3) -> [Quad; 6] {
 	let tex_stretch = 1.0 / 8.0;
+	let scale = |p: ivec3| vec3(p.x as f32 * size.x, p.y as f32 * size.y, p.z as f32 * size.z);
 
 	let vx = |x: i32, (tx, ty): (i32, i32), norm: vec3| -> Vertex {
-		let pos = ivec3(x, tx, ty).to_vec() + corner_pos;
+		let pos = scale(ivec3(x, tx, ty)) + corner_pos;
 		let tex = tex_stretch * vec2(pos.y + pos.x, pos.z + pos.x);
 		Vertex::new(pos, tex, norm)
 	};
 
 	let vy = |y: i32, (tx, ty): (i32, i32), norm: vec3| -> Vertex {
-		let pos = ivec3(tx, y, ty).to_vec() + corner_pos;
+		let pos = scale(ivec3(tx, y, ty)) + corner_pos;
 		let tex = tex_stretch * vec2(pos.x + pos.y, pos.z + pos.y);
 		Vertex::new(pos, tex, norm)
 	};
 
 	let vz = |z: i32, (tx, ty): (i32, i32), norm: vec3| -> Vertex {
-		let pos = ivec3(tx, ty, z).to_vec() + corner_pos;
+		let pos = scale(ivec3(tx, ty, z)) + corner_pos;
 		let tex = tex_stretch * vec2(pos.x + pos.z, pos.y + pos.z);
 		Vertex::new(pos, tex, norm)
 	};
@@ -1,6 +1,9 @@
 pub use super::super::prelude::*;
 
 pub use super::cube::*;
+#[cfg(feature = "gl")]
+pub(super) use super::mesher::MeshResults;
 pub use super::quad::*;
+pub use super::shadows::*;
 pub use super::voxel::*;
 pub use super::voxelbox::*;
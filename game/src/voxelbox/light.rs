@@ -0,0 +1,373 @@
+use super::internal::*;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Light levels for every voxel cell of a `VoxelBox`, propagated by flood fill:
+///
+///   * sky light, shining straight down from above the world and spreading sideways,
+///   * colored block light (red, green, blue), emitted by voxels like lava (see `Voxel::emission`).
+///
+/// Light spreads through transparent voxels (and empty space), losing one level per voxel.
+/// Except sky light going straight down through empty space, which does not fall off.
+/// Each channel is propagated independently, so colored light mixes.
+///
+/// Cells outside of the field's region are in open air: full sky light, no block light.
+#[derive(Clone)]
+pub struct LightField {
+	region: VoxelBounds,                   // cells with tracked light: the world's bounds plus a margin.
+	chunks: HashMap<ivec3, Arc<Vec<u16>>>, // 4 bits per channel. Chunks in open air are not stored. Shared with clones until modified.
+	dirty: HashSet<ivec3>,                 // chunks with faces affected by light changes since the last `update`.
+}
+
+impl LightField {
+	/// Light level of full sky light and of the brightest emitters.
+	pub const MAX: u8 = 15;
+
+	/// Channels (see `level`).
+	pub const SKY: usize = 0;
+	pub const RED: usize = 1;
+	pub const GREEN: usize = 2;
+	pub const BLUE: usize = 3;
+
+	// Chunk size, same as the VoxelBox's, so that dirty chunks can be re-meshed.
+	const POW: u32 = 4;
+	const SIZE: i32 = 1 << Self::POW;
+	const MASK: i32 = Self::SIZE - 1;
+
+	const OPEN_AIR: u16 = Self::MAX as u16; // sky channel in the lowest bits.
+	const DOWN: ivec3 = ivec3(0, -1, 0);
+	const NEIGHBORS: [ivec3; 6] = [ivec3(0, -1, 0), ivec3(-1, 0, 0), ivec3(1, 0, 0), ivec3(0, 0, -1), ivec3(0, 0, 1), ivec3(0, 1, 0)];
+
+	/// Compute the light of all cells in (and around) `voxels`.
+	pub fn new(voxels: &VoxelBox) -> Self {
+		let mut light = Self {
+			region: Self::region_for(voxels),
+			chunks: HashMap::default(),
+			dirty: HashSet::default(),
+		};
+		let emitters = light.init_sky(voxels);
+		for ch in Self::RED..=Self::BLUE {
+			let mut queue = VecDeque::new();
+			for &index in &emitters {
+				light.set_level(index, ch, Self::emission_level(voxels.at(index), ch));
+				queue.push_back(index);
+			}
+			light.propagate(voxels, ch, queue);
+		}
+		light.dirty.clear();
+		light
+	}
+
+	/// Does the field hold all cells that can be lit in a world with given bounds?
+	/// Unbounded worlds may grow out of their field, which must then be re-computed.
+	pub fn covers(&self, bounds: VoxelBounds) -> bool {
+		bounds.is_empty() || (self.region.contains(bounds.min - ivec3(1, 1, 1)) && self.region.contains(bounds.max))
+	}
+
+	/// Light level (0..=MAX) of channel `ch` (`SKY`, `RED`, ...) at a cell.
+	pub fn level(&self, index: ivec3, ch: usize) -> u8 {
+		((self.cell(index) >> (4 * ch)) & 0xf) as u8
+	}
+
+	/// Update the light after the voxel at `index` has changed (the change is already made in `voxels`).
+	/// Returns the chunks (by chunk position) with faces that are lit differently now.
+	pub fn update(&mut self, voxels: &VoxelBox, index: ivec3) -> HashSet<ivec3> {
+		let voxel = voxels.at(index);
+		for ch in Self::SKY..=Self::BLUE {
+			// Take away all light that may have passed through (or come from) `index`,
+			// then let it flow back in from the surroundings that are still lit.
+			let mut relight = VecDeque::new();
+			let level = self.level(index, ch);
+			if level != 0 {
+				self.set_level(index, ch, 0);
+				self.remove(voxels, ch, index, level, &mut relight);
+			}
+			let emission = Self::emission_level(voxel, ch);
+			if emission > self.level(index, ch) {
+				self.set_level(index, ch, emission);
+				relight.push_back(index);
+			}
+			if voxel.is_transparent() {
+				relight.extend(Self::NEIGHBORS.iter().map(|&d| index + d));
+			}
+			self.propagate(voxels, ch, relight);
+		}
+		std::mem::take(&mut self.dirty)
+	}
+
+	fn region_for(voxels: &VoxelBox) -> VoxelBounds {
+		let bounds = voxels.bounds();
+		if bounds.is_empty() {
+			return VoxelBounds::EMPTY;
+		}
+		// Unbounded worlds get some room to grow before the field must be re-computed.
+		let margin = if voxels.is_unbounded() { Self::SIZE } else { 1 };
+		let margin = ivec3(margin, margin, margin);
+		VoxelBounds::new(bounds.min - margin, bounds.max + margin)
+	}
+
+	// Sky light, column by column: full light down to the first non-empty voxel, dark below.
+	// Then spread sideways into the dark parts. Returns the emissive voxels found along the way.
+	fn init_sky(&mut self, voxels: &VoxelBox) -> Vec<ivec3> {
+		let r = self.region;
+		let size = r.size();
+		let mut emitters = Vec::new();
+
+		// lowest cell with full sky light, per column.
+		let mut sky_floor = vec![r.max.y; (size.x * size.z).max(0) as usize];
+		let column = |ix: i32, iz: i32| ((iz - r.min.z) * size.x + (ix - r.min.x)) as usize;
+
+		for iz in r.min.z..r.max.z {
+			for ix in r.min.x..r.max.x {
+				let mut lit = true;
+				for iy in (r.min.y..r.max.y).rev() {
+					let index = ivec3(ix, iy, iz);
+					let v = voxels.at(index);
+					if v.is_emissive() {
+						emitters.push(index);
+					}
+					lit = lit && v == Voxel::EMPTY;
+					if lit {
+						sky_floor[column(ix, iz)] = iy;
+					} else {
+						self.set_level(index, Self::SKY, 0);
+					}
+				}
+			}
+		}
+
+		// Spread from lit cells next to a dark cell: the column's floor (spreading down),
+		// and cells next to a neighboring column that is dark at that height.
+		let mut queue = VecDeque::new();
+		for iz in r.min.z..r.max.z {
+			for ix in r.min.x..r.max.x {
+				let floor = sky_floor[column(ix, iz)];
+				let mut top = floor;
+				for &(dx, dz) in &[(-1, 0), (1, 0), (0, -1), (0, 1)] {
+					let (nx, nz) = (ix + dx, iz + dz);
+					if nx >= r.min.x && nx < r.max.x && nz >= r.min.z && nz < r.max.z {
+						top = i32::max(top, sky_floor[column(nx, nz)] - 1);
+					}
+				}
+				for iy in floor..=i32::min(top, r.max.y - 1) {
+					queue.push_back(ivec3(ix, iy, iz));
+				}
+			}
+		}
+		self.propagate(voxels, Self::SKY, queue);
+		emitters
+	}
+
+	// Flood fill light from the cells in `queue` to their neighbors, as far as it reaches.
+	fn propagate(&mut self, voxels: &VoxelBox, ch: usize, mut queue: VecDeque<ivec3>) {
+		while let Some(index) = queue.pop_front() {
+			let level = self.level(index, ch);
+			if level == 0 {
+				continue;
+			}
+			for &d in &Self::NEIGHBORS {
+				let neighbor = index + d;
+				if !self.region.contains(neighbor) {
+					continue;
+				}
+				let v = voxels.at(neighbor);
+				if !v.is_transparent() {
+					continue;
+				}
+				let new = if ch == Self::SKY && d == Self::DOWN && level == Self::MAX && v == Voxel::EMPTY {
+					Self::MAX
+				} else {
+					level - 1
+				};
+				if new > self.level(neighbor, ch) {
+					self.set_level(neighbor, ch, new);
+					queue.push_back(neighbor);
+				}
+			}
+		}
+	}
+
+	// Remove the light that came from `start` (which had `level`, and was just set to 0):
+	// darken all cells it lit, and collect the surrounding cells that are lit otherwise
+	// in `relight`, for `propagate` to fill the darkened cells from.
+	fn remove(&mut self, voxels: &VoxelBox, ch: usize, start: ivec3, level: u8, relight: &mut VecDeque<ivec3>) {
+		let mut queue = VecDeque::new();
+		queue.push_back((start, level));
+		let mut emitters = Vec::new();
+		while let Some((index, level)) = queue.pop_front() {
+			if Self::emission_level(voxels.at(index), ch) != 0 {
+				emitters.push(index);
+			}
+			for &d in &Self::NEIGHBORS {
+				let neighbor = index + d;
+				if !self.region.contains(neighbor) {
+					continue;
+				}
+				let l = self.level(neighbor, ch);
+				if l == 0 {
+					continue;
+				}
+				if l < level || (ch == Self::SKY && d == Self::DOWN && level == Self::MAX) {
+					self.set_level(neighbor, ch, 0);
+					queue.push_back((neighbor, l));
+				} else {
+					relight.push_back(neighbor);
+				}
+			}
+		}
+		// emitters that were darkened shine again.
+		for index in emitters {
+			let emission = Self::emission_level(voxels.at(index), ch);
+			if emission > self.level(index, ch) {
+				self.set_level(index, ch, emission);
+				relight.push_back(index);
+			}
+		}
+	}
+
+	fn emission_level(v: Voxel, ch: usize) -> u8 {
+		let e = v.emission();
+		let intensity = match ch {
+			Self::RED => e.x,
+			Self::GREEN => e.y,
+			Self::BLUE => e.z,
+			_ => 0.0,
+		};
+		(intensity.clamp(0.0, 1.0) * (Self::MAX as f32)).round() as u8
+	}
+
+	fn cell(&self, index: ivec3) -> u16 {
+		let (chnk, i) = Self::index_internal(index);
+		match self.chunks.get(&chnk) {
+			Some(chunk) => chunk[i],
+			None => Self::OPEN_AIR,
+		}
+	}
+
+	fn set_level(&mut self, index: ivec3, ch: usize, level: u8) {
+		debug_assert!(level <= Self::MAX);
+		let (chnk, i) = Self::index_internal(index);
+		let old = self.cell(index);
+		let new = (old & !(0xf << (4 * ch))) | ((level as u16) << (4 * ch));
+		if new == old {
+			return;
+		}
+		Arc::make_mut(self.chunks.entry(chnk).or_insert_with(|| Arc::new(vec![Self::OPEN_AIR; 1 << (3 * Self::POW)])))[i] = new;
+
+		// Faces are lit by the cells next to them (see `VoxelSnapshot::calc_vertex_light`),
+		// which may be in a neighboring chunk.
+		let lo = (index - ivec3(1, 1, 1)).map(|v| v >> Self::POW);
+		let hi = (index + ivec3(1, 1, 1)).map(|v| v >> Self::POW);
+		for cz in lo.z..=hi.z {
+			for cy in lo.y..=hi.y {
+				for cx in lo.x..=hi.x {
+					self.dirty.insert(ivec3(cx, cy, cz));
+				}
+			}
+		}
+	}
+
+	fn index_internal(index: ivec3) -> (ivec3, usize) {
+		let chnk = index.map(|v| v >> Self::POW);
+		let int = index.map(|v| v & Self::MASK);
+		(chnk, ((int.y << (2 * Self::POW)) | (int.z << Self::POW) | int.x) as usize)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn assert_same(got: &LightField, want: &LightField) {
+		let r = want.region;
+		for iz in r.min.z..r.max.z {
+			for iy in r.min.y..r.max.y {
+				for ix in r.min.x..r.max.x {
+					let index = ivec3(ix, iy, iz);
+					assert_eq!(got.cell(index), want.cell(index), "at {}", index);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn sky() {
+		let mut c = VoxelBox::new(uvec3(32, 16, 32));
+		for iz in 0..32 {
+			for ix in 0..32 {
+				c.set(ivec3(ix, 0, iz), Voxel::GREYSTONE);
+			}
+		}
+		// a roof over (8..12, 4, 8..12)
+		for iz in 8..12 {
+			for ix in 8..12 {
+				c.set(ivec3(ix, 4, iz), Voxel::GREYSTONE);
+			}
+		}
+		let l = LightField::new(&c);
+		let sky = |index| l.level(index, LightField::SKY);
+		assert_eq!(sky(ivec3(2, 1, 2)), LightField::MAX); // open air, all the way down
+		assert_eq!(sky(ivec3(2, 0, 2)), 0); // inside the ground
+		assert_eq!(sky(ivec3(8, 1, 8)), LightField::MAX - 1); // under the edge of the roof
+		assert_eq!(sky(ivec3(9, 3, 9)), LightField::MAX - 2); // deeper under the roof
+		assert_eq!(sky(ivec3(9, 1, 9)), LightField::MAX - 2);
+		assert_eq!(l.level(ivec3(2, 1, 2), LightField::RED), 0);
+	}
+
+	#[test]
+	fn lava_lights_cave() {
+		let mut c = VoxelBox::new(uvec3(32, 32, 32));
+		for iz in 0..32 {
+			for iy in 0..32 {
+				for ix in 0..32 {
+					c.set(ivec3(ix, iy, iz), Voxel::GREYSTONE);
+				}
+			}
+		}
+		// a closed tunnel at y = 10, with lava at one end.
+		for ix in 5..25 {
+			c.set(ivec3(ix, 10, 10), Voxel::EMPTY);
+		}
+		c.set(ivec3(5, 10, 10), Voxel::LAVA);
+		let l = LightField::new(&c);
+
+		assert_eq!(l.level(ivec3(20, 10, 10), LightField::SKY), 0); // dark
+		assert_eq!(l.level(ivec3(6, 10, 10), LightField::RED), LightField::MAX - 1);
+		assert_eq!(l.level(ivec3(10, 10, 10), LightField::RED), LightField::MAX - 5);
+		assert_eq!(l.level(ivec3(6, 10, 10), LightField::GREEN), 4); // lava is orange
+		assert_eq!(l.level(ivec3(10, 10, 10), LightField::GREEN), 0);
+		assert_eq!(l.level(ivec3(6, 10, 10), LightField::BLUE), 0);
+		assert_eq!(l.level(ivec3(6, 11, 10), LightField::RED), 0); // inside the rock
+	}
+
+	// Incremental updates give the same light as computing from scratch.
+	#[test]
+	fn update() {
+		let mut c = VoxelBox::new(uvec3(32, 24, 32));
+		for iz in 0..32 {
+			for ix in 0..32 {
+				for iy in 0..(4 + (ix * iz) % 7) {
+					c.set(ivec3(ix, iy, iz), Voxel::GREYSTONE);
+				}
+			}
+		}
+		let glass = VoxelTypes::get().by_name("glass").unwrap();
+		let mut l = LightField::new(&c);
+
+		let edits = [
+			(ivec3(10, 15, 10), Voxel::GREYSTONE), // block the sky
+			(ivec3(10, 16, 10), Voxel::LAVA),      // light on top
+			(ivec3(3, 2, 3), Voxel::EMPTY),        // dig into the ground
+			(ivec3(3, 1, 3), Voxel::CRYSTAL),      // light in the ground
+			(ivec3(3, 2, 3), glass),               // covered with glass
+			(ivec3(10, 16, 10), Voxel::EMPTY),     // light removed
+			(ivec3(10, 15, 10), Voxel::EMPTY),     // sky unblocked
+			(ivec3(3, 1, 3), Voxel::GREYSTONE),    // light replaced
+		];
+		for &(index, v) in &edits {
+			c.set(index, v);
+			l.update(&c, index);
+			assert_same(&l, &LightField::new(&c));
+		}
+	}
+}
@@ -0,0 +1,218 @@
+use super::internal::*;
+#[cfg(feature = "gl")]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+#[cfg(feature = "gl")]
+use std::sync::mpsc::{channel, Receiver, TryIter};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::thread;
+
+/// Builds chunk meshes (the CPU part: `MeshBuffer`s) on background threads,
+/// so that the render thread only has to upload them (see `VoxelBox::draw`).
+///
+/// Jobs nearest to the camera are done first.
+/// A newer job for the same chunk replaces the pending one.
+/// One pool is shared by all VoxelBoxes in the process, started when first used.
+pub struct MeshPool {
+	queue: Mutex<Queue>,
+	pending: Condvar, // signaled when a job is added
+}
+
+struct Queue {
+	jobs: HashMap<(u64, ivec3), MeshJob>, // by VoxelBox id (see `MeshResults`), chunk position.
+	camera: vec3,
+}
+
+/// A request to build the meshes of one chunk.
+pub struct MeshJob {
+	pub chnk: ivec3,
+	pub version: u64, // version of the chunk (see `Chunk`), returned with the result.
+	pub lod: Lod,
+	pub meshing: Meshing,
+	pub snapshot: Arc<VoxelSnapshot>,
+	pub results: Sender<MeshResult>,
+}
+
+/// The meshes built for a `MeshJob`.
+pub struct MeshResult {
+	pub chnk: ivec3,
+	pub version: u64,
+	pub buffers: Vec<MeshBuffer>, // by tex_id
+}
+
+static POOL: OnceLock<MeshPool> = OnceLock::new();
+
+impl MeshPool {
+	/// At most this many worker threads, leaving a core for the render thread.
+	const MAX_WORKERS: usize = 4;
+
+	/// The process-wide pool, started if needed.
+	pub fn get() -> &'static Self {
+		POOL.get_or_init(|| {
+			let workers = thread::available_parallelism().map(|n| n.get().saturating_sub(1)).unwrap_or(1).clamp(1, Self::MAX_WORKERS);
+			debug!("starting {} mesh workers", workers);
+			for i in 0..workers {
+				thread::Builder::new().name(format!("mesher-{}", i)).spawn(|| Self::get().work()).expect("spawn mesh worker");
+			}
+			Self::new()
+		})
+	}
+
+	fn new() -> Self {
+		Self {
+			queue: Mutex::new(Queue {
+				jobs: HashMap::default(),
+				camera: vec3::ZERO,
+			}),
+			pending: Condvar::new(),
+		}
+	}
+
+	/// Queue a job for the VoxelBox with given id.
+	pub fn submit(&self, owner: u64, job: MeshJob) {
+		self.queue.lock().unwrap().jobs.insert((owner, job.chnk), job);
+		self.pending.notify_one();
+	}
+
+	/// Prioritize jobs near this position.
+	pub fn set_camera(&self, pos: vec3) {
+		self.queue.lock().unwrap().camera = pos;
+	}
+
+	// Worker thread: build meshes forever.
+	fn work(&self) {
+		loop {
+			let job = self.take();
+			let buffers = job.snapshot.build_meshes(job.chnk, job.lod, job.meshing);
+			// The VoxelBox may have been dropped in the meantime, that's fine.
+			let _ = job.results.send(MeshResult {
+				chnk: job.chnk,
+				version: job.version,
+				buffers,
+			});
+		}
+	}
+
+	// Wait for the next job.
+	fn take(&self) -> MeshJob {
+		let mut queue = self.queue.lock().unwrap();
+		loop {
+			if let Some(job) = queue.pop_nearest() {
+				return job;
+			}
+			queue = self.pending.wait(queue).unwrap();
+		}
+	}
+}
+
+impl Queue {
+	fn pop_nearest(&mut self) -> Option<MeshJob> {
+		let camera = self.camera;
+		let dist = |chnk: ivec3| (Chunk::center(chnk) - camera).len2();
+		let nearest = self.jobs.keys().copied().min_by(|a, b| dist(a.1).partial_cmp(&dist(b.1)).unwrap_or(std::cmp::Ordering::Equal))?;
+		self.jobs.remove(&nearest)
+	}
+}
+
+/// Where a VoxelBox receives the meshes built for it.
+/// Each VoxelBox (including clones) has its own.
+#[cfg(feature = "gl")]
+pub(super) struct MeshResults {
+	id: u64,
+	send: Sender<MeshResult>,
+	recv: Receiver<MeshResult>,
+}
+
+#[cfg(feature = "gl")]
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+#[cfg(feature = "gl")]
+impl MeshResults {
+	pub fn new() -> Self {
+		let (send, recv) = channel();
+		Self {
+			id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+			send,
+			recv,
+		}
+	}
+
+	/// Ask the pool to build the meshes of a chunk.
+	pub fn submit(&self, chnk: ivec3, version: u64, lod: Lod, snapshot: Arc<VoxelSnapshot>) {
+		MeshPool::get().submit(
+			self.id,
+			MeshJob {
+				chnk,
+				version,
+				lod,
+				meshing: Meshing::get(),
+				snapshot,
+				results: self.send.clone(),
+			},
+		)
+	}
+
+	/// The results that came in so far.
+	pub fn ready(&self) -> TryIter<'_, MeshResult> {
+		self.recv.try_iter()
+	}
+}
+
+#[cfg(feature = "gl")]
+impl Clone for MeshResults {
+	fn clone(&self) -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use std::sync::mpsc::channel;
+
+	fn snapshot() -> Arc<VoxelSnapshot> {
+		let mut c = VoxelBox::new(uvec3(64, 16, 64));
+		for iz in 0..64 {
+			for ix in 0..64 {
+				c.set(ivec3(ix, 0, iz), Voxel::SNOW);
+			}
+		}
+		Arc::new(c.snapshot())
+	}
+
+	#[test]
+	fn mesh_pool() {
+		let snapshot = snapshot();
+
+		// nearest first, newest job per chunk.
+		let pool = MeshPool::new();
+		let (send, _recv) = channel();
+		for &(chnk, version) in &[(ivec3(0, 0, 0), 1), (ivec3(3, 0, 3), 1), (ivec3(1, 0, 1), 1), (ivec3(3, 0, 3), 2)] {
+			let job = MeshJob {
+				chnk,
+				version,
+				lod: Lod::FULL,
+				meshing: Meshing::Greedy,
+				snapshot: snapshot.clone(),
+				results: send.clone(),
+			};
+			pool.submit(1, job);
+		}
+		pool.set_camera(vec3(64.0, 0.0, 64.0));
+		let order = (0..3).map(|_| pool.take()).map(|job| (job.chnk, job.version)).collect::<Vec<_>>();
+		assert_eq!(order, vec![(ivec3(3, 0, 3), 2), (ivec3(1, 0, 1), 1), (ivec3(0, 0, 0), 1)]);
+	}
+
+	#[test]
+	#[cfg(feature = "gl")]
+	fn mesh_results() {
+		// results from the process-wide pool, same as built in place.
+		let snapshot = snapshot();
+		let results = MeshResults::new();
+		results.submit(ivec3(1, 0, 1), 7, Lod::FULL, snapshot.clone());
+		let result = results.recv.recv().unwrap();
+		assert_eq!((result.chnk, result.version), (ivec3(1, 0, 1), 7));
+		let want = snapshot.build_meshes(ivec3(1, 0, 1), Lod::FULL, Meshing::get());
+		assert_eq!(result.buffers.iter().map(|b| b.len()).collect::<Vec<_>>(), want.iter().map(|b| b.len()).collect::<Vec<_>>());
+	}
+}
@@ -0,0 +1,370 @@
+use super::internal::*;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How chunk meshes are built (see `VoxelSnapshot::build_meshes`):
+///
+///   * Naive: two triangles per visible voxel face (well, four, see `Quad`).
+///   * Greedy: adjacent faces in the same plane, with the same texture and uniform lighting,
+///     are merged into larger quads. Much fewer vertices for flat terrain.
+///
+/// Process-wide, like the `VoxelTypes`. Set before meshes are built.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Meshing {
+	Naive = 0,
+	Greedy = 1,
+}
+
+static MESHING: AtomicU8 = AtomicU8::new(Meshing::Greedy as u8);
+
+impl Meshing {
+	/// The meshing in use (default: Greedy).
+	pub fn get() -> Self {
+		match MESHING.load(Ordering::Relaxed) {
+			0 => Meshing::Naive,
+			_ => Meshing::Greedy,
+		}
+	}
+
+	/// Use this meshing for all meshes built from now on.
+	pub fn set(self) {
+		MESHING.store(self as u8, Ordering::Relaxed)
+	}
+}
+
+impl FromStr for Meshing {
+	type Err = String;
+	fn from_str(s: &str) -> std::result::Result<Self, String> {
+		match s.to_ascii_lowercase().as_str() {
+			"naive" => Ok(Meshing::Naive),
+			"greedy" => Ok(Meshing::Greedy),
+			bad => Err(format!("unknown meshing: {} (need naive or greedy)", bad)),
+		}
+	}
+}
+
+/// Level of detail of a chunk mesh: chunks are meshed from cells of 2^lod voxels on a side,
+/// so that distant chunks have fewer triangles (see `VoxelSnapshot::build_meshes`).
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Lod(pub u8);
+
+impl Lod {
+	/// Full detail: one cell per voxel.
+	pub const FULL: Self = Lod(0);
+
+	/// Chunks further away than these distances (from the camera) are meshed at the next level of detail.
+	const DIST: [f32; 2] = [48.0, 96.0];
+	/// Chunks within this distance of a threshold keep their current level of detail,
+	/// so that they don't flip back and forth as the camera moves a little.
+	const HYSTERESIS: f32 = 8.0;
+
+	/// Level of detail for a chunk at given distance, currently meshed at `current`.
+	pub fn for_distance(dist: f32, current: Lod) -> Lod {
+		let h = 0.5 * Self::HYSTERESIS;
+		let coarsest = Self::DIST.iter().filter(|&&d| dist > d + h).count() as u8;
+		let finest = Self::DIST.iter().filter(|&&d| dist > d - h).count() as u8;
+		Lod(current.0.clamp(coarsest, finest))
+	}
+
+	/// Cell size, in voxels.
+	pub fn scale(self) -> i32 {
+		1 << self.0
+	}
+}
+
+/// The uniformly lit faces of one chunk, to be merged by greedy meshing.
+/// Faces are stored per direction, per slice of the chunk along that direction.
+pub(super) struct GreedyFaces {
+	size: u32,                         // chunk size, in cells.
+	scale: i32,                        // cell size, in voxels (see `Lod`).
+	faces: Vec<Option<(Voxel, vec3)>>, // voxel type, vertex attrib. Indexed by `index`.
+}
+
+impl GreedyFaces {
+	pub fn new(size: u32, scale: i32) -> Self {
+		Self {
+			size,
+			scale,
+			faces: vec![None; (6 * size * size * size) as usize],
+		}
+	}
+
+	/// Add a face of the cell at position `int` inside the chunk,
+	/// in direction `face_dir` (see `cube_at`), with the same `attrib` for all vertices.
+	pub fn add(&mut self, face_dir: usize, int: uvec3, voxel: Voxel, attrib: vec3) {
+		let (slice, u, v) = Self::plane_coords(face_dir, int);
+		let i = self.index(face_dir, slice, u, v);
+		self.faces[i] = Some((voxel, attrib));
+	}
+
+	/// Merge the faces into as few rectangles as (greedily) possible,
+	/// and add them to the mesh buffers (by tex_id).
+	/// `world_offset`: position of the chunk's first voxel.
+	pub fn build(mut self, world_offset: ivec3, buffers: &mut [MeshBuffer]) {
+		let n = self.size;
+		for face_dir in 0..6 {
+			for slice in 0..n {
+				for v in 0..n {
+					for u in 0..n {
+						let key = match self.faces[self.index(face_dir, slice, u, v)] {
+							None => continue,
+							Some(key) => key,
+						};
+
+						// grow right as far as possible, then grow up for as long as all of the row matches.
+						let mut w = 1;
+						while u + w < n && self.faces[self.index(face_dir, slice, u + w, v)] == Some(key) {
+							w += 1;
+						}
+						let mut h = 1;
+						while v + h < n && (u..u + w).all(|u| self.faces[self.index(face_dir, slice, u, v + h)] == Some(key)) {
+							h += 1;
+						}
+						for dv in 0..h {
+							for du in 0..w {
+								let i = self.index(face_dir, slice, u + du, v + dv);
+								self.faces[i] = None;
+							}
+						}
+
+						let (voxel, attrib) = key;
+						let corner = world_offset + Self::chunk_coords(face_dir, slice, u, v).as_ivec() * self.scale;
+						let size = (Self::chunk_coords(face_dir, 1, w, h).as_ivec() * self.scale).to_vec();
+						let mut face = box_at(corner.to_vec(), size)[face_dir].clone();
+						for i in 0..4 {
+							face.set_vertex(i, Vertex { attrib, ..face.vertex(i).clone() });
+						}
+						buffers[voxel.tex_id()].push_all(&face.triangle_vertices());
+					}
+				}
+			}
+		}
+	}
+
+	fn index(&self, face_dir: usize, slice: u32, u: u32, v: u32) -> usize {
+		let n = self.size as usize;
+		((face_dir * n + slice as usize) * n + v as usize) * n + u as usize
+	}
+
+	// Position inside the chunk -> (slice along the face normal, u, v inside the slice).
+	fn plane_coords(face_dir: usize, int: uvec3) -> (u32, u32, u32) {
+		match face_dir / 2 {
+			0 => (int.x, int.y, int.z),
+			1 => (int.y, int.x, int.z),
+			_ => (int.z, int.x, int.y),
+		}
+	}
+
+	// Inverse of `plane_coords`.
+	fn chunk_coords(face_dir: usize, slice: u32, u: u32, v: u32) -> uvec3 {
+		match face_dir / 2 {
+			0 => uvec3(slice, u, v),
+			1 => uvec3(u, slice, v),
+			_ => uvec3(u, v, slice),
+		}
+	}
+}
+
+impl VoxelSnapshot {
+	/// Brightness of the light from the sky (see `LightField`), and direct sunlight (see `Shadows`).
+	const AMBIENT: f32 = 0.6;
+	const SUNLIGHT: f32 = 0.5;
+
+	/// The vertices of a chunk's visible faces, one buffer per voxel type (by tex_id),
+	/// at given level of detail.
+	pub fn build_meshes(&self, chnk: ivec3, lod: Lod, meshing: Meshing) -> Vec<MeshBuffer> {
+		let world_offset = chnk * (Chunk::SIZE as i32);
+		let scale = lod.scale();
+		let n = Chunk::SIZE >> lod.0; // chunk size in cells
+		let mut shadows = Shadows::new(self, self.sun_dir());
+
+		let mut b: Vec<MeshBuffer> = zeros(VoxelTypes::get().count()); // by tex_id
+		let mut greedy = GreedyFaces::new(n, scale);
+
+		// the chunk's voxels, downsampled.
+		let mut cells = Vec::with_capacity((n * n * n) as usize);
+		for iy in 0..n {
+			for iz in 0..n {
+				for ix in 0..n {
+					cells.push(self.cell_at(world_offset + uvec3(ix, iy, iz).as_ivec() * scale, scale));
+				}
+			}
+		}
+		let cell = |int: ivec3| cells[((int.y as u32 * n + int.z as u32) * n + int.x as u32) as usize];
+
+		let neighbors = [
+			ivec3(-1, 0, 0), // left
+			ivec3(1, 0, 0),  // right
+			ivec3(0, -1, 0), // bottom
+			ivec3(0, 1, 0),  // top
+			ivec3(0, 0, -1), // back
+			ivec3(0, 0, 1),  // front
+		];
+
+		for iy in 0..n {
+			for iz in 0..n {
+				for ix in 0..n {
+					// cell coordinates inside chunk
+					let cell_int = uvec3(ix, iy, iz);
+					let block = cell(cell_int.as_ivec());
+
+					// global voxel coordinates of the cell's first voxel
+					let cell_world = world_offset + cell_int.as_ivec() * scale;
+					let cube_corner = cell_world.map(|v| v as f32);
+
+					if block != Voxel::EMPTY {
+						let cube_faces = box_at(cube_corner, (scale as f32) * vec3::ONES);
+
+						for (i, &d) in neighbors.iter().enumerate() {
+							let neigh = cell_int.as_ivec() + d;
+							let inside = neigh.x >= 0 && neigh.y >= 0 && neigh.z >= 0 && neigh.x < n as i32 && neigh.y < n as i32 && neigh.z < n as i32;
+							let visible = match inside {
+								true => Self::face_visible(block, cell(neigh)),
+								false => self.boundary_face_visible(block, cell_world, scale, d),
+							};
+							if visible {
+								let mut face = self.with_light(&mut shadows, i, &cube_faces[i]);
+								if block.is_emissive() {
+									face = Self::with_emission(&face, block.emission())
+								}
+								let attrib = face.vertex(0).attrib;
+								if meshing == Meshing::Greedy && (1..4).all(|v| face.vertex(v).attrib == attrib) {
+									greedy.add(i, cell_int, block, attrib);
+								} else {
+									b[block.tex_id()].push_all(&(face.triangle_vertices()))
+								}
+							}
+						}
+					}
+				}
+			}
+		}
+		greedy.build(world_offset, &mut b);
+		b
+	}
+
+	// The voxel representing the `scale`^3 voxels starting at `min`, for level of detail meshes:
+	// the most common opaque voxel, else the most common transparent one, else empty.
+	// So coarse cells contain all voxels of the full detail mesh (and then some).
+	fn cell_at(&self, min: ivec3, scale: i32) -> Voxel {
+		if scale == 1 {
+			return self.at(min);
+		}
+		let mut count = [0u32; 256];
+		for iy in 0..scale {
+			for iz in 0..scale {
+				for ix in 0..scale {
+					count[self.at(min + ivec3(ix, iy, iz)).id() as usize] += 1;
+				}
+			}
+		}
+		let most_common = |transparent: bool| {
+			(1..256)
+				.map(|id| Voxel::from(id as u8))
+				.filter(|v| count[v.id() as usize] != 0 && v.is_transparent() == transparent)
+				.max_by_key(|v| count[v.id() as usize])
+		};
+		most_common(false).or_else(|| most_common(true)).unwrap_or(Voxel::EMPTY)
+	}
+
+	// Is the face of a cell on the chunk boundary visible?
+	// The neighboring chunk may be meshed at a different level of detail,
+	// so look at its voxels at full detail: the face is visible if any voxel right across it can be seen through.
+	// Otherwise, a coarse cell sticking out of (the full detail mesh of) a neighbor would leave a hole.
+	fn boundary_face_visible(&self, block: Voxel, cell_world: ivec3, scale: i32, d: ivec3) -> bool {
+		// first voxel across the face, then the patch of scale x scale voxels along it.
+		let start = cell_world + d.map(|v| if v > 0 { scale } else { v });
+		let (du, dv) = match (d.x != 0, d.y != 0) {
+			(true, _) => (ivec3(0, 1, 0), ivec3(0, 0, 1)),
+			(_, true) => (ivec3(1, 0, 0), ivec3(0, 0, 1)),
+			_ => (ivec3(1, 0, 0), ivec3(0, 1, 0)),
+		};
+		(0..scale).any(|v| (0..scale).any(|u| Self::face_visible(block, self.at(start + du * u + dv * v))))
+	}
+
+	/// Is the face of voxel `block`, adjacent to voxel `neighbor`, visible?
+	/// Only if the neighbor can be seen through.
+	/// Faces between transparent voxels of the same type (e.g. inside water) are not drawn.
+	pub(super) fn face_visible(block: Voxel, neighbor: Voxel) -> bool {
+		neighbor == Voxel::EMPTY || (neighbor.is_transparent() && neighbor != block)
+	}
+
+	fn with_emission(face: &Quad, emission: vec3) -> Quad {
+		let mut result = face.clone();
+		for i in 0..4 {
+			result.set_vertex(
+				i,
+				Vertex {
+					attrib: 0.5 * face.vertex(i).attrib + 0.5 * emission,
+					..face.vertex(i).clone()
+				},
+			)
+		}
+		result
+	}
+
+	fn with_light(&self, shadows: &mut Shadows, face_dir: usize, face: &Quad) -> Quad {
+		let mut face = face.clone();
+
+		let n = face.normal();
+		let t1 = face.tangent1();
+		let t2 = face.tangent2();
+		for i in 0..4 {
+			let attrib = self.calc_vertex_light(shadows, face_dir, face.vertex(i).pos, n, t1, t2);
+			face.set_vertex(i, Vertex { attrib, ..face.vertex(i).clone() })
+		}
+		face
+	}
+
+	// Smooth lighting: average the light of the 4 cells in front of the face that touch the vertex.
+	// Opaque cells are dark, which darkens corners (ambient occlusion).
+	// Sunlight adds to this, depending on the angle with the sun, if the cells are not in the shadow.
+	fn calc_vertex_light(&self, shadows: &mut Shadows, face_dir: usize, pos: vec3, n: vec3, t1: vec3, t2: vec3) -> vec3 {
+		let probe_starts = [
+			pos + 0.5 * (n + t1 + t2), //
+			pos + 0.5 * (n - t1 + t2), //
+			pos + 0.5 * (n + t1 - t2), //
+			pos + 0.5 * (n - t1 - t2), //
+		];
+
+		// sky light comes from above
+		let face_light = [
+			0.65, // left
+			0.65, // right
+			0.15, // bottom
+			1.00, // top
+			0.45, // back
+			0.45, //front
+		];
+		let mul = face_light[face_dir];
+
+		let sun = f32::max(0.0, n.dot(shadows.sun_dir()));
+
+		let mut accum = vec3::ZERO;
+		let mut sunlit = 0;
+		for &start in &probe_starts {
+			let cell = start.floor_ivec();
+			accum += self.light_probe(cell, mul);
+			if sun > 0.0 && shadows.is_sunlit(cell) {
+				sunlit += 1;
+			}
+		}
+		0.25 * (Self::AMBIENT * accum + (Self::SUNLIGHT * sun * sunlit as f32) * vec3::ONES)
+	}
+
+	fn light_probe(&self, index: ivec3, mul: f32) -> vec3 {
+		let light = self.light();
+		let sky = mul * Self::brightness(light.level(index, LightField::SKY)) * vec3::ONES;
+		let block = vec3(
+			Self::brightness(light.level(index, LightField::RED)),
+			Self::brightness(light.level(index, LightField::GREEN)),
+			Self::brightness(light.level(index, LightField::BLUE)),
+		);
+		sky + block
+	}
+
+	// Perceived brightness of a light level: each level down is 20% darker.
+	fn brightness(level: u8) -> f32 {
+		f32::powi(0.8, (LightField::MAX - level) as i32)
+	}
+}
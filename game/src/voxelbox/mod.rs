@@ -2,7 +2,17 @@ pub mod prelude;
 
 mod internal;
 
+mod bounds;
 pub mod cube;
+mod light;
+mod mesher;
+mod meshing;
 mod quad;
+mod raycast;
+mod shadows;
+mod snapshot;
+mod sweep;
 mod voxel;
+mod voxel_state;
+mod voxel_types;
 mod voxelbox;
@@ -1,2 +1,11 @@
+pub use super::bounds::*;
+pub use super::light::*;
+pub use super::mesher::*;
+pub use super::meshing::*;
+pub use super::raycast::*;
+pub use super::snapshot::*;
+pub use super::sweep::*;
 pub use super::voxel::*;
+pub use super::voxel_state::*;
+pub use super::voxel_types::*;
 pub use super::voxelbox::*;
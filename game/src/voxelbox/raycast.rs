@@ -0,0 +1,197 @@
+use super::internal::*;
+
+/// Walks a ray through the voxel grid, visiting every cell it passes through, in order
+/// (Amanatides & Woo, "A Fast Voxel Traversal Algorithm for Ray Tracing").
+///
+/// Yields `(cell, dist, normal)`: the distance along the ray where it enters the cell,
+/// and the normal of the face it enters through (pointing back towards the ray's origin).
+/// The first cell is the one containing the start point, with distance 0 and normal zero.
+/// The ray never ends: stop iterating at the desired distance.
+pub struct VoxelRay {
+	cell: ivec3,
+	step: [i32; 3],    // -1, 0 or 1 per axis
+	t_max: [f32; 3],   // distance along the ray to the next cell boundary, per axis
+	t_delta: [f32; 3], // distance along the ray between cell boundaries, per axis
+	dist: f32,         // where the ray entered the current cell
+	normal: ivec3,     // face through which the ray entered the current cell
+	started: bool,
+}
+
+impl VoxelRay {
+	/// A ray from `start`, in direction `dir` (normalized).
+	pub fn new(start: vec3, dir: vec3) -> Self {
+		let cell = start.floor_ivec();
+		let (pos, dir, cell_arr) = ([start.x, start.y, start.z], [dir.x, dir.y, dir.z], [cell.x, cell.y, cell.z]);
+		let mut step = [0; 3];
+		let mut t_max = [f32::INFINITY; 3];
+		let mut t_delta = [f32::INFINITY; 3];
+		for i in 0..3 {
+			if dir[i] != 0.0 {
+				step[i] = if dir[i] > 0.0 { 1 } else { -1 };
+				t_delta[i] = 1.0 / dir[i].abs();
+				let boundary = if dir[i] > 0.0 { cell_arr[i] + 1 } else { cell_arr[i] } as f32;
+				t_max[i] = (boundary - pos[i]) / dir[i];
+			}
+		}
+		Self {
+			cell,
+			step,
+			t_max,
+			t_delta,
+			dist: 0.0,
+			normal: ivec3(0, 0, 0),
+			started: false,
+		}
+	}
+
+	/// Can the ray never (again) enter `bounds`, from the current cell on?
+	fn leaving(&self, bounds: &VoxelBounds) -> bool {
+		let (cell, min, max) = ([self.cell.x, self.cell.y, self.cell.z], bounds.min, bounds.max);
+		let (min, max) = ([min.x, min.y, min.z], [max.x, max.y, max.z]);
+		(0..3).any(|i| (cell[i] < min[i] && self.step[i] <= 0) || (cell[i] >= max[i] && self.step[i] >= 0))
+	}
+}
+
+impl Iterator for VoxelRay {
+	type Item = (ivec3, f32, ivec3);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if !self.started {
+			self.started = true;
+			return Some((self.cell, self.dist, self.normal));
+		}
+
+		let axis = if self.t_max[0] < self.t_max[1] && self.t_max[0] < self.t_max[2] {
+			0
+		} else if self.t_max[1] < self.t_max[2] {
+			1
+		} else {
+			2
+		};
+		if self.t_max[axis] == f32::INFINITY {
+			return None; // zero direction: stuck in the first cell.
+		}
+		self.dist = self.t_max[axis];
+		self.t_max[axis] += self.t_delta[axis];
+		let mut delta = [0; 3];
+		delta[axis] = self.step[axis];
+		let delta = ivec3(delta[0], delta[1], delta[2]);
+		self.cell += delta;
+		self.normal = -delta;
+		Some((self.cell, self.dist, self.normal))
+	}
+}
+
+/// Where a ray hits a voxel (see `VoxelBox::raycast`).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RayHit {
+	/// The voxel that was hit.
+	pub index: ivec3,
+	/// Normal of the face the ray entered through, pointing back towards the ray's origin.
+	/// Zero if the ray started inside the voxel.
+	pub normal: ivec3,
+	/// Distance from the ray's origin to where it entered the voxel.
+	pub dist: f32,
+	/// Where the ray entered the voxel.
+	pub pos: vec3,
+}
+
+impl RayHit {
+	/// The cell the ray came from, next to the hit voxel (e.g. to place a voxel against it).
+	/// Empty, unless the ray started inside the hit voxel.
+	pub fn before(&self) -> ivec3 {
+		self.index + self.normal
+	}
+}
+
+impl VoxelBox {
+	/// The first voxel for which `hit` returns true, along a ray from `start` in direction `dir` (normalized),
+	/// if within distance `max`.
+	pub fn raycast(&self, start: vec3, dir: vec3, max: f32, hit: impl Fn(Voxel) -> bool) -> Option<RayHit> {
+		let bounds = self.bounds();
+		let mut ray = VoxelRay::new(start, dir);
+		while let Some((index, dist, normal)) = ray.next() {
+			if dist > max {
+				return None;
+			}
+			if hit(self.at(index)) {
+				return Some(RayHit {
+					index,
+					normal,
+					dist,
+					pos: start + dist * dir,
+				});
+			}
+			if ray.leaving(&bounds) {
+				return None; // no more voxels to hit.
+			}
+		}
+		None
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn voxel_ray() {
+		// along an axis
+		let cells = VoxelRay::new(vec3(0.5, 0.5, 0.5), vec3(1.0, 0.0, 0.0)).take(3).collect::<Vec<_>>();
+		assert_eq!(
+			cells,
+			vec![
+				(ivec3(0, 0, 0), 0.0, ivec3(0, 0, 0)),
+				(ivec3(1, 0, 0), 0.5, ivec3(-1, 0, 0)),
+				(ivec3(2, 0, 0), 1.5, ivec3(-1, 0, 0)),
+			]
+		);
+
+		// diagonally, from a negative cell: every cell shares a face with the previous one.
+		let dir = vec3(-1.0, 2.0, 0.5).normalized();
+		let mut prev = None;
+		for (cell, dist, normal) in VoxelRay::new(vec3(-0.3, -5.2, 7.9), dir).take(50) {
+			let pos = vec3(-0.3, -5.2, 7.9) + (dist + 1e-4) * dir;
+			assert_eq!(pos.floor_ivec(), cell);
+			if let Some(prev) = prev {
+				assert_eq!(cell + normal, prev);
+			}
+			prev = Some(cell);
+		}
+	}
+
+	#[test]
+	fn raycast() {
+		let mut c = VoxelBox::new(uvec3(16, 16, 16));
+		for iz in 0..16 {
+			for ix in 0..16 {
+				c.set(ivec3(ix, 0, iz), Voxel::SNOW);
+			}
+		}
+
+		// straight down onto the floor
+		let hit = c.raycast(vec3(3.5, 10.0, 4.5), vec3(0.0, -1.0, 0.0), 100.0, |v| v != Voxel::EMPTY).unwrap();
+		assert_eq!(hit.index, ivec3(3, 0, 4));
+		assert_eq!(hit.normal, ivec3(0, 1, 0));
+		assert_eq!(hit.before(), ivec3(3, 1, 4));
+		assert!((hit.dist - 9.0).abs() < 1e-5);
+		assert!((hit.pos - vec3(3.5, 1.0, 4.5)).len() < 1e-5);
+
+		// out of range, pointing away, leaving the world.
+		assert_eq!(c.raycast(vec3(3.5, 10.0, 4.5), vec3(0.0, -1.0, 0.0), 8.0, |v| v != Voxel::EMPTY), None);
+		assert_eq!(c.raycast(vec3(3.5, 10.0, 4.5), vec3(0.0, 1.0, 0.0), 1000.0, |v| v != Voxel::EMPTY), None);
+		assert_eq!(c.raycast(vec3(3.5, 10.0, 4.5), vec3(1.0, 0.0, 0.0), 1e9, |v| v != Voxel::EMPTY), None);
+
+		// from outside the world
+		let hit = c.raycast(vec3(-10.0, 0.5, 2.5), vec3(1.0, 0.0, 0.0), 100.0, |v| v != Voxel::EMPTY).unwrap();
+		assert_eq!((hit.index, hit.normal), (ivec3(0, 0, 2), ivec3(-1, 0, 0)));
+
+		// grazing the corner of a voxel, between 0.1 steps.
+		c.set(ivec3(5, 5, 5), Voxel::SNOW);
+		let start = vec3(4.0, 4.97, 5.5);
+		let dir = vec3(1.0, 1.0, 0.0).normalized();
+		let hit = c.raycast(start, dir, 10.0, |v| v != Voxel::EMPTY).unwrap();
+		assert_eq!((hit.index, hit.normal), (ivec3(5, 5, 5), ivec3(-1, 0, 0)));
+		assert!((0..100).map(|i| (start + (0.1 * i as f32) * dir).floor_ivec()).all(|cell| cell != ivec3(5, 5, 5)));
+	}
+}
@@ -0,0 +1,112 @@
+use super::internal::*;
+
+/// Direct sunlight, for baking sun shadows into the meshes of a chunk.
+///
+/// A cell is sunlit if a ray from its center towards the sun leaves the world
+/// without passing through an opaque voxel. Rays are traced through the voxel grid
+/// one cell at a time (see `VoxelRay`), and cached, as the faces of a chunk share most of their cells.
+pub struct Shadows<'a> {
+	voxels: &'a VoxelSnapshot,
+	sun_dir: vec3,               // towards the sun, normalized.
+	cache: HashMap<ivec3, bool>, // cell -> sunlit
+}
+
+impl<'a> Shadows<'a> {
+	/// Voxels further away than this (towards the sun) do not cast shadows.
+	pub const MAX_DIST: f32 = 128.0;
+
+	pub fn new(voxels: &'a VoxelSnapshot, sun_dir: vec3) -> Self {
+		Self {
+			voxels,
+			sun_dir: sun_dir.normalized(),
+			cache: HashMap::default(),
+		}
+	}
+
+	/// Direction towards the sun.
+	pub fn sun_dir(&self) -> vec3 {
+		self.sun_dir
+	}
+
+	/// Does the sun shine on (the center of) a cell?
+	/// Opaque cells are never sunlit.
+	pub fn is_sunlit(&mut self, cell: ivec3) -> bool {
+		if let Some(&lit) = self.cache.get(&cell) {
+			return lit;
+		}
+		let lit = self.voxels.at(cell).is_transparent() && self.trace(cell);
+		self.cache.insert(cell, lit);
+		lit
+	}
+
+	// Walk the grid from the center of `start` towards the sun.
+	fn trace(&self, start: ivec3) -> bool {
+		let bounds = self.voxels.bounds();
+		for (cell, dist, _) in VoxelRay::new(start.to_vec() + vec3(0.5, 0.5, 0.5), self.sun_dir).skip(1) {
+			if dist > Self::MAX_DIST {
+				return true;
+			}
+			if !bounds.contains(cell) {
+				return true; // left the world, can't come back.
+			}
+			if !self.voxels.at(cell).is_transparent() {
+				return false;
+			}
+		}
+		true
+	}
+
+	/// Positions along the shadow cast by the voxel at `index`:
+	/// the cells whose sunlight may change when that voxel changes are within 1 voxel of these.
+	pub fn shadow_line(index: ivec3, sun_dir: vec3, bounds: VoxelBounds) -> impl Iterator<Item = ivec3> {
+		let start = index.to_vec() + vec3(0.5, 0.5, 0.5);
+		let dir = -sun_dir.normalized();
+		let step = 0.5;
+		(0..=((Self::MAX_DIST / step) as usize))
+			.map(move |i| (start + (i as f32 * step) * dir).floor_ivec())
+			.take_while(move |&cell| bounds.contains(cell))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn shadows() {
+		let mut c = VoxelBox::new(uvec3(32, 32, 32));
+		for iz in 0..32 {
+			for ix in 0..32 {
+				c.set(ivec3(ix, 0, iz), Voxel::GREYSTONE);
+			}
+		}
+		c.set(ivec3(10, 10, 10), Voxel::GREYSTONE); // floating block
+
+		// sun straight above: the shadow is right below.
+		let snapshot = c.snapshot();
+		let mut s = Shadows::new(&snapshot, vec3(0.0, 1.0, 0.0));
+		assert!(s.is_sunlit(ivec3(10, 11, 10)));
+		assert!(!s.is_sunlit(ivec3(10, 9, 10)));
+		assert!(!s.is_sunlit(ivec3(10, 1, 10)));
+		assert!(s.is_sunlit(ivec3(11, 1, 10)));
+		assert!(!s.is_sunlit(ivec3(5, 0, 5))); // opaque
+
+		// sun at 45 degrees (from +x): the shadow moves to -x.
+		let mut s = Shadows::new(&snapshot, vec3(1.0, 1.0, 0.0));
+		assert!(s.is_sunlit(ivec3(10, 1, 10)));
+		assert!(!s.is_sunlit(ivec3(1, 1, 10)));
+		assert!(!s.is_sunlit(ivec3(9, 9, 10)));
+		assert!(s.is_sunlit(ivec3(9, 9, 11)));
+
+		// glass does not cast shadows.
+		c.set(ivec3(10, 10, 10), VoxelTypes::get().by_name("glass").unwrap());
+		let snapshot = c.snapshot();
+		let mut s = Shadows::new(&snapshot, vec3(0.0, 1.0, 0.0));
+		assert!(s.is_sunlit(ivec3(10, 1, 10)));
+
+		// the shadow line covers the shadow.
+		let line = Shadows::shadow_line(ivec3(10, 10, 10), vec3(1.0, 1.0, 0.0), c.bounds()).collect::<Vec<_>>();
+		assert!(line.contains(&ivec3(1, 1, 10)));
+		assert!(line.contains(&ivec3(9, 9, 10)));
+	}
+}
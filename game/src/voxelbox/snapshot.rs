@@ -0,0 +1,42 @@
+use super::internal::*;
+use std::sync::Arc;
+
+/// A frozen, read-only copy of a VoxelBox's voxels and light,
+/// for building meshes on other threads (see `MeshPool`).
+///
+/// Cheap to make: chunks are shared with the VoxelBox, which copies them when it modifies them.
+pub struct VoxelSnapshot {
+	bounds: VoxelBounds,
+	chunks: HashMap<ivec3, Arc<Vec<Voxel>>>, // as in `Chunk`
+	light: LightField,
+	sun_dir: vec3,
+}
+
+impl VoxelSnapshot {
+	pub(super) fn new(bounds: VoxelBounds, chunks: HashMap<ivec3, Arc<Vec<Voxel>>>, light: LightField, sun_dir: vec3) -> Self {
+		Self { bounds, chunks, light, sun_dir }
+	}
+
+	/// Voxel at given position, as `VoxelBox::at`.
+	pub fn at(&self, index: ivec3) -> Voxel {
+		let (chnk, int) = VoxelBox::index_internal(index);
+		match self.chunks.get(&chnk) {
+			None => Voxel::EMPTY,
+			Some(voxels) => voxels[Chunk::index_internal(int)],
+		}
+	}
+
+	/// The VoxelBox's bounds, as `VoxelBox::bounds`.
+	pub fn bounds(&self) -> VoxelBounds {
+		self.bounds
+	}
+
+	pub fn light(&self) -> &LightField {
+		&self.light
+	}
+
+	/// Direction towards the sun.
+	pub fn sun_dir(&self) -> vec3 {
+		self.sun_dir
+	}
+}
@@ -0,0 +1,190 @@
+use super::internal::*;
+
+/// Result of moving a box through the voxel grid (see `VoxelBox::sweep`).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Sweep {
+	/// How far the box got. Movement along an axis stops at the first contact on that axis,
+	/// movement along the other axes continues (the box slides along the voxels).
+	pub delta: vec3,
+	/// Time of impact per axis, as a fraction of the movement. 1 if there was no contact on that axis.
+	pub toi: vec3,
+	/// Per axis, the normal of the voxel face that was touched (-1 or 1), or 0 if none.
+	pub normal: ivec3,
+}
+
+impl Sweep {
+	/// Time of the first impact, as a fraction of the movement (1 if none).
+	pub fn first_toi(&self) -> f32 {
+		self.toi.x.min(self.toi.y).min(self.toi.z)
+	}
+
+	pub fn hit(&self) -> bool {
+		self.normal != ivec3(0, 0, 0)
+	}
+}
+
+impl VoxelBox {
+	// Boxes touching a voxel face within this distance are in contact, but do not overlap.
+	pub(super) const SWEEP_EPS: f32 = 1e-3;
+
+	/// Move a box by `delta`, stopping at the first solid voxel in the way, per axis.
+	///
+	/// Exact, at any speed: the box's leading faces are stepped through every layer of voxels they cross.
+	/// Voxels only stop the box when it enters them: the layers of voxels it already overlaps at the start are ignored,
+	/// so that a box stuck inside voxels can get out (see `Model::tick_rescue`).
+	pub fn sweep(&self, bounds: &BoundingBox, delta: vec3) -> Sweep {
+		let eps = Self::SWEEP_EPS;
+		let mut min = [bounds.min().x, bounds.min().y, bounds.min().z];
+		let mut max = [bounds.max().x, bounds.max().y, bounds.max().z];
+		let mut d = [delta.x, delta.y, delta.z]; // remaining movement
+		let mut toi = [1.0; 3];
+		let mut normal = [0; 3];
+		let mut t = 0.0; // time (fraction of `delta`) so far
+
+		// Range of voxels overlapped by [lo, hi] along an axis.
+		// Touching (within eps) does not count, but a thin box always overlaps at least one voxel.
+		let cells = |lo: f32, hi: f32| {
+			let first = (lo + eps).floor() as i32;
+			(first, i32::max(first, (hi - eps).ceil() as i32 - 1))
+		};
+
+		// The next layer of voxels each leading face will enter.
+		let mut next = [0; 3];
+		for i in 0..3 {
+			let (first, last) = cells(min[i], max[i]);
+			next[i] = if d[i] > 0.0 { last + 1 } else { first - 1 };
+		}
+
+		loop {
+			// the axis whose leading face enters a new layer first, and when (as a fraction of the remaining movement).
+			let mut axis = None;
+			let mut t_axis = 1.0;
+			for i in 0..3 {
+				if d[i] == 0.0 {
+					continue;
+				}
+				let dist = if d[i] > 0.0 { next[i] as f32 - max[i] } else { min[i] - (next[i] + 1) as f32 };
+				let t = f32::max(0.0, dist) / d[i].abs();
+				if t <= t_axis {
+					axis = Some(i);
+					t_axis = t;
+				}
+			}
+
+			// move up to there.
+			for i in 0..3 {
+				min[i] += t_axis * d[i];
+				max[i] += t_axis * d[i];
+			}
+			t += t_axis * (1.0 - t);
+			for di in &mut d {
+				*di *= 1.0 - t_axis;
+			}
+			let i = match axis {
+				None => break, // moved all the way.
+				Some(i) => i,
+			};
+
+			// contact if any voxel of the new layer (across the box's extent along the other axes) is solid.
+			let (j, k) = ((i + 1) % 3, (i + 2) % 3);
+			let (j0, j1) = cells(min[j], max[j]);
+			let (k0, k1) = cells(min[k], max[k]);
+			let mut blocked = false;
+			'layer: for cj in j0..=j1 {
+				for ck in k0..=k1 {
+					let mut index = [0; 3];
+					index[i] = next[i];
+					index[j] = cj;
+					index[k] = ck;
+					if self.at(ivec3(index[0], index[1], index[2])).is_solid() {
+						blocked = true;
+						break 'layer;
+					}
+				}
+			}
+
+			if blocked {
+				// touch exactly, without rounding errors.
+				let snap = if d[i] > 0.0 { next[i] as f32 - max[i] } else { (next[i] + 1) as f32 - min[i] };
+				min[i] += snap;
+				max[i] += snap;
+				normal[i] = if d[i] > 0.0 { -1 } else { 1 };
+				toi[i] = t;
+				d[i] = 0.0;
+			} else {
+				next[i] += if d[i] > 0.0 { 1 } else { -1 };
+			}
+			if d == [0.0; 3] {
+				break;
+			}
+		}
+
+		Sweep {
+			delta: vec3(min[0], min[1], min[2]) - bounds.min(),
+			toi: vec3(toi[0], toi[1], toi[2]),
+			normal: ivec3(normal[0], normal[1], normal[2]),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn unit_box(min: vec3) -> BoundingBox {
+		BoundingBox::new(min, min + vec3(0.8, 1.8, 0.8))
+	}
+
+	#[test]
+	fn sweep() {
+		let mut c = VoxelBox::new(uvec3(32, 32, 32));
+		for iz in 0..32 {
+			for ix in 0..32 {
+				c.set(ivec3(ix, 0, iz), Voxel::SNOW); // floor
+				c.set(ivec3(20, 1 + (ix % 2), iz), Voxel::GREYSTONE); // wall at x=20
+			}
+		}
+
+		// falling onto the floor
+		let s = c.sweep(&unit_box(vec3(5.1, 3.0, 5.1)), vec3(0.0, -4.0, 0.0));
+		assert!((s.delta - vec3(0.0, -2.0, 0.0)).len() < 1e-4, "{:?}", s);
+		assert_eq!(s.normal, ivec3(0, 1, 0));
+		assert!((s.toi.y - 0.5).abs() < 1e-4);
+		assert_eq!(s.toi.x, 1.0);
+		assert!((s.first_toi() - 0.5).abs() < 1e-4);
+
+		// no tunneling, at any speed
+		let s = c.sweep(&unit_box(vec3(5.1, 3.0, 5.1)), vec3(0.0, -1e4, 0.0));
+		assert!((s.delta.y + 2.0).abs() < 1e-4);
+
+		// standing on the floor: sliding along it, into the wall.
+		let s = c.sweep(&unit_box(vec3(5.1, 1.0, 5.1)), vec3(30.0, -1.0, 2.0));
+		assert_eq!(s.normal, ivec3(-1, 1, 0));
+		assert!((s.delta - vec3(20.0 - 5.9, 0.0, 2.0)).len() < 1e-3, "{:?}", s);
+		assert_eq!(s.toi.y, 0.0);
+
+		// touching the wall, moving along it, and away from it.
+		let s = c.sweep(&unit_box(vec3(19.2, 1.0, 5.1)), vec3(0.0, 0.0, 3.0));
+		assert!(!s.hit());
+		let s = c.sweep(&unit_box(vec3(19.2, 1.0, 5.1)), vec3(-3.0, 0.0, 0.0));
+		assert!(!s.hit());
+
+		// a point (e.g. a particle) hitting the floor.
+		let p = vec3(7.5, 10.0, 7.5);
+		let s = c.sweep(&BoundingBox::new(p, p), vec3(1.0, -20.0, 0.0));
+		assert_eq!(s.normal, ivec3(0, 1, 0));
+		assert!((p + s.delta - vec3(8.5, 1.0, 7.5)).len() < 1e-3, "{:?}", s); // sliding after landing
+
+		// starting inside voxels: can get out.
+		let s = c.sweep(&unit_box(vec3(5.1, 0.5, 5.1)), vec3(0.0, 2.0, 0.0));
+		assert!(!s.hit());
+
+		// the same, in many small steps.
+		let mut b = unit_box(vec3(5.1, 3.0, 5.1));
+		for _ in 0..100 {
+			let s = c.sweep(&b, vec3(0.3, -0.2, 0.0));
+			b = BoundingBox::new(b.min() + s.delta, b.max() + s.delta);
+		}
+		assert!((b.min() - vec3(19.2, 1.0, 5.1)).len() < 1e-3, "{:?}", b);
+	}
+}
@@ -1,6 +1,7 @@
 use super::internal::*;
 
-/// All the voxel (cube) types: sand, lava, snow,...
+/// A voxel (cube) type: sand, lava, snow,...
+/// Its properties are looked up in the `VoxelTypes` registry.
 #[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
 pub struct Voxel(u8);
 
@@ -16,7 +17,6 @@ impl Voxel {
 	pub const STARS: Voxel = Voxel(8);
 	pub const SPONGE: Voxel = Voxel(9);
 	pub const CRYSTAL: Voxel = Voxel(10);
-	pub const MAX: usize = 10;
 
 	/// The index of this Voxel's texture, in VoxelBox.texture_pack.
 	pub fn tex_id(self) -> usize {
@@ -34,20 +34,54 @@ impl Voxel {
 		Self(id)
 	}
 
+	/// Properties of this voxel type (see `VoxelTypes`).
+	#[inline]
+	pub fn properties(self) -> &'static VoxelType {
+		VoxelTypes::get().get_type(self)
+	}
+
 	/// Color of the light emitted by this voxel, if any.
 	pub fn emission(self) -> vec3 {
-		match self {
-			Voxel::LAVA => vec3(1.0, 0.3, 0.0),
-			Voxel::CRYSTAL => vec3(0.1, 0.4, 1.0),
-			_ => vec3(0.0, 0.0, 0.0),
-		}
+		self.properties().emission
 	}
 
 	/// Does this voxel emit light?
 	pub fn is_emissive(self) -> bool {
-		match self {
-			Voxel::LAVA | Voxel::CRYSTAL => true,
-			_ => false,
-		}
+		self.emission() != vec3::ZERO
+	}
+
+	/// Do players bump into this voxel? (EMPTY is not solid).
+	pub fn is_solid(self) -> bool {
+		self.properties().solid
+	}
+
+	/// Can you see through this voxel? (EMPTY is transparent).
+	pub fn is_transparent(self) -> bool {
+		self.properties().transparent
+	}
+
+	/// Does this voxel flow, like lava or water?
+	pub fn is_fluid(self) -> bool {
+		self.properties().fluid
+	}
+
+	/// Can players climb this voxel, like a ladder?
+	pub fn is_climbable(self) -> bool {
+		self.properties().climbable
+	}
+
+	/// Number of hits (e.g. laser shots) it takes to break this voxel.
+	pub fn hit_points(self) -> u8 {
+		self.properties().hardness
+	}
+
+	/// Does the laser melt this voxel into lava?
+	pub fn melts(self) -> bool {
+		self.properties().melts
+	}
+
+	/// How quickly a player standing on this voxel reaches walking speed (1: immediately).
+	pub fn friction(self) -> f32 {
+		self.properties().friction
 	}
 }
@@ -0,0 +1,64 @@
+use super::internal::*;
+
+/// Optional per-voxel state, on top of the voxel type.
+///
+/// Most voxels are in the default state, which is not stored
+/// (see `VoxelBox::state_at`, `VoxelBox::set_state`).
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct VoxelState {
+	/// Facing direction of directional blocks, one of `VoxelState::UP`, `DOWN`, ...
+	pub orientation: u8,
+	/// Hit points lost so far. A voxel breaks when its damage reaches `Voxel::hit_points`.
+	pub damage: u8,
+	/// Free for use by game logic.
+	pub data: u8,
+}
+
+impl VoxelState {
+	pub const UP: u8 = 0; // default
+	pub const DOWN: u8 = 1;
+	pub const LEFT: u8 = 2; // -X
+	pub const RIGHT: u8 = 3; // +X
+	pub const BACK: u8 = 4; // -Z
+	pub const FRONT: u8 = 5; // +Z
+
+	/// Default state, except for the orientation.
+	pub fn oriented(orientation: u8) -> Self {
+		Self { orientation, ..Self::default() }
+	}
+
+	/// The orientation (`UP`, `DOWN`, ...) closest to direction `dir`.
+	pub fn orientation_of(dir: vec3) -> u8 {
+		let (x, y, z) = (dir.x.abs(), dir.y.abs(), dir.z.abs());
+		if y >= x && y >= z {
+			if dir.y >= 0.0 {
+				Self::UP
+			} else {
+				Self::DOWN
+			}
+		} else if x >= z {
+			if dir.x >= 0.0 {
+				Self::RIGHT
+			} else {
+				Self::LEFT
+			}
+		} else if dir.z >= 0.0 {
+			Self::FRONT
+		} else {
+			Self::BACK
+		}
+	}
+
+	/// Does this state need to be stored? (Only non-default states are).
+	pub fn is_default(&self) -> bool {
+		*self == Self::default()
+	}
+
+	/// This state after taking `hits` damage.
+	pub fn damaged(self, hits: u8) -> Self {
+		Self {
+			damage: self.damage.saturating_add(hits),
+			..self
+		}
+	}
+}
@@ -0,0 +1,227 @@
+use super::internal::*;
+use std::fs;
+use std::sync::OnceLock;
+
+/// Properties of a voxel type, as listed in the voxel types data file
+/// (see `voxel_types.json` for the built-in types).
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VoxelType {
+	/// Unique name, recorded in map files.
+	pub name: String,
+
+	/// Texture file name, without extension, in the texture directory.
+	pub texture: String,
+
+	/// Fallback color, used when the texture fails to load.
+	pub color: vec3,
+
+	/// Color of the light emitted by this voxel, if any.
+	#[serde(default = "zero")]
+	pub emission: vec3,
+
+	/// Do players bump into this voxel?
+	#[serde(default = "yes")]
+	pub solid: bool,
+
+	/// Can you see through this voxel?
+	/// Faces behind transparent voxels are drawn, and transparent voxels are drawn blended.
+	#[serde(default)]
+	pub transparent: bool,
+
+	/// Opacity of transparent voxels (0: invisible, 1: opaque).
+	#[serde(default = "one")]
+	pub opacity: f32,
+
+	/// Does this voxel flow (see `FluidSim`)?
+	#[serde(default)]
+	pub fluid: bool,
+
+	/// How quickly a player standing on this voxel reaches walking speed.
+	/// 1: immediately, lower: slippery.
+	#[serde(default = "one")]
+	pub friction: f32,
+
+	/// Number of hits (e.g. laser shots) it takes to break this voxel.
+	#[serde(default = "one_hit")]
+	pub hardness: u8,
+
+	/// Does the laser melt this voxel into lava?
+	#[serde(default)]
+	pub melts: bool,
+
+	/// Can players climb this voxel, like a ladder?
+	#[serde(default)]
+	pub climbable: bool,
+}
+
+/// The registry of all voxel types, indexed by `Voxel::id()`.
+///
+/// Loaded once per process, either from a data file (`VoxelTypes::init`),
+/// or else from the built-in types (`voxel_types.json`).
+pub struct VoxelTypes {
+	types: Vec<VoxelType>, // types[0] is EMPTY
+}
+
+static VOXEL_TYPES: OnceLock<VoxelTypes> = OnceLock::new();
+
+impl VoxelTypes {
+	/// Voxel types that the game logic refers to by constant (`Voxel::SNOW`, ...).
+	/// A data file must list them first, by these names, in this order.
+	const BUILTIN: [(Voxel, &'static str); 10] = [
+		(Voxel::SNOW, "snow"),
+		(Voxel::LAVA, "lava"),
+		(Voxel::PLASMA, "plasma"),
+		(Voxel::WHITESTONE, "whitestone"),
+		(Voxel::GREYSTONE, "greystone"),
+		(Voxel::SAND, "sand"),
+		(Voxel::WHITERSTONE, "whiterstone"),
+		(Voxel::STARS, "stars"),
+		(Voxel::SPONGE, "sponge"),
+		(Voxel::CRYSTAL, "crystal"),
+	];
+
+	/// The registry in use.
+	/// Initializes the built-in types if `init` was not called before.
+	pub fn get() -> &'static Self {
+		VOXEL_TYPES.get_or_init(Self::builtin)
+	}
+
+	/// Use the voxel types listed in a JSON data file (see `voxel_types.json`).
+	/// Must be called before the registry is first used.
+	///
+	/// Clients and servers must use the same voxel types.
+	pub fn init(file: &Path) -> Result<()> {
+		let data = fs::read_to_string(file)?;
+		let types = Self::parse(&data).map_err(|e| error(format!("load {}: {}", file.to_string_lossy(), e)))?;
+		VOXEL_TYPES.set(types).map_err(|_| error("voxel types: already initialized"))
+	}
+
+	fn builtin() -> Self {
+		Self::parse(include_str!("voxel_types.json")).expect("built-in voxel types")
+	}
+
+	fn parse(json: &str) -> Result<Self> {
+		let empty = VoxelType {
+			name: "empty".into(),
+			texture: String::new(),
+			color: vec3::ZERO,
+			emission: vec3::ZERO,
+			solid: false,
+			transparent: true,
+			opacity: 0.0,
+			fluid: false,
+			friction: 1.0,
+			hardness: 1,
+			melts: false,
+			climbable: false,
+		};
+		let mut types = vec![empty];
+		types.extend(serde_json::from_str::<Vec<VoxelType>>(json)?);
+
+		if types.len() > 256 {
+			return err(format!("voxel types: too many types: {} (max 255)", types.len() - 1));
+		}
+		for (i, t) in types.iter().enumerate() {
+			if types[..i].iter().any(|other| other.name == t.name) {
+				return err(format!("voxel types: duplicate name: {}", t.name));
+			}
+			if t.name.is_empty() || t.name.len() > 255 {
+				return err(format!("voxel types: #{}: name must be 1-255 bytes long", i));
+			}
+			if t.hardness == 0 {
+				return err(format!("voxel types: {}: hardness must be at least 1", t.name));
+			}
+		}
+		for &(voxel, name) in &Self::BUILTIN {
+			match types.get(voxel.id() as usize) {
+				Some(t) if t.name == name => (),
+				_ => return err(format!("voxel types: type #{} must be {}", voxel.id(), name)),
+			}
+		}
+		Ok(Self { types })
+	}
+
+	/// Properties of a voxel type.
+	/// Unknown voxels (never produced by a decoded map) have the properties of EMPTY.
+	#[inline]
+	pub fn get_type(&self, voxel: Voxel) -> &VoxelType {
+		self.types.get(voxel.id() as usize).unwrap_or(&self.types[0])
+	}
+
+	/// The voxel type with given name, if any.
+	pub fn by_name(&self, name: &str) -> Option<Voxel> {
+		self.types.iter().position(|t| t.name == name).map(|i| Voxel::from(i as u8))
+	}
+
+	/// Number of voxel types, not counting EMPTY.
+	pub fn count(&self) -> usize {
+		self.types.len() - 1
+	}
+
+	/// All non-empty voxel types, in id order.
+	pub fn iter(&self) -> impl Iterator<Item = (Voxel, &VoxelType)> {
+		self.types.iter().enumerate().skip(1).map(|(i, t)| (Voxel::from(i as u8), t))
+	}
+
+	/// Names of all non-empty voxel types, in id order.
+	/// Recorded in map files so that they can be loaded with a different registry.
+	pub fn palette(&self) -> Vec<String> {
+		self.iter().map(|(_, t)| t.name.clone()).collect()
+	}
+}
+
+fn zero() -> vec3 {
+	vec3::ZERO
+}
+
+fn yes() -> bool {
+	true
+}
+
+fn one() -> f32 {
+	1.0
+}
+
+fn one_hit() -> u8 {
+	1
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn parse() {
+		let types = VoxelTypes::builtin();
+		assert_eq!(types.count(), 15);
+		assert_eq!(types.by_name("crystal"), Some(Voxel::CRYSTAL));
+		assert_eq!(types.get_type(Voxel::LAVA).emission, vec3(1.0, 0.3, 0.0));
+		assert!(types.get_type(Voxel::SNOW).solid);
+		assert!(!types.get_type(Voxel::EMPTY).solid);
+		let water = types.by_name("water").unwrap();
+		assert!(!types.get_type(water).solid);
+		assert!(types.get_type(water).transparent);
+		assert!(types.get_type(types.by_name("ladder").unwrap()).climbable);
+		assert!(!types.get_type(water).climbable);
+
+		let custom = r#"[
+			{"name": "snow", "texture": "snow", "color": {"x": 1.0, "y": 1.0, "z": 1.0}},
+			{"name": "lava", "texture": "lava", "color": {"x": 1.0, "y": 0.0, "z": 0.0}}
+		]"#;
+		assert!(VoxelTypes::parse(custom).is_err()); // missing built-in types
+
+		let json = include_str!("voxel_types.json");
+		let extended = json.replacen('[', r#"[{"name": "slush", "texture": "slush", "color": {"x": 0.8, "y": 0.9, "z": 1.0}, "friction": 0.1},"#, 1);
+		assert!(VoxelTypes::parse(&extended).is_err()); // built-in types must come first
+
+		let extended = json.replace("\n]", r#", {"name": "slush", "texture": "slush", "color": {"x": 0.8, "y": 0.9, "z": 1.0}, "friction": 0.1}]"#);
+		let types = VoxelTypes::parse(&extended).unwrap();
+		let slush = types.by_name("slush").unwrap();
+		assert_eq!(slush.id(), 16);
+		assert_eq!(types.get_type(slush).friction, 0.1);
+		assert_eq!(types.get_type(slush).hardness, 1); // default
+
+		let duplicate = json.replace("\n]", r#", {"name": "snow", "texture": "x", "color": {"x": 0.0, "y": 0.0, "z": 0.0}}]"#);
+		assert!(VoxelTypes::parse(&duplicate).is_err());
+	}
+}
@@ -1,86 +1,227 @@
 use super::internal::*;
-use std::cell::RefCell;
+use std::cell::{Cell, Ref, RefCell};
+#[cfg(any(feature = "gl", test))]
+use std::cmp::Ordering;
+use std::sync::Arc;
 
 /// Sparse 3D array of Voxels.
+///
+/// Voxels are stored in cubic Chunks, which are only allocated
+/// when they contain at least one non-empty voxel.
+/// So memory use, edits and meshing are proportional to the region of interest.
+///
+/// Voxels may have additional state (`VoxelState`, e.g. damage),
+/// which is stored sparsely as well: only for voxels not in the default state.
+///
+/// Voxel faces are lit by a propagated `LightField`, computed when first drawn
+/// and updated incrementally on edits, and by the sun (see `Shadows`).
+///
+/// Chunk meshes are built in the background (see `MeshPool`), from a `VoxelSnapshot`,
+/// and uploaded when drawn. Until then, a modified chunk is drawn with its previous mesh.
+/// Distant chunks are meshed at a lower level of detail (see `Lod`).
+///
+/// A VoxelBox is either bounded (voxels can only be set inside fixed bounds),
+/// or unbounded (voxels can be set anywhere, including at negative indices).
+#[derive(Clone)]
 pub struct VoxelBox {
-	world_size: uvec3,
-	chunks: Vec<Chunk>, // 2D array of chunks in XZ (horizontal)
-	chunks_dim: uvec2,  // size of chunks array
+	limits: Option<VoxelBounds>,        // voxels can only be set inside these bounds, if any.
+	extent: VoxelBounds,                // bounds of all voxels set so far (does not shrink on removal).
+	chunks: HashMap<ivec3, Chunk>,      // chunk position (index / Chunk::SIZE) -> chunk
+	light: RefCell<Option<LightField>>, // computed on demand (see `light`), servers never need it.
+	sun_dir: Cell<vec3>,                // sun direction the meshes were lit with.
+	mesh_version: Cell<u64>,            // last version given to a chunk (see `Chunk`).
+	#[cfg(feature = "gl")]
+	mesh_results: MeshResults, // meshes built in the background, to be uploaded.
 }
 
-/// A VoxelBox Chunk can grow/change independently of others,
+/// A VoxelBox Chunk can change independently of others,
 /// and has its own Mesh.
+///
+/// Meshes are versioned: modifying a chunk gives it a new version, newer than all before.
+/// A mesh is re-built if the chunk's version is newer than the one last queued,
+/// and the result is uploaded if newer than the mesh in use.
+/// Changing the level of detail (see `Lod`) gives the chunk a new version too.
 pub struct Chunk {
-	voxels: Vec<Voxel>,
-	// A cache for this chunk's OpenGL Vertex Array.
-	vaos: RefCell<Option<Vec<(usize, Mesh)>>>,
+	voxels: Arc<Vec<Voxel>>,          // Chunk::SIZE^3 voxels, X fastest, then Z, then Y. Shared with snapshots until modified.
+	num_solid: u32,                   // number of non-empty voxels.
+	states: HashMap<u16, VoxelState>, // non-default voxel states, by internal index.
+	version: Cell<u64>,               // version of the voxels (and lighting), for meshing.
+	#[cfg(feature = "gl")]
+	mesh: ChunkMesh,
+}
+
+// A cache for a Chunk's OpenGL Vertex Arrays.
+#[cfg(feature = "gl")]
+struct ChunkMesh {
+	vaos: RefCell<Option<ChunkMeshes>>,
+	queued: Cell<u64>, // version last sent to the MeshPool.
+	meshed: Cell<u64>, // version of the `vaos`.
+	lod: Cell<Lod>,    // level of detail to mesh at, depending on the distance to the camera.
+}
+
+// A Chunk's meshes, one per voxel type.
+// Transparent voxels are drawn in a separate pass, after all opaque voxels.
+#[cfg(feature = "gl")]
+struct ChunkMeshes {
+	opaque: Vec<(Voxel, Mesh)>,
+	transparent: Vec<(Voxel, Mesh)>,
 }
 
 impl VoxelBox {
-	/// New empty world with given size.
-	/// X, Z must be a multiple of Chunk size.
+	/// New empty, bounded world with given size.
 	pub fn new(world_size: uvec3) -> Self {
 		assert!(world_size.x != 0);
 		assert!(world_size.y != 0);
 		assert!(world_size.z != 0);
-		assert!(world_size.x % Chunk::XZSIZE == 0);
-		assert!(world_size.z % Chunk::XZSIZE == 0);
 
-		let xchunks = world_size.x >> Chunk::POW;
-		let zchunks = world_size.z >> Chunk::POW;
-		let num_chunks = xchunks * zchunks;
 		VoxelBox {
-			world_size,
-			chunks_dim: uvec2(xchunks, zchunks),
-			chunks: zeros(num_chunks as usize),
+			limits: Some(VoxelBounds::with_size(world_size)),
+			extent: VoxelBounds::EMPTY,
+			chunks: HashMap::default(),
+			light: RefCell::new(None),
+			sun_dir: Cell::new(vec3(0.0, 1.0, 0.0)),
+			mesh_version: Cell::new(1),
+			#[cfg(feature = "gl")]
+			mesh_results: MeshResults::new(),
+		}
+	}
+
+	/// New empty world without bounds.
+	pub fn unbounded() -> Self {
+		VoxelBox {
+			limits: None,
+			extent: VoxelBounds::EMPTY,
+			chunks: HashMap::default(),
+			light: RefCell::new(None),
+			sun_dir: Cell::new(vec3(0.0, 1.0, 0.0)),
+			mesh_version: Cell::new(1),
+			#[cfg(feature = "gl")]
+			mesh_results: MeshResults::new(),
 		}
 	}
 
+	pub fn is_unbounded(&self) -> bool {
+		self.limits.is_none()
+	}
+
 	/// Return the Voxel at given position.
 	/// Out-of-bounds access is safe, returns Voxel::EMPTY.
 	pub fn at(&self, index: ivec3) -> Voxel {
 		if !self.valid_index(index) {
-			Voxel::EMPTY
-		} else {
-			let (chnk, int) = self.index_internal(index);
-			self.chunk(chnk).at_internal(int)
+			return Voxel::EMPTY;
+		}
+		let (chnk, int) = Self::index_internal(index);
+		match self.chunks.get(&chnk) {
+			None => Voxel::EMPTY,
+			Some(chunk) => chunk.at_internal(int),
+		}
+	}
+
+	/// State of the Voxel at given position.
+	/// Default for empty and out-of-bounds voxels.
+	pub fn state_at(&self, index: ivec3) -> VoxelState {
+		if !self.valid_index(index) {
+			return VoxelState::default();
+		}
+		let (chnk, int) = Self::index_internal(index);
+		match self.chunks.get(&chnk) {
+			None => VoxelState::default(),
+			Some(chunk) => chunk.state_internal(int),
+		}
+	}
+
+	/// Set the state of the (non-empty) Voxel at given position.
+	/// Empty and out-of-bounds voxels are ignored, they cannot have state.
+	pub fn set_state(&mut self, index: ivec3, state: VoxelState) {
+		if !self.valid_index(index) {
+			return;
+		}
+		let (chnk, int) = Self::index_internal(index);
+		if let Some(chunk) = self.chunks.get_mut(&chnk) {
+			chunk.set_state_internal(int, state)
 		}
 	}
 
-	/// Set the Voxel at given position.
+	/// All voxels in a non-default state, in no particular order.
+	pub fn states(&self) -> impl Iterator<Item = (ivec3, VoxelState)> + '_ {
+		self.chunks.iter().flat_map(|(&chnk, chunk)| {
+			let world_offset = chnk * (Chunk::SIZE as i32);
+			chunk.states.iter().map(move |(&i, &state)| (world_offset + Chunk::internal_index(i).as_ivec(), state))
+		})
+	}
+
+	/// Set the Voxel at given position, in the default state.
 	/// Out-of-bounds positions are ignored.
 	pub fn set(&mut self, index: ivec3, v: Voxel) {
 		if !self.valid_index(index) {
 			return;
 		}
-		let (chnk, int) = self.index_internal(index);
-		self.chunk_mut(chnk).set_internal(int, v);
-
-		// Invalidate this chunk's vao and those of it's neighbors
-		// where the affected voxel was in or directly adjacent.
-		// May invalidate the same chunk multiple times.
-		for &dx in &[-1, 0, 1] {
-			for &dy in &[-1, 0, 1] {
-				let neigh_idx = index + ivec3(dx, 0, dy);
-				if !self.valid_index(neigh_idx) {
-					continue;
+		let (chnk, int) = Self::index_internal(index);
+		if v == Voxel::EMPTY {
+			match self.chunks.get_mut(&chnk) {
+				None => return, // already empty
+				Some(chunk) => {
+					chunk.set_internal(int, v);
+					if chunk.num_solid == 0 {
+						self.chunks.remove(&chnk);
+					}
+				}
+			}
+		} else {
+			self.chunks.entry(chnk).or_default().set_internal(int, v);
+			self.extent.add(index);
+		}
+
+		// Invalidate the vaos of all chunks with faces affected by this voxel:
+		// the voxel's neighbors (culling), and faces lit differently now.
+		match self.light.take() {
+			Some(mut light) if light.covers(self.bounds()) => {
+				for chnk in light.update(self, index) {
+					self.invalidate_vao(chnk);
+				}
+				*self.light.get_mut() = Some(light);
+			}
+			Some(_) => self.invalidate_vaos(), // grown out of the light field: re-compute all.
+			None => (),
+		}
+		// Chunks in the voxel's shadow (see `Shadows`).
+		let mut shadowed = HashSet::default();
+		for cell in Shadows::shadow_line(index, self.sun_dir.get(), self.bounds()) {
+			let (min, _) = Self::index_internal(cell - ivec3(1, 1, 1));
+			let (max, _) = Self::index_internal(cell + ivec3(1, 1, 1));
+			for cz in min.z..=max.z {
+				for cy in min.y..=max.y {
+					for cx in min.x..=max.x {
+						shadowed.insert(ivec3(cx, cy, cz));
+					}
+				}
+			}
+		}
+		for chnk in shadowed {
+			self.invalidate_vao(chnk);
+		}
+		let (min, _) = Self::index_internal(index - ivec3(1, 1, 1));
+		let (max, _) = Self::index_internal(index + ivec3(1, 1, 1));
+		for cz in min.z..=max.z {
+			for cy in min.y..=max.y {
+				for cx in min.x..=max.x {
+					self.invalidate_vao(ivec3(cx, cy, cz));
 				}
-				let (neigh_chnk, _) = self.index_internal(neigh_idx);
-				self.invalidate_vao(neigh_chnk);
 			}
 		}
 	}
 
-	/// Tests if a bounding box overlaps with ("bumps into") any non-empty voxel.
+	/// Tests if a bounding box overlaps with ("bumps into") any solid voxel.
+	/// Merely touching a voxel (see `sweep`) does not count.
 	pub fn bumps(&self, bounds: &BoundingBox) -> bool {
-		let imin = bounds.min().to_ivec();
-		let imax = bounds.max().to_ivec();
+		let imin = bounds.min().floor_ivec();
+		let imax = (bounds.max() - Self::SWEEP_EPS * vec3::ONES).floor_ivec();
 
 		for iz in imin.z..(imax.z + 1) {
 			for iy in imin.y..(imax.y + 1) {
 				for ix in imin.x..(imax.x + 1) {
 					let pos = ivec3(ix, iy, iz);
-					if self.at(pos) != Voxel::EMPTY {
+					if self.at(pos).is_solid() {
 						return true;
 					}
 				}
@@ -89,231 +230,98 @@ impl VoxelBox {
 		false
 	}
 
-	fn update_vao(&self, chnk: uvec2) {
-		//println!("Chunks::update_vao {}", chnk);
-		let world_offset = ivec3(chnk.x as i32, 0, chnk.y as i32) * (Chunk::XZSIZE as i32);
-
-		let mut b: Vec<MeshBuffer> = zeros(Voxel::MAX);
-
-		let neighbors = [
-			ivec3(-1, 0, 0), // left
-			ivec3(1, 0, 0),  // right
-			ivec3(0, -1, 0), // bottom
-			ivec3(0, 1, 0),  // top
-			ivec3(0, 0, -1), // back
-			ivec3(0, 0, 1),  // front
-		];
-
-		let size = self.chunk(chnk).size();
-		for iz in 0..size.z {
-			for iy in 0..size.y {
-				for ix in 0..size.x {
-					// voxel coordinates inside chunk
-					let vox_int = uvec3(ix, iy, iz);
-					let block = self.chunk(chnk).at_internal(vox_int);
-
-					// global voxel coordinates
-					let vox_world = world_offset + uvec3(ix, iy, iz).as_ivec();
-					let cube_corner = vox_world.map(|v| v as f32);
-
-					if block != Voxel::EMPTY {
-						let cube_faces = cube_at(cube_corner);
-
-						for (i, &d) in neighbors.iter().enumerate() {
-							let neigh = vox_world + d;
-							if self.at(neigh) == Voxel::EMPTY {
-								let mut face = self.with_light(i, &cube_faces[i]);
-								if block.is_emissive() {
-									face = Self::with_emission(&face, block.emission())
-								}
-								b[block.tex_id()].push_all(&(face.triangle_vertices()))
-							}
-						}
-					}
-				}
-			}
-		}
-
-		let mut meshes = Vec::new();
-		for (i, builder) in b.iter().enumerate() {
-			if builder.len() != 0 {
-				let m = builder.build();
-				meshes.push((i, m));
-			}
-		}
-		*(self.chunk(chnk).vaos.borrow_mut()) = Some(meshes);
-	}
-
-	fn with_emission(face: &Quad, emission: vec3) -> Quad {
-		let mut result = face.clone();
-		for i in 0..4 {
-			result.set_vertex(
-				i,
-				Vertex {
-					attrib: 0.5 * face.vertex(i).attrib + 0.5 * emission,
-					..face.vertex(i).clone()
-				},
-			)
-		}
-		result
-	}
-
-	fn with_light(&self, face_dir: usize, face: &Quad) -> Quad {
-		let mut face = face.clone();
-
-		let n = face.normal();
-		let t1 = face.tangent1();
-		let t2 = face.tangent2();
-		for i in 0..4 {
-			let attrib = self.calc_vertex_light(face_dir, face.vertex(i).pos, n, t1, t2);
-			face.set_vertex(i, Vertex { attrib, ..face.vertex(i).clone() })
-		}
-		face
-	}
-
-	fn calc_vertex_light(&self, face_dir: usize, pos: vec3, n: vec3, t1: vec3, t2: vec3) -> vec3 {
-		let probe_starts = [
-			pos + 0.5 * (n + t1 + t2), //
-			pos + 0.5 * (n - t1 + t2), //
-			pos + 0.5 * (n + t1 - t2), //
-			pos + 0.5 * (n - t1 - t2), //
-		];
-
-		let face_light = [
-			0.65, // left
-			0.65, // right
-			0.15, // bottom
-			1.00, // top
-			0.45, // back
-			0.45, //front
-		];
-
-		let mut accum = vec3::ZERO;
-		let mul = face_light[face_dir];
-
-		match face_dir {
-			3 => {
-				// top
-				for &start in &probe_starts {
-					accum += self.light_v_probe(start, mul)
-				}
-			}
-			2 => {
-				//bottom
-				for &start in &probe_starts {
-					accum += self.light_l_probe(start, mul)
-				}
-			}
-			_ => {
-				// vertical
-				for &start in &probe_starts[0..2] {
-					// top vertices: probe up
-					accum += self.light_v_probe(start, mul)
-				}
-				for &start in &probe_starts[2..4] {
-					// bottom vertices: probe local
-					accum += self.light_l_probe(start, mul)
-				}
-			}
+	#[cfg(feature = "gl")]
+	fn update_vao(&self, chnk: ivec3) {
+		trace!("update_vao {}", chnk);
+		if let Some(chunk) = self.chunks.get(&chnk) {
+			let version = chunk.version.get();
+			chunk.upload(version, &self.build_meshes(chnk, chunk.mesh.lod.get(), Meshing::get()));
+			chunk.mesh.queued.set(version);
 		}
+	}
 
-		0.25 * accum
+	/// The vertices of a chunk's visible faces, one buffer per voxel type (by tex_id).
+	/// Built in place, see `MeshPool` for building in the background.
+	#[cfg(any(feature = "gl", test))]
+	fn build_meshes(&self, chnk: ivec3, lod: Lod, meshing: Meshing) -> Vec<MeshBuffer> {
+		self.snapshot().build_meshes(chnk, lod, meshing)
 	}
 
-	fn light_v_probe(&self, start: vec3, mul: f32) -> vec3 {
-		let start = start.map(|v| v as i32);
+	/// A read-only copy of the voxels and their lighting, for meshing.
+	/// Computes the light field if needed.
+	pub fn snapshot(&self) -> VoxelSnapshot {
+		let chunks = self.chunks.iter().map(|(&chnk, chunk)| (chnk, chunk.voxels.clone())).collect();
+		VoxelSnapshot::new(self.bounds(), chunks, self.light().clone(), self.sun_dir.get())
+	}
 
-		const SHADOW_H: i32 = 10;
-		let mut ceil_dist = 0;
-		for i in 0..SHADOW_H {
-			let up = start + ivec3(0, i, 0);
-			if self.at(up) == Voxel::EMPTY {
-				ceil_dist += 1;
-			} else {
-				break;
-			}
-		}
-		let ambient_light = ((ceil_dist) as f32 / (SHADOW_H as f32)) * vec3::ONES;
-		if self.at(start).is_emissive() {
-			0.25 * mul * ambient_light + (4.0 * 0.75) * self.at(start).emission()
-		} else {
-			mul * ambient_light
+	/// The light field, computed if needed.
+	fn light(&self) -> Ref<'_, LightField> {
+		if self.light.borrow().is_none() {
+			debug!("computing light field for {}", self.bounds());
+			*self.light.borrow_mut() = Some(LightField::new(self));
 		}
+		Ref::map(self.light.borrow(), |light| light.as_ref().unwrap())
 	}
 
-	fn light_l_probe(&self, start: vec3, mul: f32) -> vec3 {
-		let start = start.map(|v| v as i32);
-
-		let ambient_light = if self.at(start) == Voxel::EMPTY { vec3::ONES } else { vec3::ZERO };
-		if self.at(start).is_emissive() {
-			0.25 * mul * ambient_light + (4.0 * 0.75) * self.at(start).emission()
-		} else {
-			mul * ambient_light
+	// Mark a chunk's meshes out of date (they are kept until re-built).
+	fn invalidate_vao(&self, chnk: ivec3) {
+		if let Some(chunk) = self.chunks.get(&chnk) {
+			trace!("invalidate_vao {}", chnk);
+			chunk.version.set(self.next_mesh_version());
 		}
 	}
 
-	fn invalidate_vao(&mut self, chnk: uvec2) {
-		//println!("Chunks::invalidate_vao {}", chnk);
-		*(self.chunk_mut(chnk).vaos.borrow_mut()) = None;
+	fn invalidate_vaos(&self) {
+		let version = self.next_mesh_version();
+		for chunk in self.chunks.values() {
+			chunk.version.set(version);
+		}
 	}
 
-	fn ensure_vao(&self, chnk: uvec2) {
-		if self.chunk(chnk).vaos.borrow().is_none() {
-			self.update_vao(chnk)
-		}
+	fn next_mesh_version(&self) -> u64 {
+		self.mesh_version.set(self.mesh_version.get() + 1);
+		self.mesh_version.get()
 	}
 
+	/// Build all out-of-date meshes right away, instead of in the background.
+	#[cfg(feature = "gl")]
 	pub fn ensure_vaos(&mut self) {
-		for iz in 0..self.chunks_dim.y {
-			for ix in 0..self.chunks_dim.x {
-				self.ensure_vao(uvec2(ix, iz));
+		for (&chnk, chunk) in &self.chunks {
+			if chunk.mesh.meshed.get() < chunk.version.get() {
+				self.update_vao(chnk);
 			}
 		}
 	}
 
+	/// Chunk position and position inside the chunk for a voxel index.
+	/// (Also for negative indices: rounds down).
 	#[inline]
-	fn index_internal(&self, index: ivec3) -> (uvec2, uvec3) {
-		debug_assert!(self.valid_index(index));
-		let chnkx = (index.x as u32) >> Chunk::POW;
-		let chnkz = (index.z as u32) >> Chunk::POW;
-		let intx = (index.x as u32) & Chunk::MASK;
-		let intz = (index.z as u32) & Chunk::MASK;
-		(uvec2(chnkx, chnkz), uvec3(intx, index.y as u32, intz))
-	}
-
-	fn chunk(&self, xz: uvec2) -> &Chunk {
-		let idx = self.chunk_index(xz);
-		&self.chunks[idx]
-	}
-
-	fn chunk_mut(&mut self, xz: uvec2) -> &mut Chunk {
-		let idx = self.chunk_index(xz);
-		&mut self.chunks[idx as usize]
-	}
-
-	fn chunk_index(&self, xz: uvec2) -> usize {
-		debug_assert!(self.valid_chunk_index(xz.as_ivec()));
-		let stride = self.world_size.x >> Chunk::POW;
-		let idx = xz.y * stride + xz.x;
-		idx as usize
+	pub(super) fn index_internal(index: ivec3) -> (ivec3, uvec3) {
+		let chnk = index.map(|v| v >> Chunk::POW);
+		let int = index.map(|v| (v as u32) & Chunk::MASK);
+		(chnk, int)
 	}
 
 	fn valid_index(&self, index: ivec3) -> bool {
-		!(index.x < 0 || index.y < 0 || index.z < 0 || index.x >= self.world_size.x as i32 || index.y >= self.world_size.y as i32 || index.z >= self.world_size.z as i32)
-	}
-
-	fn valid_chunk_index(&self, xz: ivec2) -> bool {
-		xz.x >= 0 && xz.y >= 0 && xz.x < self.chunks_dim.x as i32 && xz.y < self.chunks_dim.y as i32
+		match &self.limits {
+			Some(limits) => limits.contains(index),
+			None => true,
+		}
 	}
 
-	pub fn world_size(&self) -> uvec3 {
-		self.world_size
+	/// The world's bounds: fixed for bounded worlds,
+	/// the bounding box of all voxels set so far for unbounded worlds.
+	pub fn bounds(&self) -> VoxelBounds {
+		match self.limits {
+			Some(limits) => limits,
+			None => self.extent,
+		}
 	}
 
 	// How high is the map at position (ix, iz)?
 	// I.e.: y-index+1 of the highest non-empty voxel.
 	fn height_at(&self, ix: usize, iz: usize) -> usize {
-		let max = self.world_size().y as usize;
+		let max = self.bounds().max.y as usize;
 		let mut height = 0;
 
 		for iy in 0..max {
@@ -325,86 +333,216 @@ impl VoxelBox {
 		height + 1
 	}
 
-	pub fn draw(&self, ctx: &GLContext, view_pos: vec3, view_dist: f32) {
+	/// Draw all chunks within `view_dist`, lit by the sun shining from `sun_dir`,
+	/// and inside the camera `frustum`: first the opaque voxels, from front to back (by chunk),
+	/// so that hidden fragments fail the depth test early, then the transparent ones, from back to front.
+	///
+	/// Out-of-date meshes of chunks within `view_dist` are re-built in the background, nearest first.
+	#[cfg(feature = "gl")]
+	pub fn draw(&self, ctx: &GLContext, frustum: &Frustum, view_pos: vec3, view_dist: f32, sun_dir: vec3) {
+		if sun_dir != self.sun_dir.get() {
+			self.sun_dir.set(sun_dir);
+			self.invalidate_vaos();
+		}
+		self.update_meshes(view_pos, view_dist);
+
 		ctx.textures().bind_voxels();
 		ctx.set_depth_test(true);
 		ctx.set_cull_face(true);
 		let shader = ctx.shaders().bind_voxel_shader();
 
-		let view_pos = vec2(view_pos.x, view_pos.z);
-		for iz in 0..self.chunks_dim.y {
-			for ix in 0..self.chunks_dim.x {
-				let chnk = uvec2(ix, iz);
-
-				let chnk_pos = vec2(chnk.x as f32 + 0.5, chnk.y as f32 + 0.5) * (Chunk::XZSIZE as f32);
-				if (chnk_pos - view_pos).len() > view_dist {
-					continue;
+		let mut transparent = Vec::new(); // (distance, chunk) with transparent voxels
+		for (dist, chnk) in self.visible_chunks(frustum, view_pos, view_dist) {
+			if let Some(meshes) = self.chunks[&chnk].mesh.vaos.borrow().as_ref() {
+				for (voxel, mesh) in &meshes.opaque {
+					shader.set_texture(voxel.tex_id());
+					mesh.bind_and_draw();
 				}
-
-				if self.chunk(chnk).vaos.borrow().is_none() {
-					self.ensure_vao(chnk)
+				if !meshes.transparent.is_empty() {
+					transparent.push((dist, chnk));
 				}
+			}
+		}
 
-				let chunk = self.chunk(chnk);
-				let meshes = chunk.vaos.borrow();
-				let meshes = meshes.as_ref();
-				if let Some(meshes) = meshes {
-					for (texid, mesh) in meshes {
-						shader.set_texture(*texid);
-						mesh.bind_and_draw();
-					}
+		// Transparent voxels must not hide what is behind them:
+		// draw back to front, without updating the depth buffer.
+		transparent.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+		ctx.set_depth_write(false);
+		for (_, chnk) in transparent {
+			if let Some(meshes) = self.chunks[&chnk].mesh.vaos.borrow().as_ref() {
+				for (voxel, mesh) in &meshes.transparent {
+					shader.set_texture(voxel.tex_id());
+					shader.set_alpha(voxel.properties().opacity);
+					mesh.bind_and_draw();
 				}
 			}
 		}
+		ctx.set_depth_write(true);
+		shader.set_alpha(1.0);
+	}
+
+	/// The chunks that may be visible: inside the frustum and within `view_dist` (the fog distance).
+	/// As (distance, chunk position), nearest first.
+	#[cfg(any(feature = "gl", test))]
+	fn visible_chunks(&self, frustum: &Frustum, view_pos: vec3, view_dist: f32) -> Vec<(f32, ivec3)> {
+		let mut visible = self
+			.chunks
+			.keys()
+			.map(|&chnk| (Chunk::distance(chnk, view_pos), chnk))
+			.filter(|&(dist, chnk)| dist <= view_dist && frustum.intersects_box(Chunk::min(chnk), Chunk::max(chnk)))
+			.collect::<Vec<_>>();
+		visible.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+		visible
+	}
+
+	// Upload the meshes built in the background since the last frame,
+	// and queue the chunks within `view_dist` that are out of date,
+	// or need a different level of detail at their distance from `view_pos`.
+	#[cfg(feature = "gl")]
+	fn update_meshes(&self, view_pos: vec3, view_dist: f32) {
+		for result in self.mesh_results.ready() {
+			if let Some(chunk) = self.chunks.get(&result.chnk) {
+				chunk.upload(result.version, &result.buffers);
+			}
+		}
+
+		let pool = MeshPool::get();
+		pool.set_camera(view_pos);
+		let mut snapshot = None; // shared by all of this frame's jobs.
+		for (&chnk, chunk) in &self.chunks {
+			let dist = Chunk::distance(chnk, view_pos);
+			if dist > view_dist {
+				continue;
+			}
+			let lod = Lod::for_distance(dist, chunk.mesh.lod.get());
+			if lod != chunk.mesh.lod.get() {
+				chunk.mesh.lod.set(lod);
+				chunk.version.set(self.next_mesh_version());
+			}
+			if chunk.mesh.queued.get() >= chunk.version.get() {
+				continue;
+			}
+			let snapshot = snapshot.get_or_insert_with(|| Arc::new(self.snapshot()));
+			self.mesh_results.submit(chnk, chunk.version.get(), lod, snapshot.clone());
+			chunk.mesh.queued.set(chunk.version.get());
+		}
 	}
 }
 
 impl Chunk {
-	const POW: u32 = 4; // Chunks will be 2^POW by 2^POW voxels in X,Z.
-	const XZSIZE: u32 = 1 << Chunk::POW; // 0b10000
-	const MASK: u32 = (1 << Chunk::POW) - 1; // 0b01111
+	pub(super) const POW: u32 = 4; // Chunks will be 2^POW voxels in X, Y, Z.
+	pub(super) const SIZE: u32 = 1 << Chunk::POW; // 0b10000
+	pub(super) const MASK: u32 = (1 << Chunk::POW) - 1; // 0b01111
 
 	pub fn new() -> Self {
 		Self {
-			voxels: Vec::new(),
-			vaos: RefCell::new(None),
+			voxels: Arc::new(vec![Voxel::EMPTY; (Self::SIZE * Self::SIZE * Self::SIZE) as usize]),
+			num_solid: 0,
+			states: HashMap::default(),
+			version: Cell::new(1),
+			#[cfg(feature = "gl")]
+			mesh: ChunkMesh::default(),
 		}
 	}
 
+	/// Center of the chunk at given chunk position, in world coordinates.
+	pub fn center(chnk: ivec3) -> vec3 {
+		(chnk.to_vec() + vec3(0.5, 0.5, 0.5)) * (Chunk::SIZE as f32)
+	}
+
+	/// Bounding box of the chunk at given chunk position, in world coordinates.
+	pub fn min(chnk: ivec3) -> vec3 {
+		chnk.to_vec() * (Chunk::SIZE as f32)
+	}
+
+	pub fn max(chnk: ivec3) -> vec3 {
+		Self::min(chnk + ivec3(1, 1, 1))
+	}
+
+	/// Distance from a point to the nearest point of a chunk (0 inside the chunk).
+	pub fn distance(chnk: ivec3, pos: vec3) -> f32 {
+		let (min, max) = (Self::min(chnk), Self::max(chnk));
+		let nearest = vec3(pos.x.clamp(min.x, max.x), pos.y.clamp(min.y, max.y), pos.z.clamp(min.z, max.z));
+		(pos - nearest).len()
+	}
+
 	pub fn at_internal(&self, idx: uvec3) -> Voxel {
-		//println!("Chunk::at_internal {}", idx);
-		let (ix, iy, iz) = (idx.x, idx.y, idx.z);
-		let i = Self::index_internal(ix, iy, iz);
-		if i >= self.voxels.len() {
-			Voxel::EMPTY
+		self.voxels[Self::index_internal(idx)]
+	}
+
+	pub fn set_internal(&mut self, idx: uvec3, v: Voxel) {
+		let i = Self::index_internal(idx);
+		match (self.voxels[i] == Voxel::EMPTY, v == Voxel::EMPTY) {
+			(true, false) => self.num_solid += 1,
+			(false, true) => self.num_solid -= 1,
+			_ => (),
+		}
+		Arc::make_mut(&mut self.voxels)[i] = v;
+		self.states.remove(&(i as u16));
+	}
+
+	pub fn state_internal(&self, idx: uvec3) -> VoxelState {
+		let i = Self::index_internal(idx) as u16;
+		self.states.get(&i).copied().unwrap_or_default()
+	}
+
+	pub fn set_state_internal(&mut self, idx: uvec3, state: VoxelState) {
+		let i = Self::index_internal(idx);
+		if state.is_default() || self.voxels[i] == Voxel::EMPTY {
+			self.states.remove(&(i as u16));
 		} else {
-			self.voxels[i]
+			self.states.insert(i as u16, state);
 		}
 	}
 
-	pub fn set_internal(&mut self, idx: uvec3, v: Voxel) {
+	pub(super) fn index_internal(idx: uvec3) -> usize {
 		let (ix, iy, iz) = idx.into();
-		let i = Self::index_internal(ix, iy, iz);
-		while i >= self.voxels.len() {
-			self.voxels.push(Voxel::EMPTY)
-		}
-		self.voxels[i] = v
+		debug_assert!(ix < Self::SIZE);
+		debug_assert!(iy < Self::SIZE);
+		debug_assert!(iz < Self::SIZE);
+		(iy << (2 * Self::POW) | iz << Self::POW | ix) as usize
 	}
 
-	fn index_internal(ix: u32, iy: u32, iz: u32) -> usize {
-		debug_assert!(ix < Self::XZSIZE);
-		debug_assert!(iz < Self::XZSIZE);
-		let idx = (iy << (2 * Self::POW) | iz << Self::POW | ix) as usize;
-		//println!("Chunk::index_internal {}, {}, {} -> {}", ix, iy, iz, idx);
-		idx
+	// inverse of index_internal.
+	fn internal_index(i: u16) -> uvec3 {
+		let i = i as u32;
+		uvec3(i & Self::MASK, i >> (2 * Self::POW), (i >> Self::POW) & Self::MASK)
 	}
 
-	pub fn size(&self) -> uvec3 {
-		uvec3(Self::XZSIZE, self.height(), Self::XZSIZE)
+	// Upload meshes built for given version, unless the meshes in use are newer.
+	#[cfg(feature = "gl")]
+	fn upload(&self, version: u64, buffers: &[MeshBuffer]) {
+		if version <= self.mesh.meshed.get() {
+			return;
+		}
+		let mut meshes = ChunkMeshes {
+			opaque: Vec::new(),
+			transparent: Vec::new(),
+		};
+		for (i, builder) in buffers.iter().enumerate() {
+			if builder.len() != 0 {
+				let voxel = Voxel::from((i + 1) as u8); // inverse of tex_id
+				let m = builder.build();
+				match voxel.is_transparent() {
+					false => meshes.opaque.push((voxel, m)),
+					true => meshes.transparent.push((voxel, m)),
+				}
+			}
+		}
+		*(self.mesh.vaos.borrow_mut()) = Some(meshes);
+		self.mesh.meshed.set(version);
 	}
+}
 
-	pub fn height(&self) -> u32 {
-		((self.voxels.len() >> (2 * Self::POW)) + 1) as u32
+#[cfg(feature = "gl")]
+impl Default for ChunkMesh {
+	fn default() -> Self {
+		Self {
+			vaos: RefCell::new(None),
+			queued: Cell::new(0),
+			meshed: Cell::new(0),
+			lod: Cell::new(Lod::FULL),
+		}
 	}
 }
 
@@ -414,9 +552,24 @@ impl Default for Chunk {
 	}
 }
 
+// Cloning a Chunk copies the voxels but not the OpenGL Vertex Array cache,
+// which the clone will re-build if and when it is drawn.
+impl Clone for Chunk {
+	fn clone(&self) -> Self {
+		Self {
+			voxels: self.voxels.clone(),
+			num_solid: self.num_solid,
+			states: self.states.clone(),
+			version: Cell::new(1),
+			#[cfg(feature = "gl")]
+			mesh: ChunkMesh::default(),
+		}
+	}
+}
+
 impl Serialize for VoxelBox {
 	fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
-		VoxelData::from(&self).serialize(serializer)
+		VoxelData::from(self).map_err(serde::ser::Error::custom)?.serialize(serializer)
 	}
 }
 
@@ -432,6 +585,9 @@ impl<'de> Deserialize<'de> for VoxelBox {
 // Helper data struct to serialize/deserialize a Voxelbox
 // without having to use low-level serde functionality.
 // Also, encodes a sparse wold relatively efficiently.
+//
+// This is the legacy map format (superseded by `map_format.rs`),
+// it can only hold bounded worlds, and does not store voxel states.
 #[derive(Serialize, Deserialize)]
 struct VoxelData {
 	world_size: uvec3,
@@ -439,8 +595,11 @@ struct VoxelData {
 }
 
 impl VoxelData {
-	fn from(map: &VoxelBox) -> Self {
-		let world_size = map.world_size();
+	fn from(map: &VoxelBox) -> std::result::Result<Self, &'static str> {
+		if map.is_unbounded() {
+			return Err("legacy map format: unbounded maps are not supported");
+		}
+		let world_size = map.bounds().size().map(|v| v as u32);
 		let mut voxel_data = Vec::new();
 
 		for iz in 0..(world_size.z as usize) {
@@ -454,7 +613,7 @@ impl VoxelData {
 			}
 		}
 
-		Self { world_size, voxel_data }
+		Ok(Self { world_size, voxel_data })
 	}
 
 	fn into(self) -> VoxelBox {
@@ -479,9 +638,233 @@ mod test {
 		let mut c = Chunk::new();
 		assert_eq!(c.at_internal(uvec3(0, 0, 0)), Voxel::EMPTY);
 		assert_eq!(c.at_internal(uvec3(0, 1, 0)), Voxel::EMPTY);
-		assert_eq!(c.at_internal(uvec3(5, 99, 7)), Voxel::EMPTY);
-		c.set_internal(uvec3(5, 99, 7), Voxel::PLASMA);
-		assert_eq!(c.at_internal(uvec3(5, 99, 7)), Voxel::PLASMA);
+		assert_eq!(c.at_internal(uvec3(5, 9, 7)), Voxel::EMPTY);
+		c.set_internal(uvec3(5, 9, 7), Voxel::PLASMA);
+		assert_eq!(c.at_internal(uvec3(5, 9, 7)), Voxel::PLASMA);
+		assert_eq!(c.num_solid, 1);
+		c.set_internal(uvec3(5, 9, 7), Voxel::SNOW);
+		assert_eq!(c.num_solid, 1);
+		c.set_internal(uvec3(5, 9, 7), Voxel::EMPTY);
+		assert_eq!(c.num_solid, 0);
+	}
+
+	#[test]
+	fn sparse_chunks() {
+		let mut c = VoxelBox::new(uvec3(512, 256, 512));
+		assert_eq!(c.chunks.len(), 0);
+
+		c.set(ivec3(100, 200, 300), Voxel::SNOW);
+		c.set(ivec3(101, 201, 301), Voxel::SNOW);
+		assert_eq!(c.chunks.len(), 1);
+		assert!(c.chunks.contains_key(&ivec3(6, 12, 18)));
+
+		c.set(ivec3(0, 0, 0), Voxel::EMPTY); // no-op
+		assert_eq!(c.chunks.len(), 1);
+
+		c.set(ivec3(100, 200, 300), Voxel::EMPTY);
+		assert_eq!(c.chunks.len(), 1);
+		c.set(ivec3(101, 201, 301), Voxel::EMPTY);
+		assert_eq!(c.chunks.len(), 0); // empty chunks are freed
+	}
+
+	#[test]
+	fn unbounded() {
+		let mut c = VoxelBox::unbounded();
+		assert!(c.bounds().is_empty());
+		for &pos in &[ivec3(-1, -1, -1), ivec3(-16, 0, 5), ivec3(-17, -300, 1000)] {
+			c.set(pos, Voxel::SNOW);
+			assert_eq!(c.at(pos), Voxel::SNOW, "at {}", pos);
+		}
+		assert_eq!(c.at(ivec3(0, 0, 0)), Voxel::EMPTY);
+		assert_eq!(c.at(ivec3(-2, -1, -1)), Voxel::EMPTY);
+		assert!(c.chunks.contains_key(&ivec3(-1, -1, -1)));
+		assert!(c.chunks.contains_key(&ivec3(-2, -19, 62)));
+		assert_eq!(c.bounds(), VoxelBounds::new(ivec3(-17, -300, -1), ivec3(0, 1, 1001)));
+
+		// the legacy format cannot hold unbounded worlds.
+		assert!(serde_json::to_string(&c).is_err());
+	}
+
+	#[test]
+	fn states() {
+		let mut c = VoxelBox::unbounded();
+		let state = VoxelState { orientation: 3, damage: 1, data: 7 };
+		let pos = ivec3(-5, 17, 2);
+
+		c.set_state(pos, state); // empty voxels cannot have state
+		assert_eq!(c.state_at(pos), VoxelState::default());
+
+		c.set(pos, Voxel::GREYSTONE);
+		c.set_state(pos, state);
+		assert_eq!(c.state_at(pos), state);
+		assert_eq!(c.state_at(pos + ivec3(1, 0, 0)), VoxelState::default());
+		assert_eq!(c.states().collect::<Vec<_>>(), vec![(pos, state)]);
+
+		c.set_state(pos, VoxelState::default()); // default states are not stored
+		assert_eq!(c.states().count(), 0);
+
+		c.set_state(pos, state);
+		c.set(pos, Voxel::SNOW); // replacing the voxel resets its state
+		assert_eq!(c.state_at(pos), VoxelState::default());
+		assert_eq!(c.states().count(), 0);
+	}
+
+	#[test]
+	fn face_visible() {
+		let water = VoxelTypes::get().by_name("water").unwrap();
+		let glass = VoxelTypes::get().by_name("glass").unwrap();
+		assert!(VoxelSnapshot::face_visible(Voxel::SNOW, Voxel::EMPTY));
+		assert!(!VoxelSnapshot::face_visible(Voxel::SNOW, Voxel::LAVA));
+		assert!(VoxelSnapshot::face_visible(Voxel::SNOW, water)); // lake bottom
+		assert!(!VoxelSnapshot::face_visible(water, Voxel::SNOW));
+		assert!(!VoxelSnapshot::face_visible(water, water));
+		assert!(VoxelSnapshot::face_visible(water, glass)); // aquarium
+
+		let mut c = VoxelBox::new(uvec3(16, 16, 16));
+		c.set(ivec3(1, 1, 1), water);
+		c.set(ivec3(2, 1, 1), Voxel::SNOW);
+		assert!(!c.bumps(&BoundingBox::new(vec3(1.1, 1.1, 1.1), vec3(1.9, 1.9, 1.9))));
+		assert!(c.bumps(&BoundingBox::new(vec3(1.1, 1.1, 1.1), vec3(2.1, 1.9, 1.9))));
+	}
+
+	// Unit faces covered by mesh buffers (by tex_id): (tex_id, normal, position of the face's min corner).
+	// Each quad's bounding box (in the plane of the quad) is split into unit faces.
+	#[cfg(test)]
+	fn coverage(buffers: &[MeshBuffer]) -> Vec<(usize, ivec3, ivec3)> {
+		let mut faces = Vec::new();
+		for (tex_id, b) in buffers.iter().enumerate() {
+			for quad in b.vertex_positions().chunks(12) {
+				let (p0, p1, p2) = (quad[0], quad[1], quad[2]);
+				let normal = (p1 - p0).cross(p2 - p0).map(|v| v.signum() as i32 * (v.abs() > 1e-6) as i32);
+				let min = quad.iter().fold(quad[0], |a, &b| vec3(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z))).map(|v| v.round() as i32);
+				let max = quad.iter().fold(quad[0], |a, &b| vec3(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))).map(|v| v.round() as i32);
+				let max = max + normal.map(|v| v.abs()); // at least one unit thick, for the loops below.
+				for iz in min.z..max.z {
+					for iy in min.y..max.y {
+						for ix in min.x..max.x {
+							faces.push((tex_id, normal, ivec3(ix, iy, iz)));
+						}
+					}
+				}
+			}
+		}
+		faces.sort_by_key(|&(t, n, p)| (t, n.x, n.y, n.z, p.x, p.y, p.z));
+		faces
+	}
+
+	#[test]
+	fn greedy_meshing() {
+		let glass = VoxelTypes::get().by_name("glass").unwrap();
+		let mut c = VoxelBox::new(uvec3(64, 32, 64));
+		for iz in 0..64 {
+			for ix in 0..64 {
+				c.set(ivec3(ix, 0, iz), Voxel::SNOW); // plains
+				if (ix + iz) % 5 == 0 && ix < 30 {
+					c.set(ivec3(ix, 1, iz), Voxel::SAND); // bumps
+				}
+			}
+		}
+		for iy in 1..6 {
+			c.set(ivec3(20, iy, 40), Voxel::GREYSTONE); // pillar, casting a shadow
+			c.set(ivec3(21, iy, 41), glass);
+		}
+		c.set(ivec3(60, 1, 60), Voxel::LAVA);
+
+		for &chnk in c.chunks.keys() {
+			let naive = c.build_meshes(chnk, Lod::FULL, Meshing::Naive);
+			let greedy = c.build_meshes(chnk, Lod::FULL, Meshing::Greedy);
+			assert_eq!(coverage(&greedy), coverage(&naive), "chunk {}", chnk);
+		}
+
+		// A chunk of plain, away from the edges of the map and the bumps, has uniformly lit top and bottom faces:
+		// two quads.
+		let greedy = c.build_meshes(ivec3(2, 0, 2), Lod::FULL, Meshing::Greedy);
+		assert_eq!(greedy[Voxel::SNOW.tex_id()].len(), 2 * 12);
+		assert_eq!(greedy.iter().map(|b| b.len()).sum::<usize>(), 2 * 12);
+	}
+
+	#[test]
+	fn lod_meshing() {
+		let mut c = VoxelBox::new(uvec3(64, 32, 64));
+		for iz in 0..64 {
+			for ix in 0..64 {
+				let height = 3 + (ix / 3 + iz / 5) % 7; // hills
+				for iy in 0..height {
+					c.set(ivec3(ix, iy, iz), Voxel::SNOW);
+				}
+			}
+		}
+
+		// fewer triangles at lower detail.
+		let len = |b: &[MeshBuffer]| b.iter().map(|b| b.len()).sum::<usize>();
+		let chnk = ivec3(1, 0, 1);
+		let full = len(&c.build_meshes(chnk, Lod::FULL, Meshing::Greedy));
+		let half = len(&c.build_meshes(chnk, Lod(1), Meshing::Greedy));
+		let quarter = len(&c.build_meshes(chnk, Lod(2), Meshing::Greedy));
+		assert!(half < full, "{} < {}", half, full);
+		assert!(quarter < half, "{} < {}", quarter, half);
+
+		// No holes in the seam between a full detail chunk (x < 32) and a coarse one (x >= 32):
+		// from wherever the full detail side is open, looking at a coarse cell that is not empty,
+		// the coarse cell's face is there.
+		let coarse = coverage(&c.build_meshes(ivec3(2, 0, 1), Lod(2), Meshing::Greedy));
+		for iz in 16..32 {
+			for iy in 0..16 {
+				let cell = ivec3(32, iy & !3, iz & !3);
+				let cell_empty = (0..64).all(|i| c.at(cell + ivec3(i & 3, (i >> 2) & 3, i >> 4)) == Voxel::EMPTY);
+				if c.at(ivec3(31, iy, iz)) == Voxel::EMPTY && !cell_empty {
+					let pos = ivec3(32, iy, iz);
+					assert!(coarse.iter().any(|&(_, n, p)| n.x != 0 && p == pos), "hole at {}", pos);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn lod_distance() {
+		assert_eq!(Lod::for_distance(10.0, Lod::FULL), Lod::FULL);
+		assert_eq!(Lod::for_distance(70.0, Lod::FULL), Lod(1));
+		assert_eq!(Lod::for_distance(200.0, Lod::FULL), Lod(2));
+		assert_eq!(Lod::for_distance(10.0, Lod(2)), Lod::FULL);
+
+		// near a threshold, keep the current level.
+		assert_eq!(Lod::for_distance(49.0, Lod::FULL), Lod::FULL);
+		assert_eq!(Lod::for_distance(47.0, Lod(1)), Lod(1));
+		assert_eq!(Lod::for_distance(97.0, Lod(1)), Lod(1));
+		assert_eq!(Lod::for_distance(95.0, Lod(2)), Lod(2));
+	}
+
+	#[test]
+	fn visible_chunks() {
+		let mut c = VoxelBox::new(uvec3(256, 32, 256));
+		for iz in 0..16 {
+			for ix in 0..16 {
+				c.set(ivec3(ix * 16, 0, iz * 16), Voxel::SNOW); // one voxel per chunk
+			}
+		}
+
+		// camera in chunk (8, 0, 8), looking down -z.
+		let view_pos = vec3(136.0, 8.0, 136.0);
+		let frustum = Frustum::for_camera((800, 600), view_pos, 0.0, 0.0);
+		let visible = c.visible_chunks(&frustum, view_pos, 64.0);
+		assert!(!visible.is_empty());
+		for &(dist, chnk) in &visible {
+			assert!(dist <= 64.0);
+			assert!(chnk.z <= 8, "behind the camera: {}", chnk);
+		}
+		assert!(visible.iter().any(|&(_, chnk)| chnk == ivec3(8, 0, 7))); // right in front
+		assert!(visible.iter().any(|&(_, chnk)| chnk == ivec3(8, 0, 8))); // the camera's own chunk
+		assert!(!visible.iter().any(|&(_, chnk)| chnk == ivec3(8, 0, 10))); // behind
+		assert!(!visible.iter().any(|&(_, chnk)| chnk == ivec3(8, 0, 2))); // fogged
+
+		// nearest first
+		for w in visible.windows(2) {
+			assert!(w[0].0 <= w[1].0);
+		}
+
+		// a chunk just beyond view distance (by its center), but with voxels within view distance.
+		let visible = c.visible_chunks(&frustum, view_pos, 10.0);
+		assert!(visible.iter().any(|&(_, chnk)| chnk == ivec3(8, 0, 7)));
 	}
 
 	#[test]
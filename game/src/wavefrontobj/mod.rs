@@ -5,7 +5,7 @@ use std::io::{BufRead, BufReader, Read};
 /// Parse a Wavefront OBJ file. See
 /// https://en.wikipedia.org/wiki/Wavefront_.obj_file
 pub fn parse_file(fname: &Path) -> Result<MeshBuffer> {
-	println!("loading {}", fname.to_string_lossy());
+	debug!("loading {}", fname.to_string_lossy());
 	parse(File::open(fname)?)
 }
 
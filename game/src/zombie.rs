@@ -32,6 +32,7 @@ impl Zombie {
 		}
 	}
 
+	#[cfg(feature = "gl")]
 	pub fn draw(&self, ctx: &GLContext) {
 		self.model.draw_third_person(ctx);
 	}
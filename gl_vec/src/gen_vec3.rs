@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Display;
+use std::hash::{Hash, Hasher};
 use std::ops::*;
 
 #[derive(Clone, Copy, Serialize, Deserialize)]
@@ -53,6 +54,20 @@ where
 	}
 }
 
+impl<T> Eq for gvec3<T> where T: Eq + Copy {}
+
+impl<T> Hash for gvec3<T>
+where
+	T: Hash,
+{
+	#[inline]
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.x.hash(state);
+		self.y.hash(state);
+		self.z.hash(state);
+	}
+}
+
 impl<T> Add for gvec3<T>
 where
 	T: Add<T, Output = T> + Copy,
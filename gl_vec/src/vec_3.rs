@@ -10,9 +10,16 @@ pub const fn vec3(x: f32, y: f32, z: f32) -> vec3 {
 }
 
 impl vec3 {
+	/// Convert to integer, rounding towards zero.
 	pub fn to_ivec(self) -> ivec3 {
 		self.map(|v| v as i32)
 	}
+
+	/// Convert to integer, rounding down
+	/// (unlike `to_ivec` for negative numbers).
+	pub fn floor_ivec(self) -> ivec3 {
+		self.map(|v| v.floor() as i32)
+	}
 }
 
 impl Mul<vec3> for f32 {